@@ -0,0 +1,137 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// On-disk model catalog stored next to the links (`.ggufy-catalog.json`).
+// Entries are keyed by path and invalidated by size + mtime, so a file is only
+// opened again when it changed, and failed probes and parses are remembered
+// the same way. Directory listings are still walked (stat only) on every
+// command; only the per-file GGUF probe and header parse are cached.
+
+use crate::gguf::{self, GgufSummary};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Result as IoResult;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const CATALOG_FILE: &str = ".ggufy-catalog.json";
+const CATALOG_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub size: u64,
+    pub mtime: u64,
+    pub is_gguf: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<GgufSummary>,
+    // The header failed to parse; not retried until size or mtime change.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unreadable: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CatalogFile {
+    version: u32,
+    entries: BTreeMap<String, CatalogEntry>,
+}
+
+pub struct Catalog {
+    path: PathBuf,
+    entries: BTreeMap<String, CatalogEntry>,
+    dirty: bool,
+}
+
+impl Catalog {
+    pub fn load(models_dir: &Path) -> Catalog {
+        let path = models_dir.join(CATALOG_FILE);
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<CatalogFile>(&s).ok())
+            .filter(|c| c.version == CATALOG_VERSION)
+            .map(|c| c.entries)
+            .unwrap_or_default();
+        Catalog {
+            path,
+            entries,
+            dirty: false,
+        }
+    }
+
+    // Starts from nothing but still writes the result, i.e. a full rescan.
+    pub fn empty(models_dir: &Path) -> Catalog {
+        Catalog {
+            path: models_dir.join(CATALOG_FILE),
+            entries: BTreeMap::new(),
+            dirty: true,
+        }
+    }
+
+    fn entry(&mut self, p: &Path) -> Option<&mut CatalogEntry> {
+        let md = fs::metadata(p).ok()?;
+        let size = md.len();
+        let mtime = md
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        let key = p.to_string_lossy().to_string();
+        let fresh = matches!(self.entries.get(&key), Some(e) if e.size == size && e.mtime == mtime);
+        if !fresh {
+            let is_gguf = gguf::is_gguf_file(p).unwrap_or(false);
+            self.entries.insert(
+                key.clone(),
+                CatalogEntry {
+                    size,
+                    mtime,
+                    is_gguf,
+                    summary: None,
+                    unreadable: false,
+                },
+            );
+            self.dirty = true;
+        }
+        self.entries.get_mut(&key)
+    }
+
+    pub fn is_gguf(&mut self, p: &Path) -> bool {
+        self.entry(p).map(|e| e.is_gguf).unwrap_or(false)
+    }
+
+    pub fn summary(&mut self, p: &Path) -> Option<GgufSummary> {
+        let e = self.entry(p)?;
+        if !e.is_gguf || e.unreadable {
+            return None;
+        }
+        if e.summary.is_none() {
+            e.summary = gguf::read_gguf(p).ok().map(|g| g.summary());
+            e.unreadable = e.summary.is_none();
+            self.dirty = true;
+        }
+        self.entries
+            .get(&p.to_string_lossy().to_string())
+            .and_then(|e| e.summary.clone())
+    }
+
+    // Drops entries for files that no longer exist and writes atomically.
+    pub fn save(&mut self) -> IoResult<()> {
+        let before = self.entries.len();
+        self.entries.retain(|k, _| Path::new(k).exists());
+        if !self.dirty && before == self.entries.len() {
+            return Ok(());
+        }
+        let file = CatalogFile {
+            version: CATALOG_VERSION,
+            entries: std::mem::take(&mut self.entries),
+        };
+        let body = serde_json::to_string(&file).map_err(std::io::Error::other);
+        self.entries = file.entries;
+        let tmp = self
+            .path
+            .with_extension(format!("json.{}", std::process::id()));
+        fs::write(&tmp, body?)?;
+        fs::rename(&tmp, &self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
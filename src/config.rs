@@ -0,0 +1,502 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Launch defaults from `~/.config/ggufy/config.toml` and the nearest
+// project-local `.ggufy.toml` (or `ggufy.toml`), which is applied on top.
+//
+//   [defaults]
+//   ctx_size = 8192
+//   n_gpu_layers = 0
+//
+//   [models."qwen2.5-*"]
+//   ctx_size = 32768
+//   temp = 0.7
+//
+// Model patterns are globs (`*`, `?`) matched against the model name as typed
+// and against the GGUF file name and stem. Presets are layered: defaults,
+// then matching model entries from least to most specific (fewest to most
+// literal characters), the project file winning ties.
+//
+// Named profiles are selected with `--profile` and applied last:
+//
+//   [profiles.low-mem]
+//   ctx_size = 4096
+//   n_gpu_layers = 0
+//   prefer = "local"
+//   env = { GGML_CUDA_NO_PINNED = "1" }
+//
+// A profile defined in both files is merged field by field, project winning.
+// A project file comes with whatever directory ggufy runs in, so it may not
+// set `env` or `args` (which reach every launched process) unless the global
+// file says `trust_project_config = true`.
+//
+// `ollama_roots = ["/srv/ollama/models"]` adds Ollama model stores to search.
+//
+// `pin = true` and `idle_minutes = 30` in a model entry tune how `ggufy
+// gateway` keeps that model's server loaded.
+
+use crate::error::{GgufyError, Result};
+use dirs::home_dir;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ctx_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_gpu_layers: Option<i32>,
+    // Built-in template name (`--chat-template`) or a Jinja file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_template_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mmproj: Option<PathBuf>,
+    // LoRA adapters; like `args`, these accumulate across layers.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lora: Vec<PathBuf>,
+    // Used when --port is not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temp: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    // llama-cli chat only (`--reverse-prompt`); llama-server takes stop
+    // words per request. Accumulates like `lora`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    // `ggufy gateway` only: never unload this model, and unload it after
+    // this many idle minutes (overriding `--idle-minutes`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_minutes: Option<f64>,
+    // Passed through verbatim after the flags above.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+}
+
+impl Preset {
+    // Fields set in `over` replace ours; `args` accumulate.
+    pub fn merge(&mut self, over: &Preset) {
+        macro_rules! take {
+            ($($f:ident),*) => {
+                $(if over.$f.is_some() {
+                    self.$f = over.$f.clone();
+                })*
+            };
+        }
+        take!(
+            ctx_size,
+            threads,
+            batch_size,
+            n_gpu_layers,
+            chat_template,
+            chat_template_file,
+            mmproj,
+            port,
+            temp,
+            top_p,
+            top_k,
+            min_p,
+            repeat_penalty,
+            seed,
+            pin,
+            idle_minutes
+        );
+        self.lora.extend(over.lora.iter().cloned());
+        self.stop.extend(over.stop.iter().cloned());
+        self.args.extend(over.args.iter().cloned());
+    }
+
+    // llama-server and llama-cli share these flags. The port is not
+    // included; it goes through port selection instead, nor are `stop` and
+    // the gateway settings.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut v = Vec::new();
+        let mut flag = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                v.push(name.to_string());
+                v.push(value);
+            }
+        };
+        flag("--ctx-size", self.ctx_size.map(|x| x.to_string()));
+        flag("--threads", self.threads.map(|x| x.to_string()));
+        flag("--batch-size", self.batch_size.map(|x| x.to_string()));
+        flag("--n-gpu-layers", self.n_gpu_layers.map(|x| x.to_string()));
+        flag("--chat-template", self.chat_template.clone());
+        flag(
+            "--chat-template-file",
+            self.chat_template_file.as_deref().map(expand_home),
+        );
+        flag("--mmproj", self.mmproj.as_deref().map(expand_home));
+        for lora in &self.lora {
+            flag("--lora", Some(expand_home(lora)));
+        }
+        flag("--temp", self.temp.map(|x| x.to_string()));
+        flag("--top-p", self.top_p.map(|x| x.to_string()));
+        flag("--top-k", self.top_k.map(|x| x.to_string()));
+        flag("--min-p", self.min_p.map(|x| x.to_string()));
+        flag(
+            "--repeat-penalty",
+            self.repeat_penalty.map(|x| x.to_string()),
+        );
+        flag("--seed", self.seed.map(|x| x.to_string()));
+        v.extend(self.args.iter().cloned());
+        v
+    }
+}
+
+// Whether `run`/`simple` try the local GGUF or Ollama's cloud model first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Route {
+    Local,
+    Cloud,
+}
+
+impl Route {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Route::Local => "local",
+            Route::Cloud => "cloud",
+        }
+    }
+}
+
+// Parsed through a table so unknown keys are still rejected; serde's
+// `flatten` would silently drop them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "toml::Table")]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefer: Option<Route>,
+    // Set on every process ggufy launches while the profile is active.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub preset: Preset,
+}
+
+impl TryFrom<toml::Table> for Profile {
+    type Error = String;
+
+    fn try_from(mut t: toml::Table) -> std::result::Result<Self, String> {
+        let prefer = t
+            .remove("prefer")
+            .map(|v| {
+                v.try_into()
+                    .map_err(|e: toml::de::Error| e.message().to_string())
+            })
+            .transpose()?;
+        let env = t
+            .remove("env")
+            .map(|v| {
+                v.try_into()
+                    .map_err(|e: toml::de::Error| e.message().to_string())
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let preset = toml::Value::Table(t)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().to_string())?;
+        Ok(Profile {
+            prefer,
+            env,
+            preset,
+        })
+    }
+}
+
+impl Profile {
+    fn merge(&mut self, over: &Profile) {
+        if over.prefer.is_some() {
+            self.prefer = over.prefer;
+        }
+        self.env
+            .extend(over.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.preset.merge(&over.preset);
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    // Extra Ollama model stores; relative paths are taken from the file's dir.
+    ollama_roots: Vec<PathBuf>,
+    defaults: Preset,
+    models: BTreeMap<String, Preset>,
+    profiles: BTreeMap<String, Profile>,
+    // Global file only: let project files set `env` and `args`.
+    trust_project_config: bool,
+}
+
+impl ConfigFile {
+    // What a project file may not set on its own.
+    fn check_untrusted(&self, trusted: bool) -> std::result::Result<(), String> {
+        if self.trust_project_config {
+            return Err("trust_project_config only counts in the global config".to_string());
+        }
+        if trusted {
+            return Ok(());
+        }
+        let mut presets: Vec<(String, &Preset)> = vec![("[defaults]".to_string(), &self.defaults)];
+        presets.extend(
+            self.models
+                .iter()
+                .map(|(k, p)| (format!("[models.\"{}\"]", k), p)),
+        );
+        for (name, profile) in &self.profiles {
+            let table = format!("[profiles.{}]", name);
+            if !profile.env.is_empty() {
+                return Err(refused("env", &table));
+            }
+            presets.push((table, &profile.preset));
+        }
+        match presets.iter().find(|(_, p)| !p.args.is_empty()) {
+            Some((table, _)) => Err(refused("args", table)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn refused(key: &str, table: &str) -> String {
+    format!(
+        "{} in {} is only honoured in a project config when the global config sets trust_project_config = true",
+        key, table
+    )
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    // Global file first, project file last.
+    files: Vec<(PathBuf, ConfigFile)>,
+    profile: Option<(String, Profile)>,
+}
+
+// The merged preset for one model and where each layer came from.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Resolved {
+    pub sources: Vec<PathBuf>,
+    pub matched: Vec<String>,
+    pub preset: Preset,
+    // From the active profile, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefer: Option<Route>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn load() -> Result<Config> {
+        let global = global_config_path().filter(|p| p.is_file());
+        let project = project_config_path().filter(|p| Some(p) != global.as_ref());
+        let paths: Vec<PathBuf> = global.iter().chain(&project).cloned().collect();
+        let config = Config::from_files(&paths)?;
+        if let Some((path, file)) = project.and(config.files.last()) {
+            let trusted = global.is_some() && config.files[0].1.trust_project_config;
+            file.check_untrusted(trusted)
+                .map_err(|message| GgufyError::Config {
+                    path: path.clone(),
+                    message,
+                })?;
+        }
+        Ok(config)
+    }
+
+    pub fn from_files(paths: &[PathBuf]) -> Result<Config> {
+        let mut files = Vec::new();
+        for path in paths {
+            if files.iter().any(|(p, _)| p == path) {
+                continue;
+            }
+            let text = fs::read_to_string(path)?;
+            let file: ConfigFile = toml::from_str(&text).map_err(|e| GgufyError::Config {
+                path: path.clone(),
+                message: e.message().to_string(),
+            })?;
+            for pattern in file.models.keys() {
+                glob_regex(pattern).map_err(|message| GgufyError::Config {
+                    path: path.clone(),
+                    message,
+                })?;
+            }
+            files.push((path.clone(), file));
+        }
+        Ok(Config {
+            files,
+            profile: None,
+        })
+    }
+
+    // Makes `name` the active profile; it must be defined in some file.
+    pub fn select_profile(&mut self, name: &str) -> Result<()> {
+        let mut found: Option<Profile> = None;
+        for (_, file) in &self.files {
+            if let Some(p) = file.profiles.get(name) {
+                found.get_or_insert_with(Profile::default).merge(p);
+            }
+        }
+        let profile = found.ok_or_else(|| {
+            let known = self.profile_names();
+            GgufyError::Usage(if known.is_empty() {
+                format!("unknown profile {:?}: no profiles are defined", name)
+            } else {
+                format!("unknown profile {:?} (defined: {})", name, known.join(", "))
+            })
+        })?;
+        self.profile = Some((name.to_string(), profile));
+        Ok(())
+    }
+
+    pub fn ollama_roots(&self) -> Vec<PathBuf> {
+        let mut roots = Vec::new();
+        for (path, file) in &self.files {
+            let base = path.parent().unwrap_or(Path::new("."));
+            for root in &file.ollama_roots {
+                roots.push(base.join(expand_home(root)));
+            }
+        }
+        roots
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .files
+            .iter()
+            .flat_map(|(_, f)| f.profiles.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn profile(&self) -> Option<&(String, Profile)> {
+        self.profile.as_ref()
+    }
+
+    pub fn prefer(&self) -> Option<Route> {
+        self.profile.as_ref().and_then(|(_, p)| p.prefer)
+    }
+
+    pub fn env(&self) -> Vec<(String, String)> {
+        self.profile
+            .as_ref()
+            .map(|(_, p)| p.env.clone().into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn sources(&self) -> Vec<PathBuf> {
+        self.files.iter().map(|(p, _)| p.clone()).collect()
+    }
+
+    pub fn resolve(&self, name: &str, path: Option<&Path>) -> Resolved {
+        let mut candidates = vec![name.to_string()];
+        if let Some(p) = path {
+            for part in [p.file_name(), p.file_stem()].into_iter().flatten() {
+                candidates.push(part.to_string_lossy().to_string());
+            }
+        }
+        let mut out = Resolved {
+            sources: self.sources(),
+            ..Resolved::default()
+        };
+        for (path, file) in &self.files {
+            if file.defaults != Preset::default() {
+                out.preset.merge(&file.defaults);
+                out.matched.push(format!("{}: [defaults]", path.display()));
+            }
+        }
+        let mut hits: Vec<(usize, usize, &PathBuf, &String, &Preset)> = Vec::new();
+        for (order, (path, file)) in self.files.iter().enumerate() {
+            for (pattern, preset) in &file.models {
+                let re = match glob_regex(pattern) {
+                    Ok(re) => re,
+                    Err(_) => continue,
+                };
+                if candidates.iter().any(|c| re.is_match(c)) {
+                    let literal = pattern.chars().filter(|c| *c != '*' && *c != '?').count();
+                    hits.push((literal, order, path, pattern, preset));
+                }
+            }
+        }
+        hits.sort_by_key(|h| (h.0, h.1));
+        for (_, _, path, pattern, preset) in hits {
+            out.preset.merge(preset);
+            out.matched
+                .push(format!("{}: [models.\"{}\"]", path.display(), pattern));
+        }
+        if let Some((name, profile)) = &self.profile {
+            out.preset.merge(&profile.preset);
+            out.prefer = profile.prefer;
+            out.env = profile.env.clone();
+            out.matched.push(format!("profile {}", name));
+        }
+        out
+    }
+}
+
+// $GGUFY_CONFIG, else $XDG_CONFIG_HOME/ggufy/config.toml, else
+// ~/.config/ggufy/config.toml (on every platform, to keep dotfiles portable).
+pub fn global_config_path() -> Option<PathBuf> {
+    if let Ok(p) = std::env::var("GGUFY_CONFIG") {
+        return Some(PathBuf::from(p));
+    }
+    if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
+        if !dir.is_empty() {
+            return Some(PathBuf::from(dir).join("ggufy/config.toml"));
+        }
+    }
+    home_dir().map(|h| h.join(".config/ggufy/config.toml"))
+}
+
+// Nearest `.ggufy.toml` or `ggufy.toml` from the current dir upwards.
+pub fn project_config_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    for dir in cwd.ancestors() {
+        for name in [".ggufy.toml", "ggufy.toml"] {
+            let p = dir.join(name);
+            if p.is_file() {
+                return Some(p);
+            }
+        }
+    }
+    None
+}
+
+fn glob_regex(pattern: &str) -> std::result::Result<Regex, String> {
+    let mut re = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).map_err(|e| format!("bad model pattern {:?}: {}", pattern, e))
+}
+
+pub fn expand_home(p: &Path) -> String {
+    match (p.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest).display().to_string(),
+        _ => p.display().to_string(),
+    }
+}
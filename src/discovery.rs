@@ -0,0 +1,471 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Where models live on disk: the ggufy links dir, the llama.cpp cache, the
+// Hugging Face hub cache and Ollama's manifests/blobs.
+
+use crate::catalog::Catalog;
+use crate::error::{GgufyError, Result};
+use crate::gguf;
+use crate::output::{ModelRecord, Source};
+use crate::resolve::resolve_ollama_model_with;
+use dirs::home_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+pub fn ensure_models_dir(link_override: Option<&PathBuf>) -> Result<()> {
+    let path = ggufy_models_dir_with(link_override);
+    fs::create_dir_all(&path).map_err(|source| GgufyError::ModelsDir { path, source })
+}
+
+pub fn ggufy_models_dir_with(link_override: Option<&PathBuf>) -> PathBuf {
+    if let Some(o) = link_override {
+        return o.clone();
+    }
+    let mut p = home_dir().expect("home");
+    p.push(".guffy/models");
+    p
+}
+
+pub fn llama_cache_dir() -> PathBuf {
+    if let Ok(override_dir) = std::env::var("LLAMA_CPP_CACHE_DIR") {
+        return PathBuf::from(override_dir);
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mut p = home_dir().expect("home");
+        p.push("Library/Caches/llama.cpp");
+        p
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let mut p = home_dir().expect("home");
+        p.push(".cache/llama.cpp");
+        p
+    }
+    #[cfg(target_os = "windows")]
+    {
+        if let Ok(local_app) = std::env::var("LOCALAPPDATA") {
+            let mut p = PathBuf::from(local_app);
+            p.push("llama.cpp");
+            p
+        } else {
+            PathBuf::from("C:/Users/Public/AppData/Local/llama.cpp")
+        }
+    }
+}
+
+pub fn ollama_models_roots() -> Vec<PathBuf> {
+    ollama_models_roots_with(&[])
+}
+
+// Ollama model stores, each holding `manifests/` and `blobs/`, in lookup
+// order: $OLLAMA_MODELS, `extra` (--ollama-root and `ollama_roots` in the
+// config), ~/.ollama/models and, on Linux, the systemd service's home.
+// Missing and duplicate roots are dropped.
+pub fn ollama_models_roots_with(extra: &[PathBuf]) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Ok(dir) = std::env::var("OLLAMA_MODELS") {
+        if !dir.is_empty() {
+            candidates.push(PathBuf::from(dir));
+        }
+    }
+    candidates.extend(extra.iter().cloned());
+    if let Some(home) = home_dir() {
+        candidates.push(home.join(".ollama/models"));
+    }
+    #[cfg(target_os = "linux")]
+    candidates.push(PathBuf::from("/usr/share/ollama/.ollama/models"));
+
+    let mut seen = Vec::new();
+    let mut roots = Vec::new();
+    for dir in candidates {
+        if !dir.is_dir() {
+            continue;
+        }
+        let key = fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone());
+        if !seen.contains(&key) {
+            seen.push(key);
+            roots.push(dir);
+        }
+    }
+    roots
+}
+
+pub fn ollama_blobs_dir(root: &Path) -> PathBuf {
+    root.join("blobs")
+}
+
+pub fn ollama_manifests_dir(root: &Path) -> PathBuf {
+    root.join("manifests")
+}
+
+const OLLAMA_REGISTRY: &str = "registry.ollama.ai";
+
+// Expands a name the way Ollama does: `model` is registry.ollama.ai/library,
+// `user/model` lives on registry.ollama.ai, and anything longer names its own
+// host (`hf.co/owner/repo`, `my.registry:5000/team/model`).
+pub fn ollama_manifest_path(root: &Path, name: &str, tag: &str) -> Option<PathBuf> {
+    let parts: Vec<&str> = name.split('/').collect();
+    if parts
+        .iter()
+        .any(|p| p.is_empty() || *p == "." || *p == "..")
+    {
+        return None;
+    }
+    let (host, namespace, model) = match parts.as_slice() {
+        [model] => (OLLAMA_REGISTRY, "library", *model),
+        [namespace, model] => (OLLAMA_REGISTRY, *namespace, *model),
+        [host, namespace, model] => (*host, *namespace, *model),
+        _ => return None,
+    };
+    Some(
+        ollama_manifests_dir(root)
+            .join(host)
+            .join(namespace)
+            .join(model)
+            .join(tag),
+    )
+}
+
+// The inverse of `ollama_manifest_path`: the shortest name Ollama accepts.
+fn ollama_short_name(host: &str, namespace: &str, model: &str) -> String {
+    match (host, namespace) {
+        (OLLAMA_REGISTRY, "library") => model.to_string(),
+        (OLLAMA_REGISTRY, _) => format!("{}/{}", namespace, model),
+        _ => format!("{}/{}/{}", host, namespace, model),
+    }
+}
+
+// `gemma3-4b.gguf`, `hf.co_bartowski_Foo-GGUF-Q4_K_M.gguf`.
+pub fn ollama_link_name(name: &str, tag: &str) -> String {
+    format!("{}-{}.gguf", name.replace('/', "_"), tag)
+}
+
+pub fn find_llama_cache_models() -> Vec<PathBuf> {
+    let root = llama_cache_dir();
+    let mut v = Vec::new();
+    if root.exists() {
+        for e in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+            if e.file_type().is_file() {
+                let p = e.path().to_path_buf();
+                if p.extension().map(|x| x == "gguf").unwrap_or(false) {
+                    v.push(p);
+                }
+            }
+        }
+    }
+    v
+}
+
+pub fn find_ollama_blob_gguf(roots: &[PathBuf], catalog: &mut Catalog) -> Vec<PathBuf> {
+    let mut v = Vec::new();
+    for root in roots {
+        let blobs = ollama_blobs_dir(root);
+        if !blobs.exists() {
+            continue;
+        }
+        for e in WalkDir::new(blobs)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if e.file_type().is_file() {
+                let p = e.path().to_path_buf();
+                if catalog.is_gguf(&p) {
+                    v.push(p);
+                }
+            }
+        }
+    }
+    v
+}
+
+pub fn hf_hub_cache_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("HF_HUB_CACHE") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Ok(home) = std::env::var("HF_HOME") {
+        return Some(PathBuf::from(home).join("hub"));
+    }
+    home_dir().map(|h| h.join(".cache/huggingface/hub"))
+}
+
+// GGUF files in the huggingface_hub cache, keyed by `owner/repo`.
+// Layout: models--owner--repo/snapshots/<revision>/<file>.gguf
+pub fn find_hf_hub_models() -> Vec<(String, PathBuf)> {
+    let root = match hf_hub_cache_dir() {
+        Some(r) if r.exists() => r,
+        _ => return Vec::new(),
+    };
+    let mut v = Vec::new();
+    for e in WalkDir::new(&root)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let p = e.path();
+        if !e.file_type().is_file() || p.extension().map(|x| x != "gguf").unwrap_or(true) {
+            continue;
+        }
+        let rel = match p.strip_prefix(&root) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        let mut comps = rel.components().map(|c| c.as_os_str().to_string_lossy());
+        let repo_dir = comps.next().unwrap_or_default();
+        if comps.next().as_deref() != Some("snapshots") {
+            continue;
+        }
+        if let Some(repo) = repo_dir.strip_prefix("models--") {
+            v.push((repo.replacen("--", "/", 1), p.to_path_buf()));
+        }
+    }
+    v
+}
+
+// Mirrors llama.cpp's own cache naming: owner_repo_file.gguf
+pub fn hf_hub_link_name(repo: &str, p: &Path) -> String {
+    let file = p.file_name().unwrap().to_string_lossy();
+    format!("{}_{}", repo.replace('/', "_"), file)
+}
+
+// Every `host/namespace/model/tag` manifest, as (name, tag) with the name
+// shortened like `ollama list` does. A model:tag present in several roots is
+// listed once; resolution uses the first root that has it.
+pub fn enumerate_ollama_library_models(roots: &[PathBuf]) -> Vec<(String, String)> {
+    let mut out: Vec<(String, String)> = Vec::new();
+    for root in roots {
+        let manifests = ollama_manifests_dir(root);
+        for e in WalkDir::new(&manifests)
+            .min_depth(4)
+            .max_depth(4)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if !e.file_type().is_file() {
+                continue;
+            }
+            let rel = match e.path().strip_prefix(&manifests) {
+                Ok(r) => r,
+                Err(_) => continue,
+            };
+            let parts: Vec<&str> = match rel.iter().map(|c| c.to_str()).collect() {
+                Some(p) => p,
+                None => continue,
+            };
+            if let [host, namespace, model, tag] = parts.as_slice() {
+                let entry = (ollama_short_name(host, namespace, model), tag.to_string());
+                if !out.contains(&entry) {
+                    out.push(entry);
+                }
+            }
+        }
+    }
+    out
+}
+
+// A projector shipped next to a model: `mmproj*.gguf` in the same directory
+// (HF snapshots), or `<prefix>mmproj*.gguf` where the model file also starts
+// with `<prefix>` (the flat llama.cpp cache, `owner_repo_mmproj-f16.gguf`).
+// Candidates must be `clip` GGUFs; the longest shared prefix wins.
+pub fn find_mmproj_with(
+    model: &Path,
+    mut is_projector: impl FnMut(&Path) -> bool,
+) -> Option<PathBuf> {
+    let model = fs::canonicalize(model).ok()?;
+    let model_name = model.file_name()?.to_string_lossy().to_lowercase();
+    if model_name.contains("mmproj") {
+        return None;
+    }
+    let mut best: Option<(usize, PathBuf)> = None;
+    let mut entries: Vec<PathBuf> = fs::read_dir(model.parent()?)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+    for p in entries {
+        let name = match p.file_name() {
+            Some(n) => n.to_string_lossy().to_lowercase(),
+            None => continue,
+        };
+        let prefix = match name.find("mmproj") {
+            Some(i) if name.ends_with(".gguf") => &name[..i],
+            _ => continue,
+        };
+        if !model_name.starts_with(prefix) || !p.is_file() || !is_projector(&p) {
+            continue;
+        }
+        if best
+            .as_ref()
+            .map(|(len, _)| prefix.len() > *len)
+            .unwrap_or(true)
+        {
+            best = Some((prefix.len(), p));
+        }
+    }
+    best.map(|(_, p)| p)
+}
+
+pub fn find_mmproj(model: &Path) -> Option<PathBuf> {
+    find_mmproj_with(model, |p| {
+        gguf::read_gguf(p)
+            .map(|g| !g.projector_modalities().is_empty())
+            .unwrap_or(false)
+    })
+}
+
+// `find_mmproj`, with the projector check served from the catalog.
+pub fn catalog_mmproj(model: &Path, catalog: &mut Catalog) -> Option<PathBuf> {
+    find_mmproj_with(model, |p| {
+        catalog
+            .summary(p)
+            .map(|s| !s.projector.is_empty())
+            .unwrap_or(false)
+    })
+}
+
+const VOCODER_NAMES: &[&str] = &["wavtokenizer", "vocoder"];
+
+// A vocoder for a TTS model: the first `wavtokenizer-dec`-style GGUF next to
+// the model, then in the llama.cpp cache.
+pub fn find_vocoder(model: &Path) -> Option<PathBuf> {
+    let model = fs::canonicalize(model).ok()?;
+    let mut dirs = vec![model.parent()?.to_path_buf()];
+    dirs.push(llama_cache_dir());
+    for dir in dirs {
+        let mut entries: Vec<PathBuf> = match fs::read_dir(&dir) {
+            Ok(rd) => rd.filter_map(|e| e.ok().map(|e| e.path())).collect(),
+            Err(_) => continue,
+        };
+        entries.sort();
+        // Only files named like a vocoder are parsed; the metadata check
+        // then confirms it.
+        let found = entries.into_iter().find(|p| {
+            p.extension().is_some_and(|x| x == "gguf")
+                && p.file_name().is_some_and(|n| {
+                    let n = n.to_string_lossy().to_lowercase();
+                    VOCODER_NAMES.iter().any(|v| n.contains(v))
+                })
+                && gguf::read_gguf(p).is_ok_and(|g| g.kind() == gguf::ModelKind::Vocoder)
+        });
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+pub fn find_hf_cached_gguf(repo: &str) -> Option<PathBuf> {
+    let parts: Vec<&str> = repo.split('/').collect();
+    if parts.len() != 2 {
+        return None;
+    }
+    let owner = parts[0];
+    let name = parts[1];
+    let root = llama_cache_dir();
+    if !root.exists() {
+        return None;
+    }
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    for e in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        if e.file_type().is_file() {
+            let p = e.path();
+            if p.extension().map(|x| x == "gguf").unwrap_or(false) {
+                let s = p.to_string_lossy();
+                let file = p.file_name().unwrap().to_string_lossy().to_lowercase();
+                if s.contains(owner) && s.contains(name) && !file.contains("mmproj") {
+                    candidates.push(p.to_path_buf());
+                }
+            }
+        }
+    }
+    candidates.sort_by_key(|p| fs::metadata(p).map(|m| m.modified().ok()).ok().flatten());
+    candidates.pop()
+}
+
+pub fn llama_cache_records(link_dir: &Path, catalog: &mut Catalog) -> Vec<ModelRecord> {
+    find_llama_cache_models()
+        .into_iter()
+        .map(|p| {
+            let name = p.file_stem().unwrap().to_string_lossy().to_string();
+            let link = link_dir.join(p.file_name().unwrap());
+            let record = ModelRecord::new(
+                Source::LlamaCache,
+                name,
+                None,
+                &p,
+                link,
+                catalog.summary(&p),
+            );
+            with_catalog_mmproj(record, catalog)
+        })
+        .collect()
+}
+
+fn with_catalog_mmproj(record: ModelRecord, catalog: &mut Catalog) -> ModelRecord {
+    let mmproj = catalog_mmproj(&record.path, catalog);
+    let summary = mmproj.as_deref().and_then(|p| catalog.summary(p));
+    record.with_mmproj(mmproj, summary)
+}
+
+pub fn hf_hub_records(link_dir: &Path, catalog: &mut Catalog) -> Vec<ModelRecord> {
+    find_hf_hub_models()
+        .into_iter()
+        .map(|(repo, p)| {
+            let tag = p.file_stem().map(|s| s.to_string_lossy().to_string());
+            let link = link_dir.join(hf_hub_link_name(&repo, &p));
+            let record =
+                ModelRecord::new(Source::HfCache, repo, tag, &p, link, catalog.summary(&p));
+            with_catalog_mmproj(record, catalog)
+        })
+        .collect()
+}
+
+pub fn ollama_blob_records(
+    link_dir: &Path,
+    roots: &[PathBuf],
+    catalog: &mut Catalog,
+) -> Vec<ModelRecord> {
+    find_ollama_blob_gguf(roots, catalog)
+        .into_iter()
+        .map(|p| {
+            let name = p.file_name().unwrap().to_string_lossy().to_string();
+            let link = link_dir.join(&name);
+            ModelRecord::new(
+                Source::OllamaBlob,
+                name,
+                None,
+                &p,
+                link,
+                catalog.summary(&p),
+            )
+        })
+        .collect()
+}
+
+// Manifest errors are skipped here; callers that link the same models report
+// them.
+pub fn ollama_library_records(
+    link_dir: &Path,
+    roots: &[PathBuf],
+    catalog: &mut Catalog,
+) -> Vec<ModelRecord> {
+    let mut v = Vec::new();
+    for (name, tag) in enumerate_ollama_library_models(roots) {
+        let m = resolve_ollama_model_with(roots, &name, &tag, |p| catalog.is_gguf(p))
+            .ok()
+            .flatten();
+        if let Some(m) = m {
+            let link = link_dir.join(ollama_link_name(&name, &tag));
+            let summary = catalog.summary(&m.model);
+            let projector = m.projector.as_deref().and_then(|p| catalog.summary(p));
+            v.push(
+                ModelRecord::new(Source::OllamaBlob, name, Some(tag), &m.model, link, summary)
+                    .with_mmproj(m.projector, projector),
+            );
+        }
+    }
+    v
+}
@@ -0,0 +1,123 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+use crate::inspect::human_bytes;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+// Exit codes (also listed in README.md). An attached child that fails passes
+// its own status through instead (128 + signal number if it was killed).
+//   1   I/O or other unexpected failure
+//   2   usage error or invalid config file
+//   3   model not found (locally and, where applicable, in the cloud), or no
+//       running instance matches a selector
+//   4   unreadable Ollama manifest or GGUF file
+//   5   link conflict or link failure
+//   6   detached llama-server did not become ready
+//   7   requested port is in use, or no free port in the auto range
+//   8   the model is estimated not to fit in free memory
+//   126 external binary found but could not be started
+//   127 external binary not found on PATH
+#[derive(Debug, Error)]
+pub enum GgufyError {
+    #[error("{0}")]
+    Usage(String),
+    #[error("invalid config {}: {message}", path.display())]
+    Config { path: PathBuf, message: String },
+    #[error("{0} not found on PATH")]
+    MissingBinary(String),
+    #[error("failed to start {program}: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{program} exited with status {code}")]
+    Exited { program: String, code: i32 },
+    #[error("llama-server at {url} did not become ready: {reason}{log}")]
+    NotReady {
+        url: String,
+        reason: String,
+        // Tail of the captured output, newline-prefixed, or empty.
+        log: String,
+    },
+    #[error("port {port} is already in use{}; pick another with --port or use --port auto", owner.as_ref().map(|o| format!(" by {}", o)).unwrap_or_default())]
+    PortInUse { port: u16, owner: Option<String> },
+    #[error("no free port in {start}-{end}; widen it with --port-range")]
+    NoFreePort { start: u16, end: u16 },
+    #[error("{model} needs about {} at --ctx-size {ctx}, more than the {} free; {} (--ignore-fit starts it anyway)", human_bytes(*need), human_bytes(*free), fit_hint(*largest))]
+    WontFit {
+        model: String,
+        ctx: u64,
+        need: u64,
+        free: u64,
+        largest: Option<u64>,
+    },
+    #[error("model not found: {0}")]
+    ModelNotFound(String),
+    #[error("no running instance matches {0}")]
+    InstanceNotFound(String),
+    #[error("no local gguf blob found for {name}:{tag} and cloud unavailable")]
+    NoLocalOrCloud { name: String, tag: String },
+    #[error("no gguf blob found for {name}:{tag} in ollama library")]
+    NotInOllamaLibrary { name: String, tag: String },
+    #[error("failed to parse manifest {}: {source}", path.display())]
+    ManifestParse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("failed to read {}: {source}", path.display())]
+    InvalidGguf {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("link conflict: {} already exists (use --force to replace)", link.display())]
+    LinkConflict { link: PathBuf },
+    #[error("failed to link {} -> {}: {source}", src.display(), dst.display())]
+    Link {
+        src: PathBuf,
+        dst: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("cannot create models directory {}: {source}", path.display())]
+    ModelsDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl GgufyError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GgufyError::Usage(_) | GgufyError::Config { .. } => 2,
+            GgufyError::Exited { code, .. } => *code,
+            GgufyError::ModelNotFound(_)
+            | GgufyError::InstanceNotFound(_)
+            | GgufyError::NoLocalOrCloud { .. }
+            | GgufyError::NotInOllamaLibrary { .. } => 3,
+            GgufyError::ManifestParse { .. } | GgufyError::InvalidGguf { .. } => 4,
+            GgufyError::LinkConflict { .. } | GgufyError::Link { .. } => 5,
+            GgufyError::NotReady { .. } => 6,
+            GgufyError::PortInUse { .. } | GgufyError::NoFreePort { .. } => 7,
+            GgufyError::WontFit { .. } => 8,
+            GgufyError::Spawn { .. } => 126,
+            GgufyError::MissingBinary(_) => 127,
+            GgufyError::ModelsDir { .. } | GgufyError::Io(_) => 1,
+        }
+    }
+}
+
+fn fit_hint(largest: Option<u64>) -> String {
+    match largest {
+        Some(n) => format!("--ctx-size {} fits", n),
+        None => "it does not fit at any context".to_string(),
+    }
+}
+
+pub type Result<T> = std::result::Result<T, GgufyError>;
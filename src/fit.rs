@@ -0,0 +1,380 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Will a model fit? Weights plus the KV cache at a given context, against
+// free RAM from `/proc/meminfo` and, when `nvidia-smi` is on PATH, free VRAM.
+// The estimate is rough on purpose: compute buffers get a flat allowance and
+// sliding-window layers are counted at the full context.
+
+use crate::error::GgufyError;
+use crate::gguf::{ggml_type_layout, ggml_type_name, GgufFile, GgufValue};
+use crate::inspect::human_bytes;
+use crate::launch::resolve_bin;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// llama.cpp's `--ctx-size` when none is given; 0 means the trained context.
+pub const DEFAULT_CTX: u64 = 4096;
+// Compute buffers, the GPU context and other allocations besides the weights
+// and the KV cache.
+pub const OVERHEAD: u64 = 512 << 20;
+// Suggested contexts are multiples of this.
+const CTX_STEP: u64 = 256;
+// Launches past this share of free memory warn before starting.
+const TIGHT: f64 = 0.9;
+// More layers than any real model has; a larger `block_count` is corrupt.
+const MAX_LAYERS: u64 = 4096;
+
+// The settings a llama-server command line asks for, last flag winning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub ctx: Option<u64>,
+    pub cache_k: String,
+    pub cache_v: String,
+    pub mmproj: Option<PathBuf>,
+}
+
+impl Settings {
+    pub fn from_args(args: &[String]) -> Settings {
+        let mut s = Settings {
+            ctx: None,
+            cache_k: "f16".to_string(),
+            cache_v: "f16".to_string(),
+            mmproj: None,
+        };
+        let mut it = args.iter();
+        while let Some(arg) = it.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((f, v)) if f.starts_with("--") => (f, Some(v.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            if !matches!(
+                flag,
+                "-c" | "--ctx-size"
+                    | "-ctk"
+                    | "--cache-type-k"
+                    | "-ctv"
+                    | "--cache-type-v"
+                    | "-mm"
+                    | "--mmproj"
+            ) {
+                if flag == "--no-mmproj" {
+                    s.mmproj = None;
+                }
+                continue;
+            }
+            let Some(value) = inline.or_else(|| it.next().cloned()) else {
+                break;
+            };
+            match flag {
+                "-c" | "--ctx-size" => s.ctx = value.parse().ok().or(s.ctx),
+                "-ctk" | "--cache-type-k" => s.cache_k = value.to_lowercase(),
+                "-ctv" | "--cache-type-v" => s.cache_v = value.to_lowercase(),
+                _ => s.mmproj = Some(PathBuf::from(value)),
+            }
+        }
+        s
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub ctx: u64,
+    pub train_ctx: Option<u64>,
+    pub cache_k: String,
+    pub cache_v: String,
+    // Tensor data, plus the projector's file when one is loaded.
+    pub weights: u64,
+    pub kv_per_token: u64,
+}
+
+impl Estimate {
+    pub fn new(g: &GgufFile, settings: &Settings) -> Estimate {
+        let train_ctx = g.arch_u64("context_length");
+        let ctx = match settings.ctx {
+            Some(0) => train_ctx.unwrap_or(DEFAULT_CTX),
+            Some(n) => n,
+            None => DEFAULT_CTX,
+        };
+        let mmproj = settings
+            .mmproj
+            .as_deref()
+            .and_then(|p| fs::metadata(p).ok())
+            .map_or(0, |m| m.len());
+        let weights = g
+            .tensors
+            .iter()
+            .filter_map(|t| t.n_bytes())
+            .fold(0u64, u64::saturating_add);
+        Estimate {
+            ctx,
+            train_ctx,
+            cache_k: settings.cache_k.clone(),
+            cache_v: settings.cache_v.clone(),
+            weights: weights.saturating_add(mmproj),
+            kv_per_token: kv_per_token(g, &settings.cache_k, &settings.cache_v),
+        }
+    }
+
+    pub fn kv(&self) -> u64 {
+        self.kv_per_token.saturating_mul(self.ctx)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.weights
+            .saturating_add(self.kv())
+            .saturating_add(OVERHEAD)
+    }
+
+    // The largest context, in CTX_STEP steps and no longer than the model was
+    // trained for, whose estimate stays within `budget`.
+    pub fn largest_ctx(&self, budget: u64) -> Option<u64> {
+        let room = budget.checked_sub(self.weights.saturating_add(OVERHEAD))?;
+        let trained = self.train_ctx.unwrap_or(u64::MAX);
+        if self.kv_per_token == 0 {
+            return Some(self.train_ctx.unwrap_or(self.ctx));
+        }
+        let n = (room / self.kv_per_token).min(trained);
+        let n = if n == trained {
+            n
+        } else {
+            n / CTX_STEP * CTX_STEP
+        };
+        (n > 0).then_some(n)
+    }
+}
+
+// K and V bytes per token across all layers. Per-layer head counts (an array
+// in hybrid models) are summed as given, so recurrent layers with no KV heads
+// add nothing. Models without attention metadata count as zero.
+fn kv_per_token(g: &GgufFile, cache_k: &str, cache_v: &str) -> u64 {
+    let Some(layers) = g.arch_u64("block_count").filter(|n| *n <= MAX_LAYERS) else {
+        return 0;
+    };
+    let arch = g.architecture().unwrap_or_default();
+    let per_layer = |suffix: &str| -> Option<Vec<u64>> {
+        match g.get(&format!("{}.{}", arch, suffix))? {
+            GgufValue::Array(items) => Some(
+                items
+                    .iter()
+                    .take(layers as usize)
+                    .filter_map(|v| v.as_u64())
+                    .collect(),
+            ),
+            v => v.as_u64().map(|n| vec![n; layers as usize]),
+        }
+    };
+    let Some(heads) = per_layer("attention.head_count") else {
+        return 0;
+    };
+    let kv_heads = per_layer("attention.head_count_kv").unwrap_or_else(|| heads.clone());
+    let max_heads = heads.iter().copied().max().unwrap_or(0);
+    let embd = g.arch_u64("embedding_length").unwrap_or(0);
+    let head_dim = embd.checked_div(max_heads).unwrap_or(0);
+    let k_len = g.arch_u64("attention.key_length").unwrap_or(head_dim);
+    let v_len = g.arch_u64("attention.value_length").unwrap_or(head_dim);
+    kv_heads
+        .iter()
+        .map(|h| {
+            cache_bytes(cache_k, h.saturating_mul(k_len))
+                .saturating_add(cache_bytes(cache_v, h.saturating_mul(v_len)))
+        })
+        .fold(0, u64::saturating_add)
+}
+
+// Size of `n` cache elements of a `--cache-type-*` such as `f16` or `q8_0`;
+// unknown names count as f16.
+fn cache_bytes(ty: &str, n: u64) -> u64 {
+    let (block, size) = (0..64)
+        .find(|t| ggml_type_name(*t).is_some_and(|name| name.eq_ignore_ascii_case(ty)))
+        .and_then(ggml_type_layout)
+        .unwrap_or((1, 2));
+    n.div_ceil(block).saturating_mul(size)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    pub ram_available: u64,
+    pub ram_total: u64,
+    pub vram_free: u64,
+    pub vram_total: u64,
+    pub gpus: usize,
+}
+
+impl Memory {
+    // Layers llama.cpp does not offload stay in RAM, so free VRAM and RAM
+    // are counted together.
+    pub fn budget(&self) -> u64 {
+        self.ram_available.saturating_add(self.vram_free)
+    }
+}
+
+// Free memory now, or None where there is no `/proc/meminfo`
+// (`GGUFY_MEMINFO` names another file in its format).
+pub fn memory() -> Option<Memory> {
+    let path = std::env::var_os("GGUFY_MEMINFO")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/proc/meminfo"));
+    let text = fs::read_to_string(path).ok()?;
+    let field = |name: &str| {
+        text.lines()
+            .find_map(|l| l.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|v| v.split_whitespace().next()?.parse::<u64>().ok())
+            .map(|kb| kb.saturating_mul(1024))
+    };
+    let ram_total = field("MemTotal")?;
+    let ram_available = field("MemAvailable").or_else(|| field("MemFree"))?;
+    let mut mem = Memory {
+        ram_available,
+        ram_total,
+        ..Memory::default()
+    };
+    for (free, total) in nvidia_gpus() {
+        mem.vram_free = mem.vram_free.saturating_add(free);
+        mem.vram_total = mem.vram_total.saturating_add(total);
+        mem.gpus += 1;
+    }
+    Some(mem)
+}
+
+// (free, total) bytes per NVIDIA GPU.
+fn nvidia_gpus() -> Vec<(u64, u64)> {
+    let Some(bin) = resolve_bin("nvidia-smi") else {
+        return Vec::new();
+    };
+    let out = Command::new(bin)
+        .args([
+            "--query-gpu=memory.free,memory.total",
+            "--format=csv,noheader,nounits",
+        ])
+        .stderr(Stdio::null())
+        .output();
+    let Some(out) = out.ok().filter(|o| o.status.success()) else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|l| {
+            let (free, total) = l.split_once(',')?;
+            let mib = |s: &str| {
+                s.trim()
+                    .parse::<u64>()
+                    .ok()
+                    .map(|n| n.saturating_mul(1 << 20))
+            };
+            Some((mib(free)?, mib(total)?))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Fits,
+    Tight,
+    TooBig,
+}
+
+pub fn verdict(est: &Estimate, mem: &Memory) -> Verdict {
+    let (need, have) = (est.total(), mem.budget());
+    if need > have {
+        Verdict::TooBig
+    } else if need as f64 > have as f64 * TIGHT {
+        Verdict::Tight
+    } else {
+        Verdict::Fits
+    }
+}
+
+pub fn wont_fit(model: &str, est: &Estimate, mem: &Memory) -> GgufyError {
+    GgufyError::WontFit {
+        model: model.to_string(),
+        ctx: est.ctx,
+        need: est.total(),
+        free: mem.budget(),
+        largest: est.largest_ctx(mem.budget()),
+    }
+}
+
+pub fn render_text(model: &str, est: &Estimate, mem: Option<&Memory>) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("model:     {}\n", model));
+    let trained = est
+        .train_ctx
+        .map(|n| format!(" (trained {})", n))
+        .unwrap_or_default();
+    out.push_str(&format!("context:   {}{}\n", est.ctx, trained));
+    out.push_str(&format!("weights:   {}\n", human_bytes(est.weights)));
+    out.push_str(&format!(
+        "kv cache:  {} ({}/{}, {} per token)\n",
+        human_bytes(est.kv()),
+        est.cache_k,
+        est.cache_v,
+        human_bytes(est.kv_per_token)
+    ));
+    out.push_str(&format!("overhead:  {}\n", human_bytes(OVERHEAD)));
+    out.push_str(&format!("total:     {}\n", human_bytes(est.total())));
+    let Some(mem) = mem else {
+        out.push_str("free:      unknown (no /proc/meminfo)\n");
+        return out;
+    };
+    let mut free = format!(
+        "{} RAM of {}",
+        human_bytes(mem.ram_available),
+        human_bytes(mem.ram_total)
+    );
+    if mem.gpus > 0 {
+        free.push_str(&format!(
+            " + {} VRAM of {} on {} GPU{}",
+            human_bytes(mem.vram_free),
+            human_bytes(mem.vram_total),
+            mem.gpus,
+            if mem.gpus == 1 { "" } else { "s" }
+        ));
+    }
+    out.push_str(&format!("free:      {}\n", free));
+    let largest = match est.largest_ctx(mem.budget()) {
+        Some(n) => format!("largest context that fits: {}", n),
+        None => "the weights alone do not fit".to_string(),
+    };
+    let verdict = match verdict(est, mem) {
+        Verdict::Fits => "fits",
+        Verdict::Tight => "fits, barely",
+        Verdict::TooBig => "does not fit",
+    };
+    out.push_str(&format!("verdict:   {}; {}\n", verdict, largest));
+    out
+}
+
+pub fn render_json(model: &str, path: &Path, est: &Estimate, mem: Option<&Memory>) -> Value {
+    let mut v = json!({
+        "model": model,
+        "path": path.display().to_string(),
+        "ctx_size": est.ctx,
+        "context_length": est.train_ctx,
+        "cache_type_k": est.cache_k,
+        "cache_type_v": est.cache_v,
+        "weights": est.weights,
+        "kv_cache": est.kv(),
+        "kv_per_token": est.kv_per_token,
+        "overhead": OVERHEAD,
+        "total": est.total(),
+        "ram_available": Value::Null,
+        "ram_total": Value::Null,
+        "vram_free": Value::Null,
+        "vram_total": Value::Null,
+        "fits": Value::Null,
+        "largest_ctx": Value::Null,
+    });
+    if let Some(mem) = mem {
+        v["ram_available"] = json!(mem.ram_available);
+        v["ram_total"] = json!(mem.ram_total);
+        if mem.gpus > 0 {
+            v["vram_free"] = json!(mem.vram_free);
+            v["vram_total"] = json!(mem.vram_total);
+        }
+        v["fits"] = json!(verdict(est, mem) != Verdict::TooBig);
+        v["largest_ctx"] = json!(est.largest_ctx(mem.budget()));
+    }
+    v
+}
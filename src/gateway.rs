@@ -0,0 +1,1184 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// `ggufy gateway` (and `ggufy ollama api`): one OpenAI- and Ollama-compatible
+// endpoint for every model ggufy can find. Each request is routed by its
+// `model` field through `route::decide`; local GGUFs (link directory or
+// Ollama store) get a llama-server of their own on an internal port, started
+// on first use and kept as a managed instance, and cloud tags go to the
+// Ollama daemon. OpenAI bodies are relayed byte for byte, so SSE streams pass
+// straight through; `/api/*` requests are translated by `ollama_api`.
+
+use crate::catalog::Catalog;
+use crate::discovery::{enumerate_ollama_library_models, find_mmproj, ggufy_models_dir_with};
+use crate::error::{GgufyError, Result};
+use crate::gguf::read_gguf;
+use crate::instance::{self, Instance};
+use crate::launch::{
+    choose_port, spawn_local_server, wait_managed_ready, LaunchOptions, PortRange, PortSpec,
+};
+use crate::ollama_api::{self, Endpoint, Reply};
+use crate::resolve::split_model_tag;
+use crate::route::{decide, Decision, Probe, SystemProbe, Target};
+use crate::{config::Preset, health};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+pub const DEFAULT_PORT: u16 = 12400;
+const MAX_HEAD: usize = 64 * 1024;
+const MAX_BODY: usize = 64 * 1024 * 1024;
+// A client gets this long to send its whole request, headers and body.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+// Connections handled at once; further ones wait in the listen backlog.
+const MAX_CONNECTIONS: usize = 64;
+const REAP_INTERVAL: Duration = Duration::from_millis(500);
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+pub struct GatewayOptions {
+    pub host: String,
+    pub port: u16,
+    // Where the per-model llama-servers are put.
+    pub range: PortRange,
+    pub link_dir: Option<PathBuf>,
+    // Servers the gateway may keep loaded at once; starting another unloads
+    // the least recently used one that is neither pinned nor busy.
+    pub max_models: Option<usize>,
+    // Unload a server idle this long, unless its preset says otherwise.
+    pub idle: Option<Duration>,
+}
+
+struct Backend {
+    inst: Instance,
+    // None when the server was already running before the gateway found it;
+    // only servers the gateway started are stopped, evicted or unloaded.
+    child: Option<Child>,
+    pinned: bool,
+    idle: Option<Duration>,
+    usage: Arc<Usage>,
+}
+
+impl Backend {
+    fn evictable(&self) -> bool {
+        self.child.is_some() && !self.pinned && self.usage.in_flight.load(Ordering::SeqCst) == 0
+    }
+
+    fn stop(&mut self, why: &str) {
+        let _ = instance::stop_instance(&self.inst);
+        self.release();
+        println!("stopped {} ({}): {}", self.inst.id, self.inst.model, why);
+    }
+
+    // Waits on a server that was stopped or died, so it is not left behind
+    // as a zombie. Called before every backend that owns a child is dropped.
+    fn release(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            reap_child(&mut child);
+        }
+    }
+}
+
+// The child has been stopped or has exited; kill is only a backstop so the
+// wait cannot block.
+fn reap_child(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[derive(Default)]
+struct Usage {
+    in_flight: AtomicUsize,
+    last_used: Mutex<Option<Instant>>,
+}
+
+impl Usage {
+    fn idle_for(&self) -> Duration {
+        self.last_used
+            .lock()
+            .unwrap()
+            .map(|t| t.elapsed())
+            .unwrap_or_default()
+    }
+}
+
+// Held for the length of a proxied request; the backend counts as busy until
+// it is dropped, and as used at that moment.
+struct Lease(Arc<Usage>);
+
+impl Lease {
+    fn new(usage: &Arc<Usage>) -> Lease {
+        usage.in_flight.fetch_add(1, Ordering::SeqCst);
+        *usage.last_used.lock().unwrap() = Some(Instant::now());
+        Lease(usage.clone())
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        *self.0.last_used.lock().unwrap() = Some(Instant::now());
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Gateway {
+    opts: LaunchOptions,
+    range: PortRange,
+    link_dir: Option<PathBuf>,
+    max_models: Option<usize>,
+    idle: Option<Duration>,
+    // One slot per GGUF. Its lock is held while the server starts, so
+    // concurrent first requests for a model share a single start.
+    backends: Mutex<HashMap<PathBuf, Arc<Mutex<Option<Backend>>>>>,
+    // Making room, picking a free port and binding it must not interleave.
+    spawning: Mutex<()>,
+    // Servers spawned but not yet ready. Their slots stay locked until then,
+    // so shutdown stops them from here instead.
+    starting: Mutex<Vec<Instance>>,
+    // The Ollama daemon for cloud models; None when OLLAMA_HOST points back
+    // at the gateway, which would forward to itself forever.
+    upstream: Option<(String, u16)>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+type HttpResult = std::result::Result<(), HttpError>;
+
+// Which error shape a client expects.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Api {
+    OpenAi,
+    Ollama,
+}
+
+const ROUTES: [&str; 10] = [
+    "/v1/models",
+    "/v1/chat/completions",
+    "/v1/completions",
+    "/api/version",
+    "/api/tags",
+    "/api/ps",
+    "/api/show",
+    "/api/generate",
+    "/api/chat",
+    "/health",
+];
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> HttpError {
+        HttpError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<io::Error> for HttpError {
+    fn from(e: io::Error) -> HttpError {
+        HttpError::new(500, e.to_string())
+    }
+}
+
+impl From<GgufyError> for HttpError {
+    fn from(e: GgufyError) -> HttpError {
+        let status = match e {
+            GgufyError::ModelNotFound(_)
+            | GgufyError::NoLocalOrCloud { .. }
+            | GgufyError::NotInOllamaLibrary { .. } => 404,
+            GgufyError::Usage(_) => 400,
+            GgufyError::NotReady { .. }
+            | GgufyError::MissingBinary(_)
+            | GgufyError::Spawn { .. }
+            | GgufyError::NoFreePort { .. } => 503,
+            _ => 500,
+        };
+        HttpError::new(status, e.to_string())
+    }
+}
+
+pub fn serve(gopts: GatewayOptions, opts: LaunchOptions) -> Result<()> {
+    let listener = TcpListener::bind((gopts.host.as_str(), gopts.port)).map_err(|e| {
+        if e.kind() == io::ErrorKind::AddrInUse {
+            GgufyError::PortInUse {
+                port: gopts.port,
+                owner: None,
+            }
+        } else {
+            e.into()
+        }
+    })?;
+    let local = listener.local_addr()?;
+    let upstream = Some(ollama_upstream()).filter(|(h, p)| !is_self(h, *p, local));
+    if upstream.is_none() {
+        eprintln!("warning: OLLAMA_HOST is this gateway; cloud models are unavailable");
+    }
+    let gateway = Arc::new(Gateway {
+        opts,
+        range: gopts.range,
+        link_dir: gopts.link_dir,
+        max_models: gopts.max_models,
+        idle: gopts.idle,
+        backends: Mutex::new(HashMap::new()),
+        spawning: Mutex::new(()),
+        starting: Mutex::new(Vec::new()),
+        upstream,
+    });
+    thread::spawn({
+        let gateway = gateway.clone();
+        move || loop {
+            thread::sleep(REAP_INTERVAL);
+            gateway.reap();
+        }
+    });
+    shutdown::on_signal({
+        let gateway = gateway.clone();
+        move || gateway.stop_all()
+    });
+    let url = health::base_url(&gopts.host, gopts.port);
+    println!("gateway on {} (OpenAI {}/v1, Ollama {}/api)", url, url, url);
+    let slots = Arc::new(Slots::default());
+    loop {
+        let slot = slots.acquire();
+        let stream = match listener.accept() {
+            Ok((s, _)) => s,
+            // Out of descriptors and the like: back off rather than spin.
+            Err(e) => {
+                eprintln!("gateway: accept: {}", e);
+                drop(slot);
+                thread::sleep(ACCEPT_BACKOFF);
+                continue;
+            }
+        };
+        let gateway = gateway.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            gateway.handle(stream)
+        });
+    }
+}
+
+// Counts connection threads against MAX_CONNECTIONS.
+#[derive(Default)]
+struct Slots {
+    used: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl Slots {
+    fn acquire(self: &Arc<Self>) -> Slot {
+        let mut used = self.used.lock().unwrap();
+        while *used >= MAX_CONNECTIONS {
+            used = self.freed.wait(used).unwrap();
+        }
+        *used += 1;
+        Slot(self.clone())
+    }
+}
+
+struct Slot(Arc<Slots>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *self.0.used.lock().unwrap() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+impl Gateway {
+    fn handle(&self, mut stream: TcpStream) {
+        let req = match read_request(&mut stream) {
+            Ok(r) => r,
+            Err(e) => return send_error(&mut stream, &e, Api::OpenAi),
+        };
+        let _ = stream.set_read_timeout(None);
+        let path = req.path.split('?').next().unwrap_or("").to_string();
+        let api = if path.starts_with("/api/") {
+            Api::Ollama
+        } else {
+            Api::OpenAi
+        };
+        let s = &mut stream;
+        let result = match (req.method.as_str(), path.as_str()) {
+            ("GET", "/health") => send_json(s, 200, &json!({"status": "ok"})),
+            // Ollama clients probe the root to see whether a server is up.
+            ("GET", "/") | ("HEAD", "/") => send_text(s, &req.method, "Ollama is running"),
+            ("GET", "/v1/models") => send_json(s, 200, &self.models()),
+            ("POST", "/v1/chat/completions") | ("POST", "/v1/completions") => {
+                self.complete(s, &req, &path)
+            }
+            ("GET", "/api/version") => {
+                send_json(s, 200, &json!({"version": env!("CARGO_PKG_VERSION")}))
+            }
+            ("GET", "/api/tags") => send_json(
+                s,
+                200,
+                &ollama_api::tags(self.link_dir.as_ref(), &self.opts.ollama_stores()),
+            ),
+            ("GET", "/api/ps") => send_json(s, 200, &self.loaded()),
+            ("POST", "/api/show") => self.api_show(s, &req, &path),
+            ("POST", "/api/generate") => self.api_generate(s, &req, &path, Endpoint::Generate),
+            ("POST", "/api/chat") => self.api_generate(s, &req, &path, Endpoint::Chat),
+            (_, p) if ROUTES.contains(&p) => Err(HttpError::new(405, "method not allowed")),
+            _ => Err(HttpError::new(
+                404,
+                format!("no route for {} {}", req.method, path),
+            )),
+        };
+        if let Err(e) = result {
+            if self.opts.verbose {
+                eprintln!("gateway: {} {}: {}", req.method, path, e.message);
+            }
+            send_error(&mut stream, &e, api);
+        }
+    }
+
+    fn route(&self, model: &str, api: Api) -> std::result::Result<Decision, HttpError> {
+        let mut probe = SystemProbe {
+            link_dir: self.link_dir.clone(),
+            ollama_roots: self.opts.ollama_stores(),
+            no_cloud: self.upstream.is_none(),
+        };
+        let prefer = self.opts.config.prefer();
+        let decision = match api {
+            Api::OpenAi => decide(model, prefer, &mut probe)?,
+            // Ollama clients leave `:latest` implicit, and `/api/tags` lists
+            // link-directory models as `<stem>:latest`.
+            Api::Ollama => {
+                let (name, tag) = split_model_tag(model);
+                match decide(&format!("{}:{}", name, tag), prefer, &mut probe) {
+                    Err(e) if tag == "latest" => {
+                        let path = probe.local(&name).ok_or(e)?;
+                        Decision {
+                            target: Target::Gguf(path),
+                            reason: format!("{} is a local GGUF", name),
+                        }
+                    }
+                    other => other?,
+                }
+            }
+        };
+        if self.opts.explain {
+            eprintln!(
+                "route: {} -> {} ({})",
+                model,
+                decision.backend(),
+                decision.reason
+            );
+        }
+        Ok(decision)
+    }
+
+    fn ollama(&self) -> std::result::Result<(&str, u16), HttpError> {
+        self.upstream
+            .as_ref()
+            .map(|(h, p)| (h.as_str(), *p))
+            .ok_or_else(|| {
+                HttpError::new(
+                    502,
+                    "no upstream Ollama: OLLAMA_HOST points at this gateway",
+                )
+            })
+    }
+
+    fn complete(&self, stream: &mut TcpStream, req: &Request, path: &str) -> HttpResult {
+        let mut body = json_body(req)?;
+        let model = model_of(&body, &["model"])?;
+        let (port, _lease) = match self.route(&model, Api::OpenAi)?.target {
+            Target::Gguf(p) => self.backend(&model, &p, None)?,
+            Target::Ollama(m) => {
+                let preset = m.preset(self.opts.verbose);
+                self.backend(&model, &m.model, Some(preset))?
+            }
+            Target::Cloud { name, tag } => {
+                body["model"] = json!(format!("{}:{}", name, tag));
+                let (host, port) = self.ollama()?;
+                return forward(stream, req, host, port, path, &body);
+            }
+        };
+        forward(stream, req, "127.0.0.1", port, path, &body)
+    }
+
+    fn api_show(&self, stream: &mut TcpStream, req: &Request, path: &str) -> HttpResult {
+        let mut body = json_body(req)?;
+        let model = model_of(&body, &["model", "name"])?;
+        let (file, ollama) = match self.route(&model, Api::Ollama)?.target {
+            Target::Gguf(p) => (p, None),
+            Target::Ollama(m) => (m.model.clone(), Some(m)),
+            Target::Cloud { name, tag } => {
+                body["model"] = json!(format!("{}:{}", name, tag));
+                let (host, port) = self.ollama()?;
+                return forward(stream, req, host, port, path, &body);
+            }
+        };
+        let g = read_gguf(&file).map_err(|source| GgufyError::InvalidGguf {
+            path: file.clone(),
+            source,
+        })?;
+        let mmproj = ollama
+            .as_ref()
+            .and_then(|m| m.projector.clone())
+            .or_else(|| find_mmproj(&file))
+            .is_some();
+        let shown = ollama_api::show(&file, &g, ollama.as_ref(), mmproj);
+        send_json(stream, 200, &shown)
+    }
+
+    // `/api/generate` and `/api/chat` through llama-server's OpenAI API,
+    // answered in Ollama's NDJSON (or one object with `"stream": false`).
+    fn api_generate(
+        &self,
+        stream: &mut TcpStream,
+        req: &Request,
+        path: &str,
+        endpoint: Endpoint,
+    ) -> HttpResult {
+        let started = Instant::now();
+        let mut body = json_body(req)?;
+        let model = model_of(&body, &["model"])?;
+        let (file, preset) = match self.route(&model, Api::Ollama)?.target {
+            Target::Gguf(p) => (p, None),
+            Target::Ollama(m) => (m.model.clone(), Some(m.preset(self.opts.verbose))),
+            Target::Cloud { name, tag } => {
+                body["model"] = json!(format!("{}:{}", name, tag));
+                let (host, port) = self.ollama()?;
+                return forward(stream, req, host, port, path, &body);
+            }
+        };
+        let empty = match endpoint {
+            Endpoint::Generate => body["prompt"].as_str().is_none_or(str::is_empty),
+            Endpoint::Chat => body["messages"].as_array().is_none_or(Vec::is_empty),
+        };
+        if empty && keep_alive_is_zero(&body["keep_alive"]) {
+            self.unload(&file);
+            return send_json(
+                stream,
+                200,
+                &ollama_api::lifecycle(endpoint, &model, "unload"),
+            );
+        }
+        let stop = preset.as_ref().map(|p| p.stop.clone()).unwrap_or_default();
+        let (port, _lease) = self.backend(&model, &file, preset)?;
+        if empty {
+            return send_json(
+                stream,
+                200,
+                &ollama_api::lifecycle(endpoint, &model, "load"),
+            );
+        }
+        let load = started.elapsed();
+        let (up_path, up_body) = ollama_api::upstream_request(endpoint, &body, &stop);
+        let upstream = post("127.0.0.1", port, up_path, &up_body)
+            .map_err(|e| HttpError::new(502, format!("llama-server: {}", e)))?;
+        if upstream.status != 200 {
+            return Err(upstream.error());
+        }
+        let streaming = body["stream"].as_bool().unwrap_or(true);
+        if streaming {
+            let head = "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n";
+            if stream.write_all(head.as_bytes()).is_err() {
+                return Ok(());
+            }
+        }
+        let mut reply = Reply::new(endpoint, &model, started, load);
+        for line in upstream.body.lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(_) => break,
+            };
+            let data = match line.strip_prefix("data:") {
+                Some(d) => d.trim(),
+                None => continue,
+            };
+            if data == "[DONE]" {
+                break;
+            }
+            let event: Value = match serde_json::from_str(data) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            if let Some(chunk) = reply.push(&event) {
+                if streaming && write_line(stream, &chunk).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        let done = reply.done(streaming);
+        if streaming {
+            let _ = write_line(stream, &done);
+            Ok(())
+        } else {
+            send_json(stream, 200, &done)
+        }
+    }
+
+    // `/api/ps`: servers loaded by (or adopted into) this gateway.
+    fn loaded(&self) -> Value {
+        let mut catalog = Catalog::load(&ggufy_models_dir_with(self.link_dir.as_ref()));
+        let slots: Vec<_> = self.backends.lock().unwrap().values().cloned().collect();
+        let mut models = Vec::new();
+        for slot in slots {
+            let guard = match slot.try_lock() {
+                Ok(g) => g,
+                Err(_) => continue,
+            };
+            let b = match guard.as_ref() {
+                Some(b) if b.inst.is_alive() => b,
+                _ => continue,
+            };
+            let file = b.inst.model_path.clone().unwrap_or_default();
+            let mut entry = json!({
+                "name": b.inst.model,
+                "model": b.inst.model,
+                "size": fs::metadata(&file).map(|m| m.len()).unwrap_or(0),
+                "digest": ollama_api::digest(&file),
+                "details": ollama_api::details(catalog.summary(&file).as_ref()),
+                "size_vram": 0,
+            });
+            if let (Some(idle), false) = (b.idle, b.pinned) {
+                let left = idle.saturating_sub(b.usage.idle_for());
+                entry["expires_at"] = json!(ollama_api::rfc3339(SystemTime::now() + left));
+            }
+            models.push(entry);
+        }
+        json!({ "models": models })
+    }
+
+    fn unload(&self, file: &Path) {
+        let slot = self.backends.lock().unwrap().get(file).cloned();
+        if let Some(slot) = slot {
+            let mut guard = slot.lock().unwrap();
+            if let Some(b) = guard.as_mut().filter(|b| b.evictable()) {
+                b.stop("unloaded on request");
+                *guard = None;
+            }
+        }
+    }
+
+    // The port of a ready llama-server for `path`, starting one if needed,
+    // and a lease that keeps it loaded while the request runs.
+    fn backend(
+        &self,
+        name: &str,
+        path: &Path,
+        preset: Option<Preset>,
+    ) -> std::result::Result<(u16, Lease), HttpError> {
+        let slot = self
+            .backends
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone();
+        let mut slot = slot.lock().unwrap();
+        if let Some(b) = slot.as_mut() {
+            if b.inst.is_alive() {
+                return Ok((b.inst.port.unwrap_or_default(), Lease::new(&b.usage)));
+            }
+            b.release();
+        }
+        let policy = self.opts.config.resolve(name, Some(path)).preset;
+        let pinned = policy.pin.unwrap_or(false);
+        let idle = match policy.idle_minutes {
+            Some(m) => idle_minutes(m),
+            None => self.idle,
+        };
+        if let Some(inst) = running_for(path) {
+            let backend = Backend {
+                inst,
+                child: None,
+                pinned,
+                idle,
+                usage: Arc::default(),
+            };
+            let found = (
+                backend.inst.port.unwrap_or_default(),
+                Lease::new(&backend.usage),
+            );
+            *slot = Some(backend);
+            return Ok(found);
+        }
+        let opts = match preset {
+            Some(p) => self.opts.with_model_preset(p),
+            None => self.opts.clone(),
+        };
+        let (inst, mut child) = {
+            let _guard = self.spawning.lock().unwrap();
+            self.make_room(path)?;
+            let port = choose_port(false, Some(PortSpec::Auto), self.range)?;
+            let started = spawn_local_server(path, name, port, &opts)?;
+            self.starting.lock().unwrap().push(started.0.clone());
+            started
+        };
+        let port = inst.port.unwrap_or_default();
+        let ready = wait_managed_ready(&inst, &mut child, "127.0.0.1", port, opts.ready_timeout);
+        self.starting.lock().unwrap().retain(|i| i.id != inst.id);
+        if let Err(e) = ready {
+            let _ = instance::stop_instance(&inst);
+            reap_child(&mut child);
+            return Err(e.into());
+        }
+        println!("started {} for {} on port {}", inst.id, name, port);
+        let backend = Backend {
+            inst,
+            child: Some(child),
+            pinned,
+            idle,
+            usage: Arc::default(),
+        };
+        let lease = Lease::new(&backend.usage);
+        *slot = Some(backend);
+        Ok((port, lease))
+    }
+
+    // Under `--max-models`, unloads least recently used servers until one
+    // more fits. Slots that are locked are starting up and count as loaded.
+    fn make_room(&self, starting: &Path) -> HttpResult {
+        let max = match self.max_models {
+            Some(m) => m.max(1),
+            None => return Ok(()),
+        };
+        let slots: Vec<Arc<Mutex<Option<Backend>>>> = self
+            .backends
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, _)| p.as_path() != starting)
+            .map(|(_, s)| s.clone())
+            .collect();
+        let mut loaded = 0;
+        let mut candidates = Vec::new();
+        for slot in &slots {
+            match slot.try_lock() {
+                Ok(guard) => match guard.as_ref() {
+                    Some(b) if b.child.is_some() && b.inst.is_alive() => {
+                        loaded += 1;
+                        if b.evictable() {
+                            candidates.push((b.usage.idle_for(), slot.clone()));
+                        }
+                    }
+                    _ => {}
+                },
+                Err(_) => loaded += 1,
+            }
+        }
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.0));
+        let mut candidates = candidates.into_iter();
+        while loaded >= max {
+            let (_, slot) = candidates.next().ok_or_else(|| {
+                let msg = format!(
+                    "{} models are loaded (--max-models {}) and none can be unloaded: they are pinned or busy",
+                    loaded, max
+                );
+                HttpError::new(503, msg)
+            })?;
+            let mut guard = slot.lock().unwrap();
+            // A request may have leased it since we looked.
+            if let Some(b) = guard.as_mut().filter(|b| b.evictable()) {
+                b.stop("least recently used");
+                *guard = None;
+                loaded -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    // Unloads servers past their idle time and forgets ones that died.
+    fn reap(&self) {
+        let slots: Vec<_> = self.backends.lock().unwrap().values().cloned().collect();
+        for slot in slots {
+            let mut guard = match slot.try_lock() {
+                Ok(g) => g,
+                Err(_) => continue,
+            };
+            let expired = match guard.as_mut() {
+                Some(b) if !b.inst.is_alive() => {
+                    b.release();
+                    true
+                }
+                Some(b) => match b.idle {
+                    Some(idle) if b.evictable() && b.usage.idle_for() >= idle => {
+                        b.stop("idle");
+                        true
+                    }
+                    _ => false,
+                },
+                None => false,
+            };
+            if expired {
+                *guard = None;
+            }
+        }
+    }
+
+    fn models(&self) -> Value {
+        let mut data = Vec::new();
+        let dir = ggufy_models_dir_with(self.link_dir.as_ref());
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .map(|rd| {
+                rd.filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().map(|x| x == "gguf").unwrap_or(false))
+                    .filter_map(|p| Some(p.file_stem()?.to_string_lossy().to_string()))
+                    .filter(|n| !n.to_lowercase().contains("mmproj"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        for id in names {
+            data.push(json!({"id": id, "object": "model", "owned_by": "ggufy"}));
+        }
+        for (name, tag) in enumerate_ollama_library_models(&self.opts.ollama_stores()) {
+            let id = format!("{}:{}", name, tag);
+            data.push(json!({"id": id, "object": "model", "owned_by": "ollama"}));
+        }
+        json!({"object": "list", "data": data})
+    }
+
+    // Never blocks on a slot: a locked one is starting a server, which is in
+    // `starting` and stopped through its instance record.
+    fn stop_all(&self) {
+        for inst in self.starting.lock().unwrap().drain(..) {
+            let _ = instance::stop_instance(&inst);
+            println!("stopped {} ({}): gateway exiting", inst.id, inst.model);
+        }
+        let slots: Vec<_> = self.backends.lock().unwrap().values().cloned().collect();
+        for slot in slots {
+            if let Ok(mut guard) = slot.try_lock() {
+                if let Some(b) = guard.as_mut().filter(|b| b.child.is_some()) {
+                    b.stop("gateway exiting");
+                }
+            }
+        }
+    }
+}
+
+// Zero (or anything unrepresentable) means never.
+pub fn idle_minutes(minutes: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(minutes * 60.0)
+        .ok()
+        .filter(|d| !d.is_zero())
+}
+
+fn json_body(req: &Request) -> std::result::Result<Value, HttpError> {
+    serde_json::from_slice(&req.body)
+        .map_err(|e| HttpError::new(400, format!("invalid JSON body: {}", e)))
+}
+
+// The first of `keys` naming the model (`/api/show` still accepts `name`).
+fn model_of(body: &Value, keys: &[&str]) -> std::result::Result<String, HttpError> {
+    keys.iter()
+        .find_map(|k| body[*k].as_str())
+        .filter(|m| !m.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| HttpError::new(400, format!("request has no \"{}\"", keys[0])))
+}
+
+// `0`, `"0"`, `"0s"`, `"0m"`: unload now.
+fn keep_alive_is_zero(v: &Value) -> bool {
+    match v {
+        Value::Number(n) => n.as_f64() == Some(0.0),
+        Value::String(s) => s.trim_end_matches(char::is_alphabetic).parse::<f64>() == Ok(0.0),
+        _ => false,
+    }
+}
+
+// Sends `body` to `path` upstream and relays the response byte for byte.
+fn forward(
+    stream: &mut TcpStream,
+    req: &Request,
+    host: &str,
+    port: u16,
+    path: &str,
+    body: &Value,
+) -> HttpResult {
+    let mut upstream = connect(host, port, path, body, |name| req.header(name))?;
+    // From here on the client has (possibly) seen bytes; errors just end
+    // the connection.
+    let _ = io::copy(&mut upstream, stream);
+    Ok(())
+}
+
+fn connect<'a>(
+    host: &str,
+    port: u16,
+    path: &str,
+    body: &Value,
+    header: impl Fn(&str) -> Option<&'a str>,
+) -> std::result::Result<TcpStream, HttpError> {
+    let payload = serde_json::to_vec(body).unwrap();
+    let mut upstream = TcpStream::connect((host, port)).map_err(|e| {
+        HttpError::new(
+            502,
+            format!("cannot reach {}: {}", health::base_url(host, port), e),
+        )
+    })?;
+    let mut head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        path,
+        host,
+        port,
+        payload.len()
+    );
+    for name in ["Accept", "Authorization"] {
+        if let Some(v) = header(name) {
+            head.push_str(&format!("{}: {}\r\n", name, v));
+        }
+    }
+    head.push_str("\r\n");
+    upstream
+        .write_all(head.as_bytes())
+        .and_then(|_| upstream.write_all(&payload))
+        .map_err(|e| HttpError::new(502, format!("upstream write failed: {}", e)))?;
+    Ok(upstream)
+}
+
+// A response read back from llama-server, its body de-chunked.
+struct Upstream {
+    status: u16,
+    body: Box<dyn BufRead + Send>,
+}
+
+impl Upstream {
+    // The upstream's own error message, with its status.
+    fn error(mut self) -> HttpError {
+        let mut text = String::new();
+        let _ = self.body.read_to_string(&mut text);
+        let v: Value = serde_json::from_str(&text).unwrap_or_default();
+        let message = v["error"]["message"]
+            .as_str()
+            .or_else(|| v["error"].as_str())
+            .map(str::to_string)
+            .unwrap_or(text);
+        HttpError::new(self.status, format!("llama-server: {}", message.trim()))
+    }
+}
+
+fn post(host: &str, port: u16, path: &str, body: &Value) -> io::Result<Upstream> {
+    let stream =
+        connect(host, port, path, body, |_| None).map_err(|e| io::Error::other(e.message))?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad status line"))?;
+    let mut chunked = false;
+    let mut length = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            let (k, v) = (k.trim(), v.trim());
+            if k.eq_ignore_ascii_case("transfer-encoding") && v.eq_ignore_ascii_case("chunked") {
+                chunked = true;
+            } else if k.eq_ignore_ascii_case("content-length") {
+                length = v.parse::<u64>().ok();
+            }
+        }
+    }
+    let body: Box<dyn BufRead + Send> = match (chunked, length) {
+        (true, _) => Box::new(BufReader::new(Chunked {
+            inner: reader,
+            left: 0,
+            done: false,
+        })),
+        (false, Some(n)) => Box::new(reader.take(n)),
+        (false, None) => Box::new(reader),
+    };
+    Ok(Upstream { status, body })
+}
+
+// `Transfer-Encoding: chunked`, which llama-server uses for streams.
+struct Chunked<R> {
+    inner: R,
+    left: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.left == 0 {
+            let mut size = String::new();
+            self.inner.read_line(&mut size)?;
+            let size = size.trim().split(';').next().unwrap_or_default();
+            self.left = usize::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+            if self.left == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let max = buf.len().min(self.left);
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.left -= n;
+        if self.left == 0 {
+            let mut crlf = String::new();
+            self.inner.read_line(&mut crlf)?;
+        }
+        Ok(n)
+    }
+}
+
+// A healthy managed llama-server already serving this exact file, e.g. from
+// an earlier gateway or `ggufy serve -d`.
+fn running_for(path: &Path) -> Option<Instance> {
+    instance::list_instances().into_iter().find(|i| {
+        i.model_path.as_deref() == Some(path)
+            && i.program.ends_with("llama-server")
+            && i.port
+                .map(|p| health::probe("127.0.0.1", p) == health::Health::Ready)
+                .unwrap_or(false)
+    })
+}
+
+// OLLAMA_HOST as the Ollama CLI reads it: `host`, `host:port` or a URL.
+fn ollama_upstream() -> (String, u16) {
+    let raw = std::env::var("OLLAMA_HOST").unwrap_or_default();
+    let raw = raw.trim().trim_end_matches('/');
+    let raw = raw
+        .strip_prefix("http://")
+        .or_else(|| raw.strip_prefix("https://"))
+        .unwrap_or(raw);
+    let (host, port) = match raw.rsplit_once(':') {
+        Some((h, p)) => (h, p.parse().unwrap_or(11434)),
+        None => (raw, 11434),
+    };
+    let host = match host {
+        "" | "0.0.0.0" => "127.0.0.1",
+        h => h,
+    };
+    (host.to_string(), port)
+}
+
+// Whether `host:port` reaches this listener: its own address, or loopback
+// when it listens on loopback or on every address.
+fn is_self(host: &str, port: u16, local: SocketAddr) -> bool {
+    let Ok(addrs) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs.into_iter().any(|a| {
+        a.port() == local.port()
+            && (a.ip() == local.ip()
+                || (a.ip().is_loopback() || a.ip().is_unspecified())
+                    && (local.ip().is_loopback() || local.ip().is_unspecified()))
+    })
+}
+
+// Reads a whole request, giving up with 408 once REQUEST_TIMEOUT has passed
+// however slowly the bytes trickle in.
+fn read_request(stream: &mut TcpStream) -> std::result::Result<Request, HttpError> {
+    let bad = |m: &str| HttpError::new(400, m);
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let read = |stream: &mut TcpStream, chunk: &mut [u8]| {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let timeout = || HttpError::new(408, "request not received in time");
+        if left.is_zero() {
+            return Err(timeout());
+        }
+        let _ = stream.set_read_timeout(Some(left));
+        stream.read(chunk).map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => timeout(),
+            _ => HttpError::new(400, e.to_string()),
+        })
+    };
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let head_end = loop {
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i;
+        }
+        if buf.len() > MAX_HEAD {
+            return Err(HttpError::new(431, "request headers too large"));
+        }
+        let n = read(stream, &mut chunk)?;
+        if n == 0 {
+            return Err(bad("connection closed mid-request"));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut start = lines.next().unwrap_or("").split_whitespace();
+    let (method, path) = match (start.next(), start.next()) {
+        (Some(m), Some(p)) => (m.to_string(), p.to_string()),
+        _ => return Err(bad("malformed request line")),
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+    let mut req = Request {
+        method,
+        path,
+        headers,
+        body: buf[head_end + 4..].to_vec(),
+    };
+    if req
+        .header("Transfer-Encoding")
+        .map(|t| t.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false)
+    {
+        return Err(HttpError::new(
+            411,
+            "chunked request bodies are not supported",
+        ));
+    }
+    let len = match req.header("Content-Length") {
+        Some(v) => v.parse::<usize>().map_err(|_| bad("bad Content-Length"))?,
+        None => 0,
+    };
+    if len > MAX_BODY {
+        return Err(HttpError::new(413, "request body too large"));
+    }
+    if req.body.len() < len
+        && req
+            .header("Expect")
+            .map(|e| e.eq_ignore_ascii_case("100-continue"))
+            .unwrap_or(false)
+    {
+        let _ = stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+    while req.body.len() < len {
+        let n = read(stream, &mut chunk)?;
+        if n == 0 {
+            return Err(bad("connection closed mid-body"));
+        }
+        req.body.extend_from_slice(&chunk[..n]);
+    }
+    req.body.truncate(len);
+    Ok(req)
+}
+
+fn send_json(stream: &mut TcpStream, status: u16, body: &Value) -> HttpResult {
+    let body = serde_json::to_vec(body).unwrap();
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason_phrase(status),
+        body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&body)?;
+    Ok(())
+}
+
+fn send_text(stream: &mut TcpStream, method: &str, text: &str) -> HttpResult {
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        text.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    if method != "HEAD" {
+        stream.write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_line(stream: &mut TcpStream, v: &Value) -> io::Result<()> {
+    let mut line = serde_json::to_vec(v).unwrap();
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+// Errors in the shape each API's client libraries know how to surface.
+fn send_error(stream: &mut TcpStream, e: &HttpError, api: Api) {
+    let body = match api {
+        Api::Ollama => json!({"error": e.message}),
+        Api::OpenAi => {
+            let kind = if e.status < 500 {
+                "invalid_request_error"
+            } else {
+                "server_error"
+            };
+            json!({"error": {"message": e.message, "type": kind, "code": e.status}})
+        }
+    };
+    let _ = send_json(stream, e.status, &body);
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(unix)]
+mod shutdown {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    static STOP: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn handler(_: libc::c_int) {
+        STOP.store(true, Ordering::SeqCst);
+    }
+
+    // Runs `cleanup` and exits on SIGINT/SIGTERM. The handler only sets a
+    // flag; the cleanup itself happens on a watcher thread.
+    pub fn on_signal(cleanup: impl FnOnce() + Send + 'static) {
+        for sig in [libc::SIGINT, libc::SIGTERM] {
+            unsafe {
+                libc::signal(sig, handler as *const () as libc::sighandler_t);
+            }
+        }
+        thread::spawn(move || {
+            while !STOP.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(100));
+            }
+            cleanup();
+            std::process::exit(0);
+        });
+    }
+}
+
+// Console Ctrl-C reaches the servers too; there is nothing to relay.
+#[cfg(not(unix))]
+mod shutdown {
+    pub fn on_signal(_cleanup: impl FnOnce() + Send + 'static) {}
+}
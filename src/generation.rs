@@ -0,0 +1,160 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Captured one-shot generations (`simple --json`, `--stream ndjson`): the
+// text, plus token counts and timings scraped from the tool's stderr, i.e.
+// llama.cpp's `llama_perf_context_print` lines or `ollama run --verbose`.
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capture {
+    Json,
+    Ndjson,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StreamFormat {
+    Ndjson,
+}
+
+impl Capture {
+    // `--stream` wins over `--json`.
+    pub fn from_flags(json: bool, stream: Option<StreamFormat>) -> Option<Capture> {
+        match (stream, json) {
+            (Some(StreamFormat::Ndjson), _) => Some(Capture::Ndjson),
+            (None, true) => Some(Capture::Json),
+            (None, false) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub prompt_ms: Option<f64>,
+    pub completion_ms: Option<f64>,
+    pub total_ms: Option<f64>,
+    pub tokens_per_second: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Generation {
+    pub model: String,
+    // `llama.cpp` or `ollama`.
+    pub backend: String,
+    pub tool: String,
+    pub text: String,
+    #[serde(flatten)]
+    pub stats: Stats,
+    // Measured by ggufy around the child process.
+    pub wall_ms: f64,
+}
+
+// llama-cli ends a completion with this on stdout.
+pub const END_OF_TEXT: &str = " [end of text]";
+
+// llama_perf_context_print: prompt eval time =  12.34 ms /  5 tokens (...)
+// llama_perf_context_print:        eval time = 100.00 ms / 31 runs   (...)
+// llama_perf_context_print:       total time = 120.00 ms / 36 tokens
+pub fn parse_llama_perf(stderr: &str) -> Stats {
+    let re = Regex::new(
+        r"llama_perf_context_print:\s*(prompt eval|eval|total) time\s*=\s*([\d.]+) ms(?:\s*/\s*(\d+) (?:tokens|runs))?",
+    )
+    .unwrap();
+    let mut s = Stats::default();
+    for c in re.captures_iter(stderr) {
+        let ms = c[2].parse::<f64>().ok();
+        let n = c.get(3).and_then(|m| m.as_str().parse::<u64>().ok());
+        match &c[1] {
+            "prompt eval" => {
+                s.prompt_ms = ms;
+                s.prompt_tokens = n;
+            }
+            "eval" => {
+                s.completion_ms = ms;
+                s.completion_tokens = n;
+            }
+            _ => s.total_ms = ms,
+        }
+    }
+    s.tokens_per_second = rate(s.completion_tokens, s.completion_ms);
+    s
+}
+
+// prompt eval count:    26 token(s)
+// prompt eval duration: 1.2ms
+// eval count:           100 token(s)
+// eval duration:        2.1s
+// total duration:       2.5s
+pub fn parse_ollama_verbose(stderr: &str) -> Stats {
+    let mut s = Stats::default();
+    for line in stderr.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => continue,
+        };
+        let count = || value.split_whitespace().next()?.parse::<u64>().ok();
+        match key {
+            "prompt eval count" => s.prompt_tokens = count(),
+            "prompt eval duration" => s.prompt_ms = go_duration_ms(value),
+            "eval count" => s.completion_tokens = count(),
+            "eval duration" => s.completion_ms = go_duration_ms(value),
+            "total duration" => s.total_ms = go_duration_ms(value),
+            _ => {}
+        }
+    }
+    s.tokens_per_second = rate(s.completion_tokens, s.completion_ms);
+    s
+}
+
+fn rate(tokens: Option<u64>, ms: Option<f64>) -> Option<f64> {
+    match (tokens, ms) {
+        (Some(n), Some(ms)) if ms > 0.0 => Some(n as f64 * 1000.0 / ms),
+        _ => None,
+    }
+}
+
+// Go's time.Duration text: `1m2.5s`, `350.2ms`, `12µs`, `800ns`.
+fn go_duration_ms(text: &str) -> Option<f64> {
+    let re = Regex::new(r"([\d.]+)(h|ms|µs|us|ns|m|s)").unwrap();
+    let mut total = 0.0;
+    let mut any = false;
+    for c in re.captures_iter(text) {
+        let v: f64 = c[1].parse().ok()?;
+        total += v * match &c[2] {
+            "h" => 3_600_000.0,
+            "m" => 60_000.0,
+            "s" => 1000.0,
+            "ms" => 1.0,
+            "µs" | "us" => 0.001,
+            _ => 0.000_001,
+        };
+        any = true;
+    }
+    any.then_some(total)
+}
+
+pub fn emit_chunk(text: &str) {
+    let line = serde_json::json!({"type": "chunk", "text": text});
+    let mut out = io::stdout().lock();
+    let _ = writeln!(out, "{}", line);
+    let _ = out.flush();
+}
+
+pub fn emit(generation: &Generation, capture: Capture) {
+    let body = serde_json::to_value(generation).unwrap();
+    match capture {
+        Capture::Json => println!("{}", serde_json::to_string_pretty(&body).unwrap()),
+        Capture::Ndjson => {
+            let mut done = serde_json::json!({"type": "done"});
+            done.as_object_mut()
+                .unwrap()
+                .extend(body.as_object().unwrap().clone());
+            println!("{}", done);
+        }
+    }
+}
@@ -0,0 +1,599 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// GGUF header reader. Only the header, metadata and tensor-info table are
+// read; tensor data is never touched, so this is cheap even on multi-GB blobs.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufReader, Error, ErrorKind, Read, Result as IoResult};
+use std::path::Path;
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const DEFAULT_ALIGNMENT: u64 = 32;
+// Upper bounds used to reject corrupt headers before allocating.
+const MAX_STRING_LEN: u64 = 1 << 30;
+const MAX_ARRAY_LEN: u64 = 1 << 32;
+const MAX_DIMS: u32 = 8;
+// Arrays of arrays recurse; deeper nesting than this is not a real model.
+const MAX_ARRAY_DEPTH: u32 = 8;
+// Counts are only bounded by the file size, so vectors start no larger than
+// this and grow as entries actually parse.
+const MAX_PREALLOC: u64 = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+    Array(Vec<GgufValue>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl GgufValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            GgufValue::U8(v) => Some(v as u64),
+            GgufValue::U16(v) => Some(v as u64),
+            GgufValue::U32(v) => Some(v as u64),
+            GgufValue::U64(v) => Some(v),
+            GgufValue::I8(v) if v >= 0 => Some(v as u64),
+            GgufValue::I16(v) if v >= 0 => Some(v as u64),
+            GgufValue::I32(v) if v >= 0 => Some(v as u64),
+            GgufValue::I64(v) if v >= 0 => Some(v as u64),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> String {
+        match self {
+            GgufValue::U8(_) => "u8".into(),
+            GgufValue::I8(_) => "i8".into(),
+            GgufValue::U16(_) => "u16".into(),
+            GgufValue::I16(_) => "i16".into(),
+            GgufValue::U32(_) => "u32".into(),
+            GgufValue::I32(_) => "i32".into(),
+            GgufValue::F32(_) => "f32".into(),
+            GgufValue::Bool(_) => "bool".into(),
+            GgufValue::String(_) => "str".into(),
+            GgufValue::U64(_) => "u64".into(),
+            GgufValue::I64(_) => "i64".into(),
+            GgufValue::F64(_) => "f64".into(),
+            GgufValue::Array(items) => match items.first() {
+                Some(first) => format!("arr[{},{}]", first.type_name(), items.len()),
+                None => "arr[0]".into(),
+            },
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            GgufValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+// Projector types whose encoder takes audio (llama.cpp's mtmd).
+const AUDIO_PROJECTORS: &[&str] = &["ultravox", "qwen2a", "qwen25o", "voxtral", "whisper"];
+// Vocoders that turn TTS audio codes into a waveform.
+const VOCODER_ARCHS: &[&str] = &["wavtokenizer-dec"];
+// Prompt markers of OuteTTS-style text-to-speech models; all must be in the
+// vocabulary.
+const TTS_TOKENS: &[&str] = &["<|text_start|>", "<|audio_start|>"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelKind {
+    Text,
+    // Multimodal projector (mmproj); loaded next to a model, never alone.
+    Projector,
+    // Turns a TTS model's audio codes into a waveform (`llama-tts -mv`).
+    Vocoder,
+    Tts,
+}
+
+#[derive(Debug, Clone)]
+pub struct TensorInfo {
+    pub name: String,
+    pub dims: Vec<u64>,
+    pub ggml_type: u32,
+    pub offset: u64,
+}
+
+impl TensorInfo {
+    // `read_gguf` rejects tensors whose element count overflows, so this
+    // only saturates for hand-built ones.
+    pub fn n_elements(&self) -> u64 {
+        self.checked_elements().unwrap_or(u64::MAX)
+    }
+
+    fn checked_elements(&self) -> Option<u64> {
+        self.dims.iter().try_fold(1u64, |n, d| n.checked_mul(*d))
+    }
+
+    pub fn n_bytes(&self) -> Option<u64> {
+        let (block, size) = ggml_type_layout(self.ggml_type)?;
+        (self.n_elements() / block).checked_mul(size)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GgufFile {
+    pub version: u32,
+    pub metadata: Vec<(String, GgufValue)>,
+    pub tensors: Vec<TensorInfo>,
+    pub alignment: u64,
+    pub data_offset: u64,
+    pub file_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GgufSummary {
+    pub architecture: Option<String>,
+    pub name: Option<String>,
+    pub parameter_count: u64,
+    pub quantization: Option<String>,
+    pub context_length: Option<u64>,
+    pub file_type: Option<u32>,
+    pub version: u32,
+    pub tensor_count: usize,
+    pub data_size: u64,
+    // Set for multimodal projector (mmproj) files: what they encode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub projector: Vec<String>,
+}
+
+impl GgufFile {
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(|v| v.as_str())
+    }
+
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(|v| v.as_u64())
+    }
+
+    pub fn architecture(&self) -> Option<&str> {
+        self.get_str("general.architecture")
+    }
+
+    // Architecture-scoped key, e.g. `llama.context_length`.
+    pub fn arch_u64(&self, suffix: &str) -> Option<u64> {
+        let arch = self.architecture()?;
+        self.get_u64(&format!("{}.{}", arch, suffix))
+    }
+
+    // Modalities of a `clip` projector file: the `clip.has_*_encoder` flags,
+    // else its encoder tensors (`v.blk.*`, `a.blk.*`) or an audio projector
+    // type. Older mmproj files have none of these and are vision-only.
+    pub fn projector_modalities(&self) -> Vec<String> {
+        if self.architecture() != Some("clip") {
+            return Vec::new();
+        }
+        let flag = |m: &str| {
+            self.get(&format!("clip.has_{}_encoder", m))
+                .and_then(|v| v.as_bool())
+        };
+        let tensors = |prefix: &str| self.tensors.iter().any(|t| t.name.starts_with(prefix));
+        let audio_type = ["clip.projector_type", "clip.audio.projector_type"]
+            .iter()
+            .filter_map(|k| self.get_str(k))
+            .any(|t| AUDIO_PROJECTORS.contains(&t));
+        let (vision, audio) = match (flag("vision"), flag("audio")) {
+            (None, None) => (tensors("v.blk."), tensors("a.blk.") || audio_type),
+            (v, a) => (v == Some(true), a == Some(true)),
+        };
+        let mut v = Vec::new();
+        if vision || !audio {
+            v.push("vision".to_string());
+        }
+        if audio {
+            v.push("audio".to_string());
+        }
+        v
+    }
+
+    // What a GGUF is for, judged from metadata and tensor names only.
+    pub fn kind(&self) -> ModelKind {
+        let arch = self.architecture().unwrap_or_default();
+        if arch == "clip" {
+            return ModelKind::Projector;
+        }
+        if VOCODER_ARCHS.contains(&arch)
+            || self.tensors.iter().any(|t| t.name.starts_with("posnet."))
+        {
+            return ModelKind::Vocoder;
+        }
+        let speaks = match self.get("tokenizer.ggml.tokens") {
+            Some(GgufValue::Array(tokens)) => TTS_TOKENS
+                .iter()
+                .all(|m| tokens.iter().any(|t| t.as_str() == Some(*m))),
+            _ => false,
+        };
+        if speaks {
+            ModelKind::Tts
+        } else {
+            ModelKind::Text
+        }
+    }
+
+    pub fn parameter_count(&self) -> u64 {
+        self.tensors
+            .iter()
+            .fold(0u64, |n, t| n.saturating_add(t.n_elements()))
+    }
+
+    pub fn file_type(&self) -> Option<u32> {
+        self.get_u64("general.file_type").map(|v| v as u32)
+    }
+
+    // Prefer the declared `general.file_type`; otherwise report the ggml type
+    // that accounts for most of the tensor bytes.
+    pub fn quantization(&self) -> Option<String> {
+        if let Some(name) = self.file_type().and_then(file_type_name) {
+            return Some(name.to_string());
+        }
+        let mut by_type: Vec<(u32, u64)> = Vec::new();
+        for t in &self.tensors {
+            let bytes = t.n_bytes().unwrap_or(0);
+            match by_type.iter_mut().find(|(ty, _)| *ty == t.ggml_type) {
+                Some((_, total)) => *total += bytes,
+                None => by_type.push((t.ggml_type, bytes)),
+            }
+        }
+        by_type
+            .into_iter()
+            .max_by_key(|(_, bytes)| *bytes)
+            .and_then(|(ty, _)| ggml_type_name(ty))
+            .map(|s| s.to_string())
+    }
+
+    pub fn summary(&self) -> GgufSummary {
+        GgufSummary {
+            architecture: self.architecture().map(|s| s.to_string()),
+            name: self.get_str("general.name").map(|s| s.to_string()),
+            parameter_count: self.parameter_count(),
+            quantization: self.quantization(),
+            context_length: self.arch_u64("context_length"),
+            file_type: self.file_type(),
+            version: self.version,
+            tensor_count: self.tensors.len(),
+            data_size: self.file_size.saturating_sub(self.data_offset),
+            projector: self.projector_modalities(),
+        }
+    }
+}
+
+pub fn is_gguf_file(p: &Path) -> IoResult<bool> {
+    let mut f = fs::File::open(p)?;
+    let mut head = [0u8; 4];
+    let n = f.read(&mut head)?;
+    Ok(n >= 4 && &head == GGUF_MAGIC)
+}
+
+pub fn read_gguf(p: &Path) -> IoResult<GgufFile> {
+    let f = fs::File::open(p)?;
+    let file_size = f.metadata()?.len();
+    let mut r = GgufReader {
+        inner: BufReader::new(f),
+        pos: 0,
+    };
+
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != GGUF_MAGIC {
+        return Err(invalid("not a GGUF file (bad magic)"));
+    }
+    let version = r.u32()?;
+    if version == 1 {
+        return Err(invalid("GGUF version 1 is not supported"));
+    }
+    if version != 2 && version != 3 {
+        if version.swap_bytes() <= 3 {
+            return Err(invalid("big-endian GGUF files are not supported"));
+        }
+        return Err(invalid(&format!("unknown GGUF version {}", version)));
+    }
+
+    let tensor_count = r.u64()?;
+    let kv_count = r.u64()?;
+    if tensor_count > file_size || kv_count > file_size {
+        return Err(invalid("corrupt GGUF header (counts exceed file size)"));
+    }
+
+    let mut metadata = Vec::with_capacity(kv_count.min(MAX_PREALLOC) as usize);
+    for _ in 0..kv_count {
+        let key = r.string()?;
+        let ty = r.u32()?;
+        let value = r.value(ty, file_size, 0)?;
+        metadata.push((key, value));
+    }
+
+    let mut tensors = Vec::with_capacity(tensor_count.min(MAX_PREALLOC) as usize);
+    for _ in 0..tensor_count {
+        let name = r.string()?;
+        let n_dims = r.u32()?;
+        if n_dims > MAX_DIMS {
+            return Err(invalid(&format!(
+                "tensor {} has {} dimensions",
+                name, n_dims
+            )));
+        }
+        let mut dims = Vec::with_capacity(n_dims as usize);
+        for _ in 0..n_dims {
+            dims.push(r.u64()?);
+        }
+        let ggml_type = r.u32()?;
+        let offset = r.u64()?;
+        let t = TensorInfo {
+            name,
+            dims,
+            ggml_type,
+            offset,
+        };
+        if t.checked_elements().is_none() {
+            return Err(invalid(&format!("tensor {} is too large", t.name)));
+        }
+        tensors.push(t);
+    }
+
+    let alignment = metadata
+        .iter()
+        .find(|(k, _)| k == "general.alignment")
+        .and_then(|(_, v)| v.as_u64())
+        .filter(|a| *a > 0)
+        .unwrap_or(DEFAULT_ALIGNMENT);
+    let data_offset = r
+        .pos
+        .div_ceil(alignment)
+        .checked_mul(alignment)
+        .ok_or_else(|| invalid("corrupt GGUF alignment"))?;
+
+    for t in &tensors {
+        if t.offset % alignment != 0 {
+            return Err(invalid(&format!("tensor {} is not aligned", t.name)));
+        }
+        let end = t
+            .n_bytes()
+            .or(Some(0))
+            .and_then(|n| data_offset.checked_add(t.offset)?.checked_add(n));
+        if end.is_none_or(|end| end > file_size) {
+            return Err(invalid(&format!(
+                "tensor {} extends past end of file",
+                t.name
+            )));
+        }
+    }
+
+    Ok(GgufFile {
+        version,
+        metadata,
+        tensors,
+        alignment,
+        data_offset,
+        file_size,
+    })
+}
+
+struct GgufReader<R: Read> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: Read> GgufReader<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> IoResult<()> {
+        self.inner.read_exact(buf)?;
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+
+    fn bytes<const N: usize>(&mut self) -> IoResult<[u8; N]> {
+        let mut b = [0u8; N];
+        self.read_exact(&mut b)?;
+        Ok(b)
+    }
+
+    fn u32(&mut self) -> IoResult<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> IoResult<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn string(&mut self) -> IoResult<String> {
+        let len = self.u64()?;
+        if len > MAX_STRING_LEN {
+            return Err(invalid("corrupt GGUF string length"));
+        }
+        let mut buf = Vec::new();
+        (&mut self.inner).take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.pos += len;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn value(&mut self, ty: u32, file_size: u64, depth: u32) -> IoResult<GgufValue> {
+        Ok(match ty {
+            0 => GgufValue::U8(u8::from_le_bytes(self.bytes()?)),
+            1 => GgufValue::I8(i8::from_le_bytes(self.bytes()?)),
+            2 => GgufValue::U16(u16::from_le_bytes(self.bytes()?)),
+            3 => GgufValue::I16(i16::from_le_bytes(self.bytes()?)),
+            4 => GgufValue::U32(self.u32()?),
+            5 => GgufValue::I32(i32::from_le_bytes(self.bytes()?)),
+            6 => GgufValue::F32(f32::from_le_bytes(self.bytes()?)),
+            7 => GgufValue::Bool(self.bytes::<1>()?[0] != 0),
+            8 => GgufValue::String(self.string()?),
+            9 => {
+                if depth >= MAX_ARRAY_DEPTH {
+                    return Err(invalid("GGUF arrays nested too deeply"));
+                }
+                let elem_ty = self.u32()?;
+                let len = self.u64()?;
+                if len > MAX_ARRAY_LEN || len > file_size {
+                    return Err(invalid("corrupt GGUF array length"));
+                }
+                let mut items = Vec::with_capacity(len.min(MAX_PREALLOC) as usize);
+                for _ in 0..len {
+                    items.push(self.value(elem_ty, file_size, depth + 1)?);
+                }
+                GgufValue::Array(items)
+            }
+            10 => GgufValue::U64(self.u64()?),
+            11 => GgufValue::I64(i64::from_le_bytes(self.bytes()?)),
+            12 => GgufValue::F64(f64::from_le_bytes(self.bytes()?)),
+            other => return Err(invalid(&format!("unknown GGUF value type {}", other))),
+        })
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// (block size in elements, bytes per block) for each ggml tensor type.
+pub fn ggml_type_layout(ty: u32) -> Option<(u64, u64)> {
+    Some(match ty {
+        0 => (1, 4),
+        1 => (1, 2),
+        2 => (32, 18),
+        3 => (32, 20),
+        6 => (32, 22),
+        7 => (32, 24),
+        8 => (32, 34),
+        9 => (32, 36),
+        10 => (256, 84),
+        11 => (256, 110),
+        12 => (256, 144),
+        13 => (256, 176),
+        14 => (256, 210),
+        15 => (256, 292),
+        16 => (256, 66),
+        17 => (256, 74),
+        18 => (256, 98),
+        19 => (256, 50),
+        20 => (32, 18),
+        21 => (256, 110),
+        22 => (256, 82),
+        23 => (256, 136),
+        24 => (1, 1),
+        25 => (1, 2),
+        26 => (1, 4),
+        27 => (1, 8),
+        28 => (1, 8),
+        29 => (256, 56),
+        30 => (1, 2),
+        34 => (256, 54),
+        35 => (256, 66),
+        39 => (32, 17),
+        _ => return None,
+    })
+}
+
+pub fn ggml_type_name(ty: u32) -> Option<&'static str> {
+    Some(match ty {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        6 => "Q5_0",
+        7 => "Q5_1",
+        8 => "Q8_0",
+        9 => "Q8_1",
+        10 => "Q2_K",
+        11 => "Q3_K",
+        12 => "Q4_K",
+        13 => "Q5_K",
+        14 => "Q6_K",
+        15 => "Q8_K",
+        16 => "IQ2_XXS",
+        17 => "IQ2_XS",
+        18 => "IQ3_XXS",
+        19 => "IQ1_S",
+        20 => "IQ4_NL",
+        21 => "IQ3_S",
+        22 => "IQ2_S",
+        23 => "IQ4_XS",
+        24 => "I8",
+        25 => "I16",
+        26 => "I32",
+        27 => "I64",
+        28 => "F64",
+        29 => "IQ1_M",
+        30 => "BF16",
+        34 => "TQ1_0",
+        35 => "TQ2_0",
+        39 => "MXFP4",
+        _ => return None,
+    })
+}
+
+// llama.cpp `llama_ftype` names as stored in `general.file_type`.
+pub fn file_type_name(ft: u32) -> Option<&'static str> {
+    Some(match ft {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        38 => "MXFP4_MOE",
+        _ => return None,
+    })
+}
+
+pub fn format_param_count(n: u64) -> String {
+    if n >= 1_000_000_000 {
+        format!("{:.1}B", n as f64 / 1e9)
+    } else if n >= 1_000_000 {
+        format!("{:.0}M", n as f64 / 1e6)
+    } else {
+        n.to_string()
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Readiness probing for llama-server: GET /health answers 503 while the model
+// is loading and 200 once it can serve requests. Plain std TCP is enough for
+// one request line and a status code.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    Ready,
+    // Listening, but not ready yet (503 or any other status).
+    Loading,
+    // Nothing accepted the connection.
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotReady {
+    Exited,
+    TimedOut { timeout: Duration, last: Health },
+}
+
+impl fmt::Display for NotReady {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotReady::Exited => write!(f, "server exited before it was ready"),
+            NotReady::TimedOut { timeout, last } => write!(
+                f,
+                "timed out after {}s ({})",
+                timeout.as_secs(),
+                if *last == Health::Down {
+                    "not listening"
+                } else {
+                    "still loading"
+                }
+            ),
+        }
+    }
+}
+
+pub fn base_url(host: &str, port: u16) -> String {
+    format!("http://{}:{}", host, port)
+}
+
+pub fn probe(host: &str, port: u16) -> Health {
+    let addr = match (host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut a| a.next())
+    {
+        Some(a) => a,
+        None => return Health::Down,
+    };
+    let mut stream = match TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) {
+        Ok(s) => s,
+        Err(_) => return Health::Down,
+    };
+    let _ = stream.set_read_timeout(Some(PROBE_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(PROBE_TIMEOUT));
+    let req = format!(
+        "GET /health HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\n\r\n",
+        host, port
+    );
+    if stream.write_all(req.as_bytes()).is_err() {
+        return Health::Loading;
+    }
+    // The whole response is tiny; drain it so the server is not left
+    // writing into a reset connection.
+    let mut buf = Vec::new();
+    let _ = stream.take(4096).read_to_end(&mut buf);
+    let status = String::from_utf8_lossy(&buf);
+    match status
+        .lines()
+        .next()
+        .and_then(|l| l.split_whitespace().nth(1))
+    {
+        Some("200") => Health::Ready,
+        _ => Health::Loading,
+    }
+}
+
+// Polls until the server reports ready, `alive` returns false, or `timeout`
+// passes.
+pub fn wait_ready(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    mut alive: impl FnMut() -> bool,
+) -> Result<(), NotReady> {
+    let start = Instant::now();
+    loop {
+        let last = probe(host, port);
+        if last == Health::Ready {
+            return Ok(());
+        }
+        if !alive() {
+            return Err(NotReady::Exited);
+        }
+        if start.elapsed() >= timeout {
+            return Err(NotReady::TimedOut { timeout, last });
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
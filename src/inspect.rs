@@ -0,0 +1,281 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Rendering for `ggufy inspect`: human-readable text or JSON.
+
+use crate::gguf::{ggml_type_name, GgufFile, GgufValue, ModelKind};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+// Arrays longer than this are abbreviated unless `--full` is given.
+const ARRAY_PREVIEW: usize = 8;
+
+pub fn render_text(path: &Path, g: &GgufFile, full: bool) -> String {
+    let mut out = String::new();
+    let s = g.summary();
+    out.push_str(&format!("file:         {}\n", path.display()));
+    out.push_str(&format!("gguf version: {}\n", g.version));
+    out.push_str(&format!("file size:    {}\n", human_bytes(g.file_size)));
+    out.push_str(&format!(
+        "alignment:    {} (data offset {})\n",
+        g.alignment, g.data_offset
+    ));
+    out.push_str(&format!(
+        "architecture: {}\n",
+        s.architecture.as_deref().unwrap_or("unknown")
+    ));
+    out.push_str(&format!("kind:         {}\n", kind_label(g)));
+    out.push_str(&format!(
+        "parameters:   {} ({})\n",
+        crate::gguf::format_param_count(s.parameter_count),
+        s.parameter_count
+    ));
+    if let Some(q) = &s.quantization {
+        out.push_str(&format!("quantization: {}\n", q));
+    }
+    if let Some(ctx) = s.context_length {
+        out.push_str(&format!("context:      {}\n", ctx));
+    }
+
+    out.push_str(&format!("\nmetadata ({} keys):\n", g.metadata.len()));
+    for (k, v) in &g.metadata {
+        out.push_str(&format!(
+            "  {} [{}] = {}\n",
+            k,
+            v.type_name(),
+            value_text(v, full)
+        ));
+    }
+
+    out.push_str("\ntokenizer:\n");
+    for (k, v) in tokenizer_summary(g) {
+        out.push_str(&format!("  {}: {}\n", k, v));
+    }
+
+    out.push_str(&format!("\ntensors ({}):\n", g.tensors.len()));
+    for t in &g.tensors {
+        let shape: Vec<String> = t.dims.iter().map(|d| d.to_string()).collect();
+        out.push_str(&format!(
+            "  {:<48} {:<20} {:<8} {}\n",
+            t.name,
+            format!("[{}]", shape.join(", ")),
+            type_label(t.ggml_type),
+            t.n_bytes().map(human_bytes).unwrap_or_else(|| "?".into())
+        ));
+    }
+
+    out.push_str("\nsize by type:\n");
+    for (ty, count, bytes) in size_by_type(g) {
+        out.push_str(&format!(
+            "  {:<8} {:>6} tensors  {}\n",
+            type_label(ty),
+            count,
+            human_bytes(bytes)
+        ));
+    }
+    out
+}
+
+pub fn render_json(path: &Path, g: &GgufFile, full: bool) -> Value {
+    let s = g.summary();
+    let mut metadata = Map::new();
+    // Arrays stay arrays when cut short; their full length goes in `truncated`.
+    let mut truncated = Map::new();
+    for (k, v) in &g.metadata {
+        if let GgufValue::Array(items) = v {
+            if !full && items.len() > ARRAY_PREVIEW {
+                truncated.insert(k.clone(), json!(items.len()));
+            }
+        }
+        metadata.insert(k.clone(), value_json(v, full));
+    }
+    let tensors: Vec<Value> = g
+        .tensors
+        .iter()
+        .map(|t| {
+            json!({
+                "name": t.name,
+                "shape": t.dims,
+                "type": type_label(t.ggml_type),
+                "offset": t.offset,
+                "bytes": t.n_bytes(),
+            })
+        })
+        .collect();
+    let by_type: Vec<Value> = size_by_type(g)
+        .into_iter()
+        .map(|(ty, count, bytes)| json!({"type": type_label(ty), "tensors": count, "bytes": bytes}))
+        .collect();
+    let tokenizer: Map<String, Value> = tokenizer_summary(g)
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), Value::String(v)))
+        .collect();
+    json!({
+        "path": path.to_string_lossy(),
+        "version": g.version,
+        "file_size": g.file_size,
+        "alignment": g.alignment,
+        "data_offset": g.data_offset,
+        "architecture": s.architecture,
+        "name": s.name,
+        "kind": g.kind(),
+        "projector": s.projector,
+        "parameter_count": s.parameter_count,
+        "quantization": s.quantization,
+        "file_type": s.file_type,
+        "context_length": s.context_length,
+        "metadata": metadata,
+        "truncated": truncated,
+        "tokenizer": tokenizer,
+        "tensors": tensors,
+        "size_by_type": by_type,
+    })
+}
+
+fn kind_label(g: &GgufFile) -> String {
+    match g.kind() {
+        ModelKind::Text => "text".to_string(),
+        ModelKind::Tts => "text-to-speech".to_string(),
+        ModelKind::Vocoder => "vocoder".to_string(),
+        ModelKind::Projector => format!("projector ({})", g.projector_modalities().join(", ")),
+    }
+}
+
+fn tokenizer_summary(g: &GgufFile) -> Vec<(&'static str, String)> {
+    let mut v = Vec::new();
+    if let Some(m) = g.get_str("tokenizer.ggml.model") {
+        v.push(("model", m.to_string()));
+    }
+    if let Some(p) = g.get_str("tokenizer.ggml.pre") {
+        v.push(("pre", p.to_string()));
+    }
+    let tokens = match g.get("tokenizer.ggml.tokens") {
+        Some(GgufValue::Array(a)) => Some(a),
+        _ => None,
+    };
+    if let Some(t) = tokens {
+        v.push(("vocab size", t.len().to_string()));
+    }
+    if let Some(GgufValue::Array(m)) = g.get("tokenizer.ggml.merges") {
+        v.push(("merges", m.len().to_string()));
+    }
+    for (label, key) in [
+        ("bos", "tokenizer.ggml.bos_token_id"),
+        ("eos", "tokenizer.ggml.eos_token_id"),
+        ("pad", "tokenizer.ggml.padding_token_id"),
+        ("unk", "tokenizer.ggml.unknown_token_id"),
+    ] {
+        if let Some(id) = g.get_u64(key) {
+            let text = tokens
+                .and_then(|t| t.get(id as usize))
+                .and_then(|t| t.as_str())
+                .map(|t| format!(" {:?}", t))
+                .unwrap_or_default();
+            v.push((label, format!("{}{}", id, text)));
+        }
+    }
+    if let Some(t) = g.get_str("tokenizer.chat_template") {
+        v.push(("chat template", format!("{} chars", t.len())));
+    }
+    if v.is_empty() {
+        v.push(("model", "none".to_string()));
+    }
+    v
+}
+
+fn size_by_type(g: &GgufFile) -> Vec<(u32, usize, u64)> {
+    let mut totals: Vec<(u32, usize, u64)> = Vec::new();
+    for t in &g.tensors {
+        let bytes = t.n_bytes().unwrap_or(0);
+        match totals.iter_mut().find(|(ty, _, _)| *ty == t.ggml_type) {
+            Some((_, count, total)) => {
+                *count += 1;
+                *total += bytes;
+            }
+            None => totals.push((t.ggml_type, 1, bytes)),
+        }
+    }
+    totals.sort_by_key(|t| std::cmp::Reverse(t.2));
+    totals
+}
+
+fn type_label(ty: u32) -> String {
+    ggml_type_name(ty)
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("type{}", ty))
+}
+
+fn value_text(v: &GgufValue, full: bool) -> String {
+    match v {
+        GgufValue::String(s) => {
+            if !full && s.len() > 120 {
+                let cut: String = s.chars().take(80).collect();
+                format!("{:?}… ({} chars)", cut, s.len())
+            } else {
+                format!("{:?}", s)
+            }
+        }
+        GgufValue::Array(items) => {
+            let shown = if full {
+                items.len()
+            } else {
+                items.len().min(ARRAY_PREVIEW)
+            };
+            let mut parts: Vec<String> =
+                items[..shown].iter().map(|i| value_text(i, full)).collect();
+            if shown < items.len() {
+                parts.push(format!("… {} more", items.len() - shown));
+            }
+            format!("[{}]", parts.join(", "))
+        }
+        other => scalar_json(other).to_string(),
+    }
+}
+
+fn value_json(v: &GgufValue, full: bool) -> Value {
+    match v {
+        GgufValue::Array(items) => {
+            let keep = if full { items.len() } else { ARRAY_PREVIEW };
+            Value::Array(
+                items
+                    .iter()
+                    .take(keep)
+                    .map(|i| value_json(i, full))
+                    .collect(),
+            )
+        }
+        other => scalar_json(other),
+    }
+}
+
+pub fn scalar_json(v: &GgufValue) -> Value {
+    match v {
+        GgufValue::U8(x) => json!(x),
+        GgufValue::I8(x) => json!(x),
+        GgufValue::U16(x) => json!(x),
+        GgufValue::I16(x) => json!(x),
+        GgufValue::U32(x) => json!(x),
+        GgufValue::I32(x) => json!(x),
+        GgufValue::F32(x) => json!(x),
+        GgufValue::Bool(x) => json!(x),
+        GgufValue::String(x) => json!(x),
+        GgufValue::U64(x) => json!(x),
+        GgufValue::I64(x) => json!(x),
+        GgufValue::F64(x) => json!(x),
+        GgufValue::Array(_) => Value::Null,
+    }
+}
+
+pub fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut v = n as f64;
+    let mut u = 0;
+    while v >= 1024.0 && u < UNITS.len() - 1 {
+        v /= 1024.0;
+        u += 1;
+    }
+    if u == 0 {
+        format!("{} B", n)
+    } else {
+        format!("{:.2} {}", v, UNITS[u])
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Managed llama-server instances. Each launch writes `<id>.json` into the
+// state dir (`~/.guffy/run`, or `GGUFY_STATE_DIR`), plus `<id>.log` when
+// running detached; records whose process has gone away are pruned when the
+// dir is read. A record also names its process's start time, so a pid reused
+// by something else (after a reboot, say) is never taken for the server.
+
+use crate::error::{GgufyError, Result};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How long `stop` waits after SIGTERM before killing outright.
+const STOP_GRACE: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Instance {
+    pub id: String,
+    pub pid: u32,
+    pub port: Option<u16>,
+    pub model: String,
+    pub model_path: Option<PathBuf>,
+    pub program: String,
+    pub args: Vec<String>,
+    pub started_at: u64,
+    // None for attached instances, whose output goes to the terminal.
+    #[serde(default)]
+    pub log: Option<PathBuf>,
+    // Clock ticks after boot at which the process started; None where that
+    // is unknown (older records, non-Linux systems).
+    #[serde(default)]
+    pub start_time: Option<u64>,
+}
+
+impl Instance {
+    pub fn is_alive(&self) -> bool {
+        pid_alive(self.pid)
+            && (self.start_time.is_none() || process_start(self.pid) == self.start_time)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        Duration::from_secs(now_secs().saturating_sub(self.started_at))
+    }
+
+    // A selector is an instance id, a port number, or a model name/path.
+    pub fn matches(&self, selector: &str) -> bool {
+        if self.id == selector || self.model == selector {
+            return true;
+        }
+        if let (Ok(port), Some(own)) = (selector.parse::<u16>(), self.port) {
+            return port == own;
+        }
+        match &self.model_path {
+            Some(p) => {
+                p.as_os_str() == selector
+                    || p.file_name().map(|n| n == selector).unwrap_or(false)
+                    || p.file_stem().map(|n| n == selector).unwrap_or(false)
+            }
+            None => false,
+        }
+    }
+
+    fn record_path(&self) -> PathBuf {
+        state_dir().join(format!("{}.json", self.id))
+    }
+
+    fn save(&self) -> Result<()> {
+        let body = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        fs::write(self.record_path(), body)?;
+        Ok(())
+    }
+
+    pub fn remove(&self) {
+        let _ = fs::remove_file(self.record_path());
+    }
+}
+
+pub fn state_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("GGUFY_STATE_DIR") {
+        return PathBuf::from(dir);
+    }
+    let mut p = home_dir().expect("home");
+    p.push(".guffy/run");
+    p
+}
+
+// Running instances, oldest first. Stale records are deleted along the way.
+pub fn list_instances() -> Vec<Instance> {
+    let dir = state_dir();
+    let mut v = Vec::new();
+    let entries = match fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(_) => return v,
+    };
+    for e in entries.flatten() {
+        let p = e.path();
+        if p.extension().map(|x| x != "json").unwrap_or(true) {
+            continue;
+        }
+        let inst = match fs::read_to_string(&p)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Instance>(&s).ok())
+        {
+            Some(i) => i,
+            None => continue,
+        };
+        if inst.is_alive() {
+            v.push(inst);
+        } else {
+            inst.remove();
+        }
+    }
+    v.sort_by(|a, b| (a.started_at, &a.id).cmp(&(b.started_at, &b.id)));
+    v
+}
+
+pub fn find_instances(selector: &str) -> Result<Vec<Instance>> {
+    let found: Vec<Instance> = list_instances()
+        .into_iter()
+        .filter(|i| i.matches(selector))
+        .collect();
+    if found.is_empty() {
+        return Err(GgufyError::InstanceNotFound(selector.to_string()));
+    }
+    Ok(found)
+}
+
+// Starts `cmd` in the background with stdout and stderr appended to a fresh
+// log file and records it in the state dir. The child handle is returned so
+// the caller can watch for an early exit; dropping it leaves the server
+// running.
+pub fn spawn_managed(
+    mut cmd: Command,
+    model: &str,
+    model_path: Option<&Path>,
+    port: Option<u16>,
+) -> Result<(Instance, Child)> {
+    let dir = state_dir();
+    fs::create_dir_all(&dir)?;
+    let id = new_id(&dir);
+    let log = dir.join(format!("{}.log", id));
+    let out = File::create(&log)?;
+    let err = out.try_clone()?;
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::from(out))
+        .stderr(Stdio::from(err));
+    let (program, args) = describe(&cmd);
+    let child = cmd.spawn().map_err(|source| GgufyError::Spawn {
+        program: program.clone(),
+        source,
+    })?;
+    let inst = Instance {
+        id,
+        pid: child.id(),
+        port,
+        model: model.to_string(),
+        model_path: model_path.map(|p| p.to_path_buf()),
+        program,
+        args,
+        started_at: now_secs(),
+        log: Some(log),
+        start_time: process_start(child.id()),
+    };
+    inst.save()?;
+    Ok((inst, child))
+}
+
+// Records an already-running attached child so `ps` and `stop` can see it.
+// The caller removes the record once the child has exited.
+pub fn track_attached(
+    cmd: &Command,
+    pid: u32,
+    model: &str,
+    model_path: Option<&Path>,
+    port: Option<u16>,
+) -> Result<Instance> {
+    let dir = state_dir();
+    fs::create_dir_all(&dir)?;
+    let (program, args) = describe(cmd);
+    let inst = Instance {
+        id: new_id(&dir),
+        pid,
+        port,
+        model: model.to_string(),
+        model_path: model_path.map(|p| p.to_path_buf()),
+        program,
+        args,
+        started_at: now_secs(),
+        log: None,
+        start_time: process_start(pid),
+    };
+    inst.save()?;
+    Ok(inst)
+}
+
+fn describe(cmd: &Command) -> (String, Vec<String>) {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let args = cmd
+        .get_args()
+        .map(|a| a.to_string_lossy().to_string())
+        .collect();
+    (program, args)
+}
+
+// SIGTERM, then SIGKILL if the process is still around after STOP_GRACE.
+pub fn stop_instance(inst: &Instance) -> Result<()> {
+    if inst.is_alive() {
+        terminate(inst.pid, false)?;
+        let mut waited = Duration::ZERO;
+        while inst.is_alive() && waited < STOP_GRACE {
+            thread::sleep(Duration::from_millis(100));
+            waited += Duration::from_millis(100);
+        }
+        if inst.is_alive() {
+            terminate(inst.pid, true)?;
+        }
+    }
+    inst.remove();
+    Ok(())
+}
+
+// Logs are kept after the record is pruned, so a crashed server can still be
+// looked at by id.
+pub fn stale_log(id: &str) -> Option<PathBuf> {
+    let p = state_dir().join(format!("{}.log", id));
+    p.is_file().then_some(p)
+}
+
+// Copies a log to `out`. With `follow`, keeps polling for new output until
+// `pid` exits.
+pub fn copy_log(
+    log: &Path,
+    pid: Option<u32>,
+    tail: Option<usize>,
+    follow: bool,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut f = File::open(log)?;
+    let mut buf = Vec::new();
+    f.read_to_end(&mut buf)?;
+    let start = match tail {
+        Some(n) => tail_offset(&buf, n),
+        None => 0,
+    };
+    out.write_all(&buf[start..])?;
+    out.flush()?;
+    if !follow {
+        return Ok(());
+    }
+    let mut pos = buf.len() as u64;
+    loop {
+        let len = f.metadata()?.len();
+        if len < pos {
+            // Truncated underneath us; start over.
+            pos = 0;
+        }
+        if len > pos {
+            f.seek(SeekFrom::Start(pos))?;
+            let mut chunk = Vec::new();
+            f.read_to_end(&mut chunk)?;
+            pos += chunk.len() as u64;
+            out.write_all(&chunk)?;
+            out.flush()?;
+        } else if !pid.map(pid_alive).unwrap_or(false) {
+            return Ok(());
+        } else {
+            thread::sleep(Duration::from_millis(250));
+        }
+    }
+}
+
+pub fn log_tail(log: &Path, lines: usize) -> String {
+    text_tail(&fs::read(log).unwrap_or_default(), lines)
+}
+
+pub fn text_tail(buf: &[u8], lines: usize) -> String {
+    String::from_utf8_lossy(&buf[tail_offset(buf, lines)..]).to_string()
+}
+
+fn tail_offset(buf: &[u8], lines: usize) -> usize {
+    let body = buf.strip_suffix(b"\n").unwrap_or(buf);
+    let mut seen = 0;
+    for (i, b) in body.iter().enumerate().rev() {
+        if *b == b'\n' {
+            seen += 1;
+            if seen == lines {
+                return i + 1;
+            }
+        }
+    }
+    if lines == 0 {
+        buf.len()
+    } else {
+        0
+    }
+}
+
+pub fn format_uptime(d: Duration) -> String {
+    let s = d.as_secs();
+    if s < 60 {
+        format!("{}s", s)
+    } else if s < 3600 {
+        format!("{}m{:02}s", s / 60, s % 60)
+    } else if s < 86400 {
+        format!("{}h{:02}m", s / 3600, (s / 60) % 60)
+    } else {
+        format!("{}d{:02}h", s / 86400, (s / 3600) % 24)
+    }
+}
+
+fn new_id(dir: &Path) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut n = (nanos as u64) ^ ((std::process::id() as u64) << 32);
+    loop {
+        let id = format!("{:08x}", n as u32);
+        if !dir.join(format!("{}.json", id)).exists() && !dir.join(format!("{}.log", id)).exists() {
+            return id;
+        }
+        n = n.wrapping_mul(6364136223846793005).wrapping_add(1);
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+pub fn pid_alive(pid: u32) -> bool {
+    // Signal 0 only checks existence/permission. EPERM still means alive.
+    let rc = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    let exists = rc == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+    exists && !is_zombie(pid)
+}
+
+// Orphaned servers are reaped by init, which may take a moment; a zombie has
+// already exited as far as we are concerned.
+#[cfg(target_os = "linux")]
+fn is_zombie(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|s| {
+            s.rsplit_once(')')
+                .map(|(_, rest)| rest.trim_start().starts_with('Z'))
+        })
+        .unwrap_or(false)
+}
+
+// Field 22 of /proc/<pid>/stat. The command name (field 2) may hold spaces
+// and parentheses, so fields are counted from its closing parenthesis.
+#[cfg(target_os = "linux")]
+pub fn process_start(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(22 - 3)?.parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_start(_pid: u32) -> Option<u64> {
+    None
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_zombie(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(windows)]
+pub fn pid_alive(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32, kill: bool) -> Result<()> {
+    let sig = if kill { libc::SIGKILL } else { libc::SIGTERM };
+    if unsafe { libc::kill(pid as libc::pid_t, sig) } != 0 {
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ESRCH) {
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn terminate(pid: u32, kill: bool) -> Result<()> {
+    let mut cmd = Command::new("taskkill");
+    cmd.args(["/PID", &pid.to_string()]);
+    if kill {
+        cmd.arg("/F");
+    }
+    cmd.stdout(Stdio::null()).stderr(Stdio::null());
+    cmd.status()?;
+    Ok(())
+}
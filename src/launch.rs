@@ -0,0 +1,1211 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Starting llama.cpp and Ollama processes.
+
+use crate::config::{expand_home, Config, Preset};
+use crate::discovery::{find_mmproj, find_vocoder, ollama_models_roots_with};
+use crate::error::{GgufyError, Result};
+use crate::fit::{self, Estimate, Settings, Verdict};
+use crate::generation::{self, Capture, Generation, END_OF_TEXT};
+use crate::gguf::{self, ModelKind};
+use crate::health::{self, NotReady};
+use crate::instance::{self, Instance};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub fn resolve_bin(name: &str) -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("PATH") {
+        for p in path.split(if cfg!(windows) { ';' } else { ':' }) {
+            let mut candidate = PathBuf::from(p);
+            candidate.push(name);
+            if cfg!(windows) {
+                if candidate.exists() {
+                    return Some(candidate);
+                }
+                let mut exe = candidate.clone();
+                exe.set_extension("exe");
+                if exe.exists() {
+                    return Some(exe);
+                }
+            } else if candidate.exists() {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+pub fn require_bin(name: &str) -> Result<PathBuf> {
+    resolve_bin(name).ok_or_else(|| GgufyError::MissingBinary(name.to_string()))
+}
+
+pub fn port_or_default(is_ollama: bool, requested: Option<u16>) -> u16 {
+    if let Some(p) = requested {
+        return p;
+    }
+    if is_ollama {
+        11434
+    } else {
+        12434
+    }
+}
+
+// `--port 8080` or `--port auto`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortSpec {
+    Auto,
+    Fixed(u16),
+}
+
+impl FromStr for PortSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(PortSpec::Auto);
+        }
+        s.parse::<u16>()
+            .map(PortSpec::Fixed)
+            .map_err(|_| format!("expected a port number or `auto`, got {:?}", s))
+    }
+}
+
+// Ports `--port auto` may hand out, `START-END` inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Default for PortRange {
+    fn default() -> Self {
+        PortRange {
+            start: 12434,
+            end: 12533,
+        }
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let err = || format!("expected START-END, got {:?}", s);
+        let (a, b) = s.split_once('-').ok_or_else(err)?;
+        let start = a.trim().parse::<u16>().map_err(|_| err())?;
+        let end = b.trim().parse::<u16>().map_err(|_| err())?;
+        if start == 0 || start > end {
+            return Err(err());
+        }
+        Ok(PortRange { start, end })
+    }
+}
+
+// Resolves the port for a new llama-server. A fixed (or default) port must
+// be free; `auto` takes the first port in `range` that is neither bound nor
+// recorded by a managed instance.
+pub fn choose_port(is_ollama: bool, spec: Option<PortSpec>, range: PortRange) -> Result<u16> {
+    let running = instance::list_instances();
+    let fixed = match spec {
+        Some(PortSpec::Auto) => {
+            return (range.start..=range.end)
+                .find(|p| !running.iter().any(|i| i.port == Some(*p)) && port_is_free(*p))
+                .ok_or(GgufyError::NoFreePort {
+                    start: range.start,
+                    end: range.end,
+                });
+        }
+        Some(PortSpec::Fixed(p)) => p,
+        None => port_or_default(is_ollama, None),
+    };
+    if let Some(owner) = running.iter().find(|i| i.port == Some(fixed)) {
+        return Err(GgufyError::PortInUse {
+            port: fixed,
+            owner: Some(format!("ggufy instance {} ({})", owner.id, owner.model)),
+        });
+    }
+    if !port_is_free(fixed) {
+        return Err(GgufyError::PortInUse {
+            port: fixed,
+            owner: None,
+        });
+    }
+    Ok(fixed)
+}
+
+pub fn port_is_free(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+#[derive(Clone, Debug)]
+pub struct LaunchOptions {
+    pub dry_run: bool,
+    pub verbose: bool,
+    // Servers only: start in the background as a managed instance instead of
+    // waiting on it.
+    pub detach: bool,
+    // Servers only: poll /health after starting. Detached launches return
+    // once the server is ready and fail if it does not get there in time.
+    pub wait_ready: bool,
+    pub ready_timeout: Duration,
+    // Per-model presets merged in front of the explicit extra args.
+    pub config: Config,
+    // Defaults shipped with the model itself (its Ollama manifest), beneath
+    // the config presets.
+    pub model_preset: Preset,
+    // Say on stderr why a route and tool were chosen (`--explain`).
+    pub explain: bool,
+    // One-shot prompts: collect the output and print it as JSON.
+    pub capture: Option<Capture>,
+    // Servers only: start even when the memory estimate says the model will
+    // not fit (`--ignore-fit`).
+    pub ignore_fit: bool,
+    // Ollama stores from --ollama-root and the config, searched after
+    // $OLLAMA_MODELS and before the default ones.
+    pub ollama_roots: Vec<PathBuf>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        LaunchOptions {
+            dry_run: false,
+            verbose: false,
+            detach: false,
+            wait_ready: true,
+            ready_timeout: Duration::from_secs(300),
+            config: Config::default(),
+            model_preset: Preset::default(),
+            explain: false,
+            capture: None,
+            ignore_fit: false,
+            ollama_roots: Vec::new(),
+        }
+    }
+}
+
+impl LaunchOptions {
+    pub fn with_model_preset(&self, preset: Preset) -> LaunchOptions {
+        LaunchOptions {
+            model_preset: preset,
+            ..self.clone()
+        }
+    }
+
+    // Every Ollama store to search, in lookup order.
+    pub fn ollama_stores(&self) -> Vec<PathBuf> {
+        ollama_models_roots_with(&self.ollama_roots)
+    }
+}
+
+fn print_command(cmd: &Command, stdin: Option<&Path>) {
+    let mut words: Vec<String> = cmd
+        .get_envs()
+        .filter_map(|(k, v)| Some(format!("{}={}", k.to_string_lossy(), v?.to_string_lossy())))
+        .collect();
+    words.push(cmd.get_program().to_string_lossy().to_string());
+    words.extend(cmd.get_args().map(|a| a.to_string_lossy().to_string()));
+    if let Some(p) = stdin {
+        words.push(format!("< {}", p.display()));
+    }
+    println!("{}", words.join(" "));
+}
+
+// Runs `cmd` attached: waits on it, relays SIGINT/SIGTERM, and turns a
+// failing exit status into GgufyError::Exited.
+pub fn run_or_print(cmd: Command, opts: &LaunchOptions) -> Result<()> {
+    run_or_print_from(cmd, None, opts)
+}
+
+// `run_or_print` for a command whose stdin was set to `stdin`.
+fn run_or_print_from(mut cmd: Command, stdin: Option<&Path>, opts: &LaunchOptions) -> Result<()> {
+    cmd.envs(opts.config.env());
+    if opts.dry_run {
+        print_command(&cmd, stdin);
+        return Ok(());
+    }
+    run_attached(cmd, None)
+}
+
+// One-shot prompt commands run attached, or captured under --json/--stream.
+fn run_prompt(cmd: Command, stdin: Option<&Path>, model: &str, opts: &LaunchOptions) -> Result<()> {
+    match opts.capture {
+        Some(capture) if !opts.dry_run => run_captured(cmd, model, capture, opts),
+        _ => run_or_print_from(cmd, stdin, opts),
+    }
+}
+
+// Chunks are held back by this much so a trailing end-of-text marker (and
+// its newline) never reaches the stream.
+const STREAM_HOLDBACK: usize = END_OF_TEXT.len() + 2;
+
+// A captured child abandoned on an error path is killed and waited, so it
+// neither keeps running nor lingers as a zombie.
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        if matches!(self.0.try_wait(), Ok(None)) {
+            let _ = self.0.kill();
+        }
+        let _ = self.0.wait();
+    }
+}
+
+fn run_captured(
+    mut cmd: Command,
+    model: &str,
+    capture: Capture,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    cmd.envs(opts.config.env());
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let tool = Path::new(&program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| program.clone());
+    let started = Instant::now();
+    let mut child = KillOnDrop(cmd.spawn().map_err(|source| GgufyError::Spawn {
+        program: program.clone(),
+        source,
+    })?);
+    let child = &mut child.0;
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        String::from_utf8_lossy(&buf).to_string()
+    });
+    let relay = signals::relay_to(child.id());
+    let mut stdout = child.stdout.take().unwrap();
+    let mut out = Vec::new();
+    let mut sent = 0;
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match stdout.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        out.extend_from_slice(&buf[..n]);
+        if capture == Capture::Ndjson {
+            let limit = out.len().saturating_sub(STREAM_HOLDBACK).max(sent);
+            let upto = match std::str::from_utf8(&out[sent..limit]) {
+                Ok(_) => limit,
+                Err(e) => sent + e.valid_up_to(),
+            };
+            if upto > sent {
+                generation::emit_chunk(std::str::from_utf8(&out[sent..upto]).unwrap());
+                sent = upto;
+            }
+        }
+    }
+    let status = child.wait();
+    drop(relay);
+    let status = status?;
+    let err = stderr_reader.join().unwrap_or_default();
+    if !status.success() {
+        eprint!("{}", err);
+        return Err(GgufyError::Exited {
+            program: tool,
+            code: exit_code_of(status),
+        });
+    }
+    let raw = String::from_utf8_lossy(&out).to_string();
+    let trimmed = raw.trim_end();
+    let text = trimmed
+        .strip_suffix(END_OF_TEXT.trim_start())
+        .unwrap_or(trimmed)
+        .trim_end()
+        .to_string();
+    if capture == Capture::Ndjson {
+        if let Some(rest) = text.get(sent..).filter(|r| !r.is_empty()) {
+            generation::emit_chunk(rest);
+        }
+    }
+    let (backend, stats) = if tool == "ollama" {
+        ("ollama", generation::parse_ollama_verbose(&err))
+    } else {
+        ("llama.cpp", generation::parse_llama_perf(&err))
+    };
+    let g = Generation {
+        model: model.to_string(),
+        backend: backend.to_string(),
+        tool,
+        text,
+        stats,
+        wall_ms: started.elapsed().as_secs_f64() * 1000.0,
+    };
+    generation::emit(&g, capture);
+    Ok(())
+}
+
+// Lines of server output quoted when it does not become ready, and how much
+// attached stderr is kept around to find them.
+const NOT_READY_LINES: usize = 20;
+const TEE_KEEP: usize = 64 * 1024;
+
+struct Tracked<'a> {
+    model: &'a str,
+    model_path: Option<&'a Path>,
+    port: u16,
+    ready_timeout: Option<Duration>,
+}
+
+fn run_attached(mut cmd: Command, track: Option<Tracked>) -> Result<()> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let ready_timeout = track.as_ref().and_then(|t| t.ready_timeout);
+    // Output still goes to the terminal; stderr is only teed so that a server
+    // that never becomes ready can be reported with it.
+    if ready_timeout.is_some() {
+        cmd.stderr(Stdio::piped());
+    }
+    let mut child = cmd.spawn().map_err(|source| GgufyError::Spawn {
+        program: program.clone(),
+        source,
+    })?;
+    let pid = child.id();
+    // Not being able to write the record only hides the server from `ps`.
+    let inst = track.as_ref().and_then(|t| {
+        instance::track_attached(&cmd, pid, t.model, t.model_path, Some(t.port)).ok()
+    });
+    let tee = child.stderr.take().map(tee_stderr);
+    let relay = signals::relay_to(pid);
+    if let (Some(t), Some(timeout)) = (&track, ready_timeout) {
+        let host = server_host(&cmd);
+        let url = health::base_url(&host, t.port);
+        let alive = || matches!(child.try_wait(), Ok(None));
+        if let Err(e) = health::wait_ready(&host, t.port, timeout, alive) {
+            if let Some(inst) = &inst {
+                let _ = instance::stop_instance(inst);
+            }
+            let _ = child.kill();
+            let status = child.wait();
+            drop(relay);
+            if let Some(inst) = inst {
+                inst.remove();
+            }
+            let mut reason = e.to_string();
+            match status {
+                Ok(s) if e == NotReady::Exited => {
+                    reason.push_str(&format!(" (exit status {})", exit_code_of(s)))
+                }
+                _ => reason.push_str("; stopped it"),
+            }
+            let tail = tee.and_then(|t| t.join().ok()).unwrap_or_default();
+            return Err(not_ready(url, reason, &tail));
+        }
+        eprintln!("ready on {}", url);
+    }
+    let status = child.wait();
+    drop(relay);
+    if let Some(inst) = inst {
+        inst.remove();
+    }
+    if let Some(tee) = tee {
+        let _ = tee.join();
+    }
+    let status = status?;
+    if status.success() {
+        return Ok(());
+    }
+    let name = Path::new(&program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(program);
+    Err(GgufyError::Exited {
+        program: name,
+        code: exit_code_of(status),
+    })
+}
+
+// Copies a child's stderr to ours and returns the last lines of it once the
+// pipe closes. Only the most recent TEE_KEEP bytes are held on to.
+fn tee_stderr(mut from: ChildStderr) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = match from.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            let _ = io::stderr().write_all(&buf[..n]);
+            kept.extend_from_slice(&buf[..n]);
+            if kept.len() > TEE_KEEP {
+                kept.drain(..kept.len() - TEE_KEEP);
+            }
+        }
+        instance::text_tail(&kept, NOT_READY_LINES)
+    })
+}
+
+// The error for a server that did not become ready, quoting the end of its
+// output.
+fn not_ready(url: String, reason: String, tail: &str) -> GgufyError {
+    let log = Some(tail.trim_end())
+        .filter(|t| !t.trim().is_empty())
+        .map(|t| format!("\n{}", t))
+        .unwrap_or_default();
+    GgufyError::NotReady { url, reason, log }
+}
+
+#[cfg(unix)]
+fn exit_code_of(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status
+        .code()
+        .or_else(|| status.signal().map(|s| 128 + s))
+        .unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn exit_code_of(status: std::process::ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+// llama-server stays attached by default; with `detach` it runs in the
+// background as a managed instance (see `ps`, `stop` and `logs`).
+fn spawn_server(
+    mut cmd: Command,
+    model: &str,
+    model_path: Option<&Path>,
+    port: u16,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    cmd.envs(opts.config.env());
+    if opts.dry_run {
+        print_command(&cmd, None);
+        return Ok(());
+    }
+    if !opts.detach {
+        cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+        let track = Tracked {
+            model,
+            model_path,
+            port,
+            ready_timeout: opts.wait_ready.then_some(opts.ready_timeout),
+        };
+        return run_attached(cmd, Some(track));
+    }
+    let host = server_host(&cmd);
+    let (inst, mut child) = instance::spawn_managed(cmd, model, model_path, Some(port))?;
+    println!(
+        "started {} on port {} (id {}, pid {})",
+        inst.model, port, inst.id, inst.pid
+    );
+    if let Some(log) = &inst.log {
+        println!("logs: {}", log.display());
+    }
+    if !opts.wait_ready {
+        return Ok(());
+    }
+    wait_managed_ready(&inst, &mut child, &host, port, opts.ready_timeout)?;
+    println!("ready on {}", health::base_url(&host, port));
+    Ok(())
+}
+
+// Waits for a server started with `instance::spawn_managed`. One that exits
+// first loses its record; one that is merely slow is left running.
+pub fn wait_managed_ready(
+    inst: &Instance,
+    child: &mut Child,
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<()> {
+    let url = health::base_url(host, port);
+    let alive = || matches!(child.try_wait(), Ok(None));
+    match health::wait_ready(host, port, timeout, alive) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let mut reason = e.to_string();
+            if e == NotReady::Exited {
+                inst.remove();
+            } else {
+                reason.push_str(&format!(
+                    "; still running as {} (`ggufy stop {}`)",
+                    inst.id, inst.id
+                ));
+            }
+            let tail = inst
+                .log
+                .as_deref()
+                .map(|l| instance::log_tail(l, NOT_READY_LINES))
+                .unwrap_or_default();
+            Err(not_ready(url, reason, &tail))
+        }
+    }
+}
+
+// A managed llama-server on loopback for the gateway. The caller waits for it
+// with `wait_managed_ready`, so choosing ports can stay serialised without
+// also serialising model loads.
+pub fn spawn_local_server(
+    model_path: &Path,
+    name: &str,
+    port: u16,
+    opts: &LaunchOptions,
+) -> Result<(Instance, Child)> {
+    let bin = require_bin("llama-server")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("-m").arg(model_path);
+    cmd.arg("--host").arg("127.0.0.1");
+    cmd.arg("--port").arg(port.to_string());
+    push_args(&mut cmd, name, Some(model_path), None, opts);
+    cmd.envs(opts.config.env());
+    if opts.verbose {
+        eprintln!(
+            "starting: llama-server -m {} on port {}",
+            model_path.display(),
+            port
+        );
+    }
+    instance::spawn_managed(cmd, name, Some(model_path), Some(port))
+}
+
+// Where to probe: llama-server's own --host, with wildcard binds reached
+// through loopback.
+fn server_host(cmd: &Command) -> String {
+    let args: Vec<String> = cmd
+        .get_args()
+        .map(|a| a.to_string_lossy().to_string())
+        .collect();
+    let host = args
+        .iter()
+        .position(|a| a == "--host")
+        .and_then(|i| args.get(i + 1))
+        .map(|h| h.as_str());
+    match host {
+        None | Some("0.0.0.0") | Some("::") | Some("[::]") => "127.0.0.1".to_string(),
+        Some(h) => h.to_string(),
+    }
+}
+
+// The model's own defaults, then config presets, then `extra`, so anything
+// given on the command line wins (llama.cpp keeps the last value of a
+// repeated flag).
+fn push_args(
+    cmd: &mut Command,
+    name: &str,
+    model_path: Option<&Path>,
+    extra: Option<&[String]>,
+    opts: &LaunchOptions,
+) -> Preset {
+    let mut preset = opts.model_preset.clone();
+    if preset.mmproj.is_none() && !mentions_mmproj(extra) {
+        preset.mmproj = model_path.and_then(find_mmproj);
+        if let (Some(p), true) = (&preset.mmproj, opts.verbose) {
+            eprintln!("using projector {}", p.display());
+        }
+    }
+    preset.merge(&opts.config.resolve(name, model_path).preset);
+    cmd.args(preset.to_args());
+    if let Some(args) = extra {
+        cmd.args(args);
+    }
+    preset
+}
+
+// An explicit `--mmproj`/`--no-mmproj` turns off the sibling lookup.
+fn mentions_mmproj(extra: Option<&[String]>) -> bool {
+    extra.unwrap_or_default().iter().any(|a| {
+        matches!(a.as_str(), "-mm" | "--mmproj" | "--no-mmproj") || a.starts_with("--mmproj=")
+    })
+}
+
+// `ps` shows what the user typed, except that paths are shortened to the
+// file stem.
+fn display_name(name: &str, model_path: &Path) -> String {
+    if name.contains(std::path::MAIN_SEPARATOR) || name.ends_with(".gguf") {
+        model_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.to_string())
+    } else {
+        name.to_string()
+    }
+}
+
+pub fn run_llama_server_hf(
+    repo: &str,
+    port: Option<u16>,
+    extra: Option<&[String]>,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("llama-server")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("-hf").arg(repo);
+    let p = port.unwrap_or(12434);
+    cmd.arg("--port").arg(p.to_string());
+    push_args(&mut cmd, repo, None, extra, opts);
+    if opts.verbose {
+        eprintln!("starting: llama-server -hf {}", repo);
+    }
+    spawn_server(cmd, repo, None, p, opts)
+}
+
+pub fn run_llama_server_model(
+    model_path: &Path,
+    name: &str,
+    port: Option<u16>,
+    extra: Option<&[String]>,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("llama-server")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("-m").arg(model_path);
+    let p = port.unwrap_or(12434);
+    cmd.arg("--port").arg(p.to_string());
+    push_args(&mut cmd, name, Some(model_path), extra, opts);
+    let label = display_name(name, model_path);
+    check_fit(&cmd, &label, model_path, opts)?;
+    if opts.verbose {
+        eprintln!("starting: llama-server -m {}", model_path.display());
+    }
+    spawn_server(cmd, &label, Some(model_path), p, opts)
+}
+
+// The arguments `run_llama_server_model` would give llama-server for a model,
+// presets included; `ggufy fit` estimates from the same flags.
+pub fn llama_server_args(
+    model_path: &Path,
+    name: &str,
+    extra: &[String],
+    opts: &LaunchOptions,
+) -> Vec<String> {
+    let mut cmd = Command::new("llama-server");
+    push_args(&mut cmd, name, Some(model_path), Some(extra), opts);
+    arg_strings(&cmd)
+}
+
+fn arg_strings(cmd: &Command) -> Vec<String> {
+    cmd.get_args()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+// Refuses to start a server the memory estimate says will not fit, and warns
+// when it barely does. Dry runs only warn. Without a readable GGUF or
+// `/proc/meminfo` there is nothing to go on, and the launch goes ahead.
+fn check_fit(cmd: &Command, label: &str, model_path: &Path, opts: &LaunchOptions) -> Result<()> {
+    if opts.ignore_fit {
+        return Ok(());
+    }
+    let (Ok(g), Some(mem)) = (gguf::read_gguf(model_path), fit::memory()) else {
+        if opts.verbose {
+            eprintln!("fit: no estimate for {}", label);
+        }
+        return Ok(());
+    };
+    let est = Estimate::new(&g, &Settings::from_args(&arg_strings(cmd)));
+    match fit::verdict(&est, &mem) {
+        Verdict::TooBig if !opts.dry_run => Err(fit::wont_fit(label, &est, &mem)),
+        Verdict::TooBig => {
+            eprintln!("warning: {}", fit::wont_fit(label, &est, &mem));
+            Ok(())
+        }
+        Verdict::Tight => {
+            eprintln!(
+                "warning: {} needs about {} at --ctx-size {}, close to the {} free",
+                label,
+                crate::inspect::human_bytes(est.total()),
+                est.ctx,
+                crate::inspect::human_bytes(mem.budget())
+            );
+            Ok(())
+        }
+        Verdict::Fits => Ok(()),
+    }
+}
+
+pub fn run_llama_cli_model(
+    model_path: &Path,
+    name: &str,
+    extra: Option<&[String]>,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("llama-cli")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("-m").arg(model_path);
+    let preset = push_args(&mut cmd, name, Some(model_path), extra, opts);
+    for stop in &preset.stop {
+        cmd.arg("--reverse-prompt").arg(stop);
+    }
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    if opts.verbose {
+        eprintln!("starting: llama-cli -m {}", model_path.display());
+    }
+    run_or_print(cmd, opts)
+}
+
+// A one-shot prompt and its optional system message.
+#[derive(Clone, Debug, Default)]
+pub struct Prompt {
+    pub text: String,
+    pub system: Option<String>,
+}
+
+impl Prompt {
+    pub fn new(text: impl Into<String>) -> Prompt {
+        Prompt {
+            text: text.into(),
+            system: None,
+        }
+    }
+
+    // For tools without a system prompt option: the system message leads.
+    fn inline(&self) -> String {
+        match &self.system {
+            Some(system) => format!("{}\n\n{}", system, self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+// Text longer than this, or spanning lines, is handed over in a file rather
+// than as one argv entry.
+const ARGV_TEXT_MAX: usize = 1024;
+
+// A prompt file under the state dir, removed once the child is done. Under
+// --dry-run it is kept so the printed command still works.
+struct TextFile(PathBuf);
+
+impl Drop for TextFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// `<pid>-<n>.txt`, numbered within the process, so a prompt and its system
+// prompt written back to back never share a name; `create_new` also skips
+// files left behind by an earlier process with the same pid.
+fn write_text_file(text: &str) -> Result<PathBuf> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = instance::state_dir().join("prompts");
+    fs::create_dir_all(&dir)?;
+    loop {
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("{}-{}.txt", std::process::id(), n));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut f) => {
+                f.write_all(text.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn fits_argv(text: &str) -> bool {
+    text.len() <= ARGV_TEXT_MAX && !text.contains('\n')
+}
+
+// `flag text`, or `file_flag <prompt file>` for long text.
+fn text_arg(
+    cmd: &mut Command,
+    flag: &str,
+    file_flag: &str,
+    text: &str,
+    opts: &LaunchOptions,
+) -> Result<Option<TextFile>> {
+    if fits_argv(text) {
+        cmd.arg(flag).arg(text);
+        return Ok(None);
+    }
+    let path = write_text_file(text)?;
+    cmd.arg(file_flag).arg(&path);
+    Ok((!opts.dry_run).then(|| TextFile(path)))
+}
+
+// Without a system message the prompt is a raw completion (`-no-cnv`); with
+// one, a single chat turn so the model's template places it.
+pub fn run_llama_cli_prompt(
+    model_path: &Path,
+    name: &str,
+    prompt: &Prompt,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("llama-cli")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("-m").arg(model_path);
+    push_args(&mut cmd, name, Some(model_path), None, opts);
+    let _text = text_arg(&mut cmd, "-p", "--file", &prompt.text, opts)?;
+    let _system = match &prompt.system {
+        Some(system) => {
+            let f = text_arg(
+                &mut cmd,
+                "--system-prompt",
+                "--system-prompt-file",
+                system,
+                opts,
+            )?;
+            cmd.arg("--single-turn");
+            f
+        }
+        None => {
+            cmd.arg("-no-cnv");
+            None
+        }
+    };
+    if opts.capture.is_some() {
+        cmd.arg("--no-display-prompt");
+    }
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::null());
+    if opts.verbose {
+        eprintln!(
+            "starting: llama-cli -m {} ({} prompt bytes)",
+            model_path.display(),
+            prompt.text.len()
+        );
+    }
+    run_prompt(cmd, None, name, opts)
+}
+
+const AUDIO_EXTS: &[&str] = &["wav", "mp3", "flac", "ogg", "m4a"];
+const IMAGE_EXTS: &[&str] = &["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+
+// One-shot prompt against a local GGUF, sent to the llama.cpp tool its
+// metadata calls for: `llama-tts` for text-to-speech models, `llama-mtmd-cli`
+// when a projector can take audio or the prompt names image/audio files, and
+// `llama-cli` otherwise.
+pub fn run_local_prompt(
+    model_path: &Path,
+    name: &str,
+    prompt: &Prompt,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let kind = gguf::read_gguf(model_path)
+        .map(|g| g.kind())
+        .unwrap_or(ModelKind::Text);
+    match kind {
+        ModelKind::Tts => {
+            let vocoder = find_vocoder(model_path).ok_or_else(|| {
+                GgufyError::Usage(format!(
+                    "{} is a text-to-speech model but no vocoder GGUF was found next to it or in the llama.cpp cache",
+                    name
+                ))
+            })?;
+            if prompt.system.is_some() {
+                return Err(GgufyError::Usage(format!(
+                    "{} is a text-to-speech model and takes no system prompt",
+                    name
+                )));
+            }
+            explain(opts, "llama-tts", "vocabulary has text-to-speech markers");
+            return run_llama_tts(model_path, &vocoder, name, &prompt.text, opts);
+        }
+        ModelKind::Projector | ModelKind::Vocoder => {
+            return Err(GgufyError::Usage(format!(
+                "{} is a {} and cannot be run on its own",
+                name,
+                if kind == ModelKind::Projector {
+                    "multimodal projector"
+                } else {
+                    "vocoder"
+                }
+            )));
+        }
+        ModelKind::Text => {}
+    }
+    let mmproj = opts
+        .config
+        .resolve(name, Some(model_path))
+        .preset
+        .mmproj
+        .or_else(|| opts.model_preset.mmproj.clone())
+        .or_else(|| find_mmproj(model_path));
+    let hears = mmproj
+        .as_deref()
+        .and_then(|p| gguf::read_gguf(Path::new(&expand_home(p))).ok())
+        .is_some_and(|g| g.projector_modalities().iter().any(|m| m == "audio"));
+    let (text, media) = split_media(&prompt.text);
+    if mmproj.is_some() && (hears || !media.is_empty()) {
+        let why = if hears {
+            "projector has an audio encoder"
+        } else {
+            "prompt names image or audio files and a projector is paired"
+        };
+        explain(opts, "llama-mtmd-cli", why);
+        let mut preset = opts.model_preset.clone();
+        preset.mmproj = mmproj;
+        let opts = opts.with_model_preset(preset);
+        let prompt = Prompt {
+            text,
+            system: prompt.system.clone(),
+        };
+        return run_llama_mtmd_prompt(model_path, name, &prompt.inline(), &media, &opts);
+    }
+    explain(opts, "llama-cli", "text model");
+    run_llama_cli_prompt(model_path, name, prompt, opts)
+}
+
+fn explain(opts: &LaunchOptions, tool: &str, why: &str) {
+    if opts.explain {
+        eprintln!("tool: {} ({})", tool, why);
+    }
+}
+
+// Prompt words that are existing audio or image files, as (flag, path), and
+// the prompt without them.
+fn split_media(prompt: &str) -> (String, Vec<(&'static str, PathBuf)>) {
+    let mut text = String::new();
+    let mut media = Vec::new();
+    let mut rest = prompt;
+    // Only whole words are dropped (with the whitespace before them); every
+    // other separator is kept as typed.
+    while !rest.is_empty() {
+        let word_at = rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        let (sep, tail) = rest.split_at(word_at);
+        let word_len = tail.find(char::is_whitespace).unwrap_or(tail.len());
+        let (word, tail) = tail.split_at(word_len);
+        rest = tail;
+        if let Some(flag) = media_flag(word) {
+            media.push((flag, PathBuf::from(word)));
+            continue;
+        }
+        if !text.is_empty() || (!word.is_empty() && media.is_empty()) {
+            text.push_str(sep);
+        }
+        text.push_str(word);
+    }
+    (text, media)
+}
+
+fn media_flag(word: &str) -> Option<&'static str> {
+    let p = Path::new(word);
+    let ext = p
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let flag = if AUDIO_EXTS.contains(&ext.as_str()) {
+        "--audio"
+    } else if IMAGE_EXTS.contains(&ext.as_str()) {
+        "--image"
+    } else {
+        return None;
+    };
+    p.is_file().then_some(flag)
+}
+
+pub fn run_llama_mtmd_prompt(
+    model_path: &Path,
+    name: &str,
+    prompt: &str,
+    media: &[(&str, PathBuf)],
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("llama-mtmd-cli")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("-m").arg(model_path);
+    push_args(&mut cmd, name, Some(model_path), None, opts);
+    for (flag, path) in media {
+        cmd.arg(flag).arg(path);
+    }
+    let _text = text_arg(&mut cmd, "-p", "--file", prompt, opts)?;
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::null());
+    if opts.verbose {
+        eprintln!("starting: llama-mtmd-cli -m {}", model_path.display());
+    }
+    run_prompt(cmd, None, name, opts)
+}
+
+// Writes the speech to llama-tts's default `output.wav`.
+pub fn run_llama_tts(
+    model_path: &Path,
+    vocoder: &Path,
+    name: &str,
+    prompt: &str,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("llama-tts")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("-m").arg(model_path).arg("-mv").arg(vocoder);
+    push_args(&mut cmd, name, Some(model_path), None, opts);
+    let _text = text_arg(&mut cmd, "-p", "--file", prompt, opts)?;
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    if opts.verbose {
+        eprintln!(
+            "starting: llama-tts -m {} -mv {}",
+            model_path.display(),
+            vocoder.display()
+        );
+    }
+    run_prompt(cmd, None, name, opts)
+}
+
+pub fn run_ollama_run(
+    model: &str,
+    tag: &str,
+    extra: Option<&[String]>,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("ollama")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("run").arg(format!("{}:{}", model, tag));
+    if let Some(args) = extra {
+        cmd.args(args);
+    }
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    if opts.verbose {
+        eprintln!("starting: ollama run {}:{}", model, tag);
+    }
+    run_or_print(cmd, opts)
+}
+
+// `ollama run` has no system prompt option, so the system message leads the
+// prompt. Long prompts are piped in on stdin.
+pub fn run_ollama_simple(
+    model: &str,
+    tag: &str,
+    prompt: &Prompt,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let bin = require_bin("ollama")?;
+    let mut cmd = Command::new(bin);
+    cmd.arg("run").arg(format!("{}:{}", model, tag));
+    let text = prompt.inline();
+    let mut stdin = None;
+    let mut _file = None;
+    if fits_argv(&text) {
+        cmd.arg(&text);
+    } else {
+        let path = write_text_file(&text)?;
+        cmd.stdin(Stdio::from(fs::File::open(&path)?));
+        stdin = Some(path.clone());
+        _file = (!opts.dry_run).then(|| TextFile(path));
+    }
+    if opts.capture.is_some() {
+        cmd.arg("--verbose");
+    }
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    if opts.verbose {
+        eprintln!(
+            "starting: ollama run {}:{} ({} prompt bytes)",
+            model,
+            tag,
+            text.len()
+        );
+    }
+    run_prompt(cmd, stdin.as_deref(), model, opts)
+}
+
+pub fn run_ollama_list(opts: &LaunchOptions) -> Result<()> {
+    let bin = require_bin("ollama")?;
+    if opts.dry_run {
+        println!("{} list", bin.display());
+        return Ok(());
+    }
+    let out = Command::new(&bin)
+        .arg("list")
+        .envs(opts.config.env())
+        .output()
+        .map_err(|source| GgufyError::Spawn {
+            program: bin.display().to_string(),
+            source,
+        })?;
+    if opts.verbose {
+        eprintln!("ollama list completed");
+    }
+    let mut buf = out.stdout;
+    if !buf.ends_with(b"\n") {
+        buf.push(b'\n');
+    }
+    print!("{}", String::from_utf8_lossy(&buf));
+    Ok(())
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static CHILD: AtomicI32 = AtomicI32::new(0);
+    const RELAYED: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+    // Restores the previous handlers when dropped.
+    pub struct Relay {
+        old: Vec<(libc::c_int, libc::sigaction)>,
+    }
+
+    extern "C" fn handler(sig: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+        let pid = CHILD.load(Ordering::SeqCst);
+        if pid <= 0 {
+            return;
+        }
+        // Ctrl-C at the terminal already reaches the child through the
+        // foreground process group; only relay signals sent to us directly.
+        if sig == libc::SIGINT && from_terminal(info) {
+            return;
+        }
+        unsafe {
+            libc::kill(pid, sig);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn from_terminal(info: *mut libc::siginfo_t) -> bool {
+        // kill(2) and friends use SI_USER (0) or negative codes.
+        !info.is_null() && unsafe { (*info).si_code } > 0
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn from_terminal(_: *mut libc::siginfo_t) -> bool {
+        true
+    }
+
+    pub fn relay_to(pid: u32) -> Relay {
+        CHILD.store(pid as i32, Ordering::SeqCst);
+        let mut old = Vec::new();
+        for sig in RELAYED {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = handler as *const () as usize;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                let mut prev: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(sig, &action, &mut prev) == 0 {
+                    old.push((sig, prev));
+                }
+            }
+        }
+        Relay { old }
+    }
+
+    impl Drop for Relay {
+        fn drop(&mut self) {
+            for (sig, prev) in &self.old {
+                unsafe {
+                    libc::sigaction(*sig, prev, std::ptr::null_mut());
+                }
+            }
+            CHILD.store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+// Console Ctrl-C goes to the whole process group, child included.
+#[cfg(not(unix))]
+mod signals {
+    pub struct Relay;
+
+    pub fn relay_to(_pid: u32) -> Relay {
+        Relay
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+//! Library side of ggufy: GGUF parsing, model discovery across llama.cpp,
+//! Hugging Face and Ollama caches, reference resolution, linking into the
+//! models dir, and launching llama.cpp/Ollama. Both binaries are built on it.
+
+pub mod catalog;
+pub mod config;
+pub mod discovery;
+pub mod error;
+pub mod fit;
+pub mod gateway;
+pub mod generation;
+pub mod gguf;
+pub mod health;
+pub mod inspect;
+pub mod instance;
+pub mod launch;
+pub mod link;
+pub mod manifest;
+pub mod ollama_api;
+pub mod output;
+pub mod resolve;
+pub mod route;
+pub mod template;
+
+pub use error::{GgufyError, Result};
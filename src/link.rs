@@ -0,0 +1,71 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Linking model files into the ggufy models dir.
+
+use crate::discovery::ggufy_models_dir_with;
+use crate::error::{GgufyError, Result};
+use crate::output::{self, LinkStatus};
+use std::fs;
+use std::io::Result as IoResult;
+use std::path::{Path, PathBuf};
+
+pub fn symlink_into_guffy(
+    src: &Path,
+    link_override: Option<&PathBuf>,
+    force: bool,
+    verbose: bool,
+) -> Result<()> {
+    let name = src.file_name().unwrap().to_string_lossy().to_string();
+    symlink_named_into_guffy(src, &name, link_override, force, verbose)
+}
+
+pub fn symlink_named_into_guffy(
+    src: &Path,
+    link_name: &str,
+    link_override: Option<&PathBuf>,
+    force: bool,
+    verbose: bool,
+) -> Result<()> {
+    let mut dst = ggufy_models_dir_with(link_override);
+    dst.push(link_name);
+    if fs::symlink_metadata(&dst).is_ok() {
+        if force {
+            let _ = fs::remove_file(&dst);
+        } else if output::link_status(src, &dst) == LinkStatus::Conflict {
+            return Err(GgufyError::LinkConflict { link: dst });
+        } else {
+            return Ok(());
+        }
+    }
+    match symlink_file(src, &dst) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            if verbose {
+                eprintln!(
+                    "symlink failed: {} -> {} ({}) — falling back to copy",
+                    src.display(),
+                    dst.display(),
+                    e
+                );
+            }
+            fs::copy(src, &dst)
+                .map(|_| ())
+                .map_err(|source| GgufyError::Link {
+                    src: src.to_path_buf(),
+                    dst,
+                    source,
+                })
+        }
+    }
+}
+
+pub fn symlink_file(src: &Path, dst: &Path) -> IoResult<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(src, dst)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_file(src, dst)
+    }
+}
@@ -1,14 +1,34 @@
 // Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
 
-use clap::{ArgAction, Parser, Subcommand};
-use dirs::home_dir;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use ggufy::catalog::Catalog;
+use ggufy::config::{Config, Resolved};
+use ggufy::discovery::{
+    ensure_models_dir, enumerate_ollama_library_models, find_hf_cached_gguf, find_hf_hub_models,
+    find_llama_cache_models, find_ollama_blob_gguf, ggufy_models_dir_with, hf_hub_link_name,
+    hf_hub_records, llama_cache_records, ollama_blob_records, ollama_library_records,
+    ollama_link_name,
+};
+use ggufy::generation::{Capture, StreamFormat};
+use ggufy::gguf::{self, GgufSummary};
+use ggufy::launch::{
+    choose_port, port_or_default, require_bin, resolve_bin, run_llama_cli_model,
+    run_llama_server_hf, run_llama_server_model, run_ollama_list, run_ollama_run, run_or_print,
+    LaunchOptions, PortRange, PortSpec,
+};
+use ggufy::link::{symlink_into_guffy, symlink_named_into_guffy};
+use ggufy::manifest::OllamaModel;
+use ggufy::output::{self, OutputFormat};
+use ggufy::resolve::{
+    normalize_model_tag, resolve_model_path, resolve_model_ref, resolve_ollama_library_gguf,
+    resolve_ollama_library_gguf_with, resolve_ollama_model, split_model_tag,
+};
+use ggufy::route::{self, decide, SystemProbe, Target};
+use ggufy::{fit, gateway, inspect, instance, launch, GgufyError, Result};
 use regex::Regex;
-use serde_json::Value;
-use std::fs;
-use std::io::{Read, Result as IoResult};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use walkdir::WalkDir;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(
@@ -23,14 +43,47 @@ struct Cli {
     llamacpp: bool,
     #[arg(long = "link-dir", env = "GGUFY_MODELS_DIR")]
     link_dir: Option<PathBuf>,
+    // Extra Ollama model stores (dirs holding manifests/ and blobs/).
+    #[arg(long = "ollama-root", global = true, action = ArgAction::Append)]
+    ollama_root: Vec<PathBuf>,
     #[arg(long = "force", action = ArgAction::SetTrue)]
     force: bool,
+    // Start llama-server even when the memory estimate says it will not fit.
+    #[arg(long = "ignore-fit", global = true, default_value_t = false)]
+    ignore_fit: bool,
+    // A port number, or `auto` for the first free one in --port-range.
     #[arg(long = "port")]
-    port: Option<u16>,
+    port: Option<PortSpec>,
+    #[arg(
+        long = "port-range",
+        env = "GGUFY_PORT_RANGE",
+        default_value = "12434-12533"
+    )]
+    port_range: PortRange,
     #[arg(long = "dry-run", default_value_t = false)]
     dry_run: bool,
     #[arg(long = "verbose", default_value_t = false)]
     verbose: bool,
+    // Print why `simple` chose its backend and tool.
+    #[arg(long = "explain", global = true, default_value_t = false)]
+    explain: bool,
+    // Start llama-server in the background instead of waiting on it.
+    #[arg(long = "detach", short = 'd', global = true, default_value_t = false)]
+    detach: bool,
+    // Seconds to wait for llama-server's /health before giving up.
+    #[arg(long = "ready-timeout", global = true, default_value_t = 300)]
+    ready_timeout: u64,
+    #[arg(long = "no-wait", global = true, default_value_t = false)]
+    no_wait: bool,
+    #[arg(long = "rescan", default_value_t = false)]
+    rescan: bool,
+    // Named profile from the config file: flags, routing preference and env.
+    #[arg(long = "profile", global = true, env = "GGUFY_PROFILE")]
+    profile: Option<String>,
+    #[arg(long = "format", value_enum, global = true)]
+    format: Option<OutputFormat>,
+    #[arg(long = "json", global = true, default_value_t = false)]
+    json: bool,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -49,6 +102,14 @@ enum Commands {
         extra: Vec<String>,
     },
     Simple {
+        // Appended to the prompt words; stdin follows when piped (or at `-`).
+        #[arg(long = "prompt-file")]
+        prompt_file: Option<PathBuf>,
+        #[arg(long = "system")]
+        system: Option<String>,
+        // Stream the generation as JSON lines; --json prints one object at the end.
+        #[arg(long = "stream", value_enum)]
+        stream: Option<StreamFormat>,
         model: String,
         #[arg(trailing_var_arg = true)]
         extra: Vec<String>,
@@ -72,16 +133,51 @@ enum Commands {
     Locate {
         pattern: String,
     },
+    Inspect {
+        model: String,
+        #[arg(long = "full", default_value_t = false)]
+        full: bool,
+    },
+    // Estimated memory for a model at a context size, against free RAM/VRAM.
+    Fit {
+        model: String,
+        #[arg(long = "ctx")]
+        ctx: Option<u64>,
+        #[arg(trailing_var_arg = true)]
+        extra: Vec<String>,
+    },
     Link,
+    // Managed llama-server instances started by run/serve/hf.
+    Ps,
+    Stop {
+        // Instance id, port, or model name.
+        selector: Option<String>,
+        #[arg(long = "all", default_value_t = false)]
+        all: bool,
+    },
+    Logs {
+        selector: String,
+        #[arg(short = 'f', long = "follow", default_value_t = false)]
+        follow: bool,
+        #[arg(short = 'n', long = "tail")]
+        tail: Option<usize>,
+    },
     OllamaServe {
         target: String,
         #[arg(trailing_var_arg = true)]
         extra: Vec<String>,
     },
+    // OpenAI- and Ollama-compatible endpoint that starts a llama-server per
+    // requested model (--port, default 12400; backends use --port-range).
+    Gateway(GatewayArgs),
     Ollama {
         #[command(subcommand)]
         sub: OllamaCmd,
     },
+    Config {
+        #[command(subcommand)]
+        sub: ConfigCmd,
+    },
     Llama {
         #[command(subcommand)]
         sub: LlamaCmd,
@@ -90,6 +186,19 @@ enum Commands {
     External(Vec<String>),
 }
 
+#[derive(Args)]
+struct GatewayArgs {
+    #[arg(long = "host", default_value = "127.0.0.1")]
+    host: String,
+    // Loaded models at once; the least recently used one makes way.
+    #[arg(long = "max-models")]
+    max_models: Option<usize>,
+    // Unload a model after this many idle minutes (per-model
+    // `idle_minutes` and `pin` in the config take precedence).
+    #[arg(long = "idle-minutes")]
+    idle_minutes: Option<f64>,
+}
+
 #[derive(Subcommand)]
 enum OllamaCmd {
     Serve {
@@ -103,6 +212,15 @@ enum OllamaCmd {
         extra: Vec<String>,
     },
     LinkAll,
+    // The gateway on Ollama's port (11434), for clients that only speak
+    // the Ollama API.
+    Api(GatewayArgs),
+}
+
+#[derive(Subcommand)]
+enum ConfigCmd {
+    // Effective merged preset for a model (or just the defaults).
+    Show { model: Option<String> },
 }
 
 #[derive(Subcommand)]
@@ -126,9 +244,41 @@ enum LlamaCmd {
 
 fn main() {
     let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(mut cli: Cli) -> Result<()> {
+    let format = if cli.json {
+        OutputFormat::Json
+    } else {
+        cli.format.unwrap_or(OutputFormat::Plain)
+    };
+
+    let mut config = Config::load()?;
+    if let Some(name) = &cli.profile {
+        config.select_profile(name)?;
+    }
+    let mut ollama_roots = cli.ollama_root.clone();
+    ollama_roots.extend(config.ollama_roots());
+    let opts = LaunchOptions {
+        dry_run: cli.dry_run,
+        verbose: cli.verbose,
+        detach: cli.detach,
+        wait_ready: !cli.no_wait,
+        ready_timeout: Duration::from_secs(cli.ready_timeout),
+        config,
+        explain: cli.explain,
+        ignore_fit: cli.ignore_fit,
+        ollama_roots,
+        ..LaunchOptions::default()
+    };
+    let stores = opts.ollama_stores();
 
     // Handle passthrough or default command
-    let command = match cli.command {
+    let command = match cli.command.take() {
         Some(c) => c,
         None => Commands::External(vec![]),
     };
@@ -137,14 +287,11 @@ fn main() {
         Commands::External(args) => {
             if cli.ollama {
                 // Pass through to ollama
-                let bin = resolve_bin("ollama").unwrap_or_else(|| {
-                    eprintln!("ollama not found on PATH");
-                    std::process::exit(127)
-                });
+                let bin = require_bin("ollama")?;
                 let mut cmd = Command::new(bin);
                 cmd.args(&args);
                 cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-                spawn_or_print(cmd, cli.dry_run);
+                run_or_print(cmd, &opts)?;
             } else if cli.llamacpp {
                 // Pass through to llama-* tools
                 // If the first arg matches a known tool suffix (e.g. "server", "quantize"), use llama-<arg>
@@ -160,15 +307,12 @@ fn main() {
                     ("llama-cli".to_string(), &args[..])
                 };
 
-                let bin = resolve_bin(&bin_name).unwrap_or_else(|| {
-                    eprintln!("{} not found on PATH", bin_name);
-                    std::process::exit(127)
-                });
+                let bin = require_bin(&bin_name)?;
 
                 let mut cmd = Command::new(bin);
                 cmd.args(args_to_pass);
                 cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-                spawn_or_print(cmd, cli.dry_run);
+                run_or_print(cmd, &opts)?;
             } else {
                 // No flag and no command -> print help
                 use clap::CommandFactory;
@@ -176,365 +320,441 @@ fn main() {
             }
         }
         Commands::Hf { repo, extra } => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+            ensure_models_dir(cli.link_dir.as_ref())?;
             if !cli.llamacpp || cli.ollama {
-                eprintln!("use -c for llama.cpp mode");
-                std::process::exit(2);
+                return Err(GgufyError::Usage("use -c for llama.cpp mode".into()));
             }
-            let port = port_or_default(false, cli.port);
-            run_llama_server_hf(&repo, Some(port), Some(&extra), cli.dry_run, cli.verbose);
+            let port = pick_port(false, &cli, &opts, &repo, None)?;
+            run_llama_server_hf(&repo, Some(port), Some(&extra), &opts)?;
             if let Some(p) = find_hf_cached_gguf(&repo) {
-                symlink_into_guffy(&p, cli.link_dir.as_ref(), cli.force, cli.verbose).ok();
-            }
-        }
-        Commands::Run { target, tag, extra } => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
-            let (name, tag_final) = normalize_model_tag(&target, tag.as_deref());
-            let tag_explicit = tag.is_some();
-            if tag_final.eq_ignore_ascii_case("cloud") {
-                run_ollama_run(&name, &tag_final, Some(&extra), cli.dry_run, cli.verbose);
-            } else if !tag_explicit {
-                if let Some(blob) = resolve_ollama_library_gguf(&name, &tag_final) {
-                    symlink_named_into_guffy(
-                        &blob,
-                        &format!("{}-{}.gguf", name, tag_final),
-                        cli.link_dir.as_ref(),
-                        cli.force,
-                        cli.verbose,
-                    )
-                    .ok();
-                    let port = port_or_default(false, cli.port);
-                    run_llama_server_model(
-                        &blob,
-                        Some(port),
-                        Some(&extra),
-                        cli.dry_run,
-                        cli.verbose,
-                    );
-                } else {
-                    run_ollama_run(&name, "cloud", Some(&extra), cli.dry_run, cli.verbose);
-                }
-            } else if is_cloud_model_available(&name) {
-                run_ollama_run(&name, "cloud", Some(&extra), cli.dry_run, cli.verbose);
-            } else if let Some(blob) = resolve_ollama_library_gguf(&name, &tag_final) {
-                symlink_named_into_guffy(
-                    &blob,
-                    &format!("{}-{}.gguf", name, tag_final),
+                report_link(symlink_into_guffy(
+                    &p,
                     cli.link_dir.as_ref(),
                     cli.force,
                     cli.verbose,
-                )
-                .ok();
-                let port = port_or_default(false, cli.port);
-                run_llama_server_model(&blob, Some(port), Some(&extra), cli.dry_run, cli.verbose);
-            } else {
-                eprintln!(
-                    "no local gguf blob found for {}:{} and cloud unavailable",
-                    name, tag_final
-                );
-                std::process::exit(1);
+                ));
             }
         }
-        Commands::Simple { model, extra } => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
-            let prompt = if extra.is_empty() {
-                String::new()
+        Commands::Run { target, tag, extra } => {
+            ensure_models_dir(cli.link_dir.as_ref())?;
+            // `--tag` only applies to a bare target; a bare one without it
+            // is left for `decide` to try in the link directory first.
+            let (name, tag_final) = normalize_model_tag(&target, tag.as_deref());
+            let model = if tag.is_none() && name == target {
+                target
             } else {
-                extra.join(" ")
+                format!("{}:{}", name, tag_final)
             };
-            if prompt.is_empty() {
-                eprintln!("missing prompt");
-                std::process::exit(2);
-            }
-
-            // Check for audio/TTS in model name (user request: audio models will have "audio" in name)
-            // If detected, force usage of llama-cli (or specific audio handling if needed)
-            let model_lower = model.to_lowercase();
-            let is_audio_model = model_lower.contains("audio");
-
-            if is_audio_model {
-                // For audio models, we prioritize local execution via llama-cli if possible,
-                // or we could just fall through to the standard llama-cli path.
-                // The user specifically said: "when -m model name is appended, audio will be in there,
-                // and that will be the indicator for Guffy to know that we will need to use llama.cli audio commands."
-                // Since we don't have specific "audio commands" confirmed, we will ensure it goes to the llama-cli path.
-
-                // If it's a simple path or model name, we try to resolve it.
-                if let Some(p) = resolve_model_ref(&model, cli.link_dir.as_ref()) {
-                    let bin = resolve_bin("llama-cli").unwrap_or_else(|| {
-                        eprintln!("llama-cli not found on PATH");
-                        std::process::exit(127)
-                    });
-                    let mut cmd = Command::new(bin);
-                    // Assuming standard llama-cli usage is sufficient or user meant just "use llama-cli"
-                    cmd.arg("-m").arg(&p).arg("-p").arg(&prompt).arg("-no-cnv");
-                    cmd.stdout(Stdio::inherit()).stderr(Stdio::null());
-                    spawn_or_print(cmd, cli.dry_run);
-                    return;
-                }
-            }
-            if model.contains(':') {
-                let (name, tag) = split_model_tag(&model);
-                if tag.eq_ignore_ascii_case("cloud") {
-                    run_ollama_simple(&name, &tag, &prompt, cli.dry_run, cli.verbose);
-                } else if let Some(blob) = resolve_ollama_library_gguf(&name, &tag) {
-                    let bin = resolve_bin("llama-cli").unwrap_or_else(|| {
-                        eprintln!("llama-cli not found on PATH");
-                        std::process::exit(127)
-                    });
-                    let mut cmd = Command::new(bin);
-                    cmd.arg("-m")
-                        .arg(&blob)
-                        .arg("-p")
-                        .arg(&prompt)
-                        .arg("-no-cnv");
-                    cmd.stdout(Stdio::inherit()).stderr(Stdio::null());
-                    spawn_or_print(cmd, cli.dry_run);
-                } else if is_cloud_model_available(&name) {
-                    run_ollama_simple(&name, "cloud", &prompt, cli.dry_run, cli.verbose);
-                } else {
-                    eprintln!(
-                        "no local gguf blob found for {}:{} and cloud unavailable",
-                        name, tag
-                    );
-                    std::process::exit(1);
+            let mut probe = SystemProbe {
+                link_dir: cli.link_dir.clone(),
+                ollama_roots: stores.clone(),
+                no_cloud: false,
+            };
+            let d = decide(&model, opts.config.prefer(), &mut probe)?;
+            if opts.explain {
+                eprintln!("route: {} ({})", d.backend(), d.reason);
+            }
+            match d.target {
+                Target::Gguf(path) => {
+                    let port = pick_port(false, &cli, &opts, &model, Some(&path))?;
+                    run_llama_server_model(&path, &model, Some(port), Some(&extra), &opts)?;
                 }
-            } else if let Some(p) = resolve_model_ref(&model, cli.link_dir.as_ref()) {
-                let bin = resolve_bin("llama-cli").unwrap_or_else(|| {
-                    eprintln!("llama-cli not found on PATH");
-                    std::process::exit(127)
-                });
-                let mut cmd = Command::new(bin);
-                cmd.arg("-m").arg(&p).arg("-p").arg(&prompt).arg("-no-cnv");
-                cmd.stdout(Stdio::inherit()).stderr(Stdio::null());
-                spawn_or_print(cmd, cli.dry_run);
-            } else if is_cloud_model_available(&model) {
-                run_ollama_simple(&model, "cloud", &prompt, cli.dry_run, cli.verbose);
-            } else {
-                eprintln!("model not found: {}", model);
-                std::process::exit(1);
+                Target::Ollama(m) => serve_ollama_model(false, &cli, &opts, &m, &extra)?,
+                Target::Cloud { name, tag } => run_ollama_run(&name, &tag, Some(&extra), &opts)?,
             }
         }
+        Commands::Simple {
+            prompt_file,
+            system,
+            stream,
+            model,
+            extra,
+        } => {
+            ensure_models_dir(cli.link_dir.as_ref())?;
+            let prompt = route::read_prompt(&extra, prompt_file.as_deref(), system)?;
+            let opts = LaunchOptions {
+                capture: Capture::from_flags(format == OutputFormat::Json, stream),
+                ..opts
+            };
+            route::run_simple(&model, &prompt, cli.link_dir.as_ref(), &opts)?;
+        }
         Commands::List => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+            ensure_models_dir(cli.link_dir.as_ref())?;
+            let link_dir = ggufy_models_dir_with(cli.link_dir.as_ref());
+            if format != OutputFormat::Plain {
+                for p in find_llama_cache_models() {
+                    report_link(symlink_into_guffy(
+                        &p,
+                        cli.link_dir.as_ref(),
+                        cli.force,
+                        cli.verbose,
+                    ));
+                }
+                for (repo, p) in find_hf_hub_models() {
+                    report_link(symlink_named_into_guffy(
+                        &p,
+                        &hf_hub_link_name(&repo, &p),
+                        cli.link_dir.as_ref(),
+                        cli.force,
+                        cli.verbose,
+                    ));
+                }
+                let mut catalog = open_catalog(&cli);
+                for (name, tag) in enumerate_ollama_library_models(&stores) {
+                    if let Some(blob) = cached_library_gguf(&stores, &name, &tag, &mut catalog) {
+                        report_link(symlink_named_into_guffy(
+                            &blob,
+                            &ollama_link_name(&name, &tag),
+                            cli.link_dir.as_ref(),
+                            cli.force,
+                            cli.verbose,
+                        ));
+                    }
+                }
+                let mut records = llama_cache_records(&link_dir, &mut catalog);
+                records.extend(hf_hub_records(&link_dir, &mut catalog));
+                records.extend(ollama_library_records(&link_dir, &stores, &mut catalog));
+                save_catalog(&mut catalog, cli.verbose);
+                output::print_records(&records, format);
+                return Ok(());
+            }
             println!();
             println!("Ollama models (local):");
             println!();
-            run_ollama_list(cli.dry_run, cli.verbose);
+            if let Err(e) = run_ollama_list(&opts) {
+                warn(&e);
+            }
             println!();
             println!("llama.cpp models (.gguf):");
             println!();
+            let mut catalog = open_catalog(&cli);
             let mut linked = 0usize;
             for p in find_llama_cache_models() {
-                println!("{}", p.display());
-                if symlink_into_guffy(&p, cli.link_dir.as_ref(), cli.force, cli.verbose).is_ok() {
+                if cli.verbose {
+                    println!("{} [{}]", p.display(), describe_gguf(catalog.summary(&p)));
+                } else {
+                    println!("{}", p.display());
+                }
+                if report_link(symlink_into_guffy(
+                    &p,
+                    cli.link_dir.as_ref(),
+                    cli.force,
+                    cli.verbose,
+                )) {
+                    linked += 1;
+                }
+            }
+            let hf_models = find_hf_hub_models();
+            if !hf_models.is_empty() {
+                println!();
+                println!("Hugging Face cache models (.gguf):");
+                println!();
+            }
+            for (repo, p) in hf_models {
+                if cli.verbose {
+                    println!("{} [{}]", p.display(), describe_gguf(catalog.summary(&p)));
+                } else {
+                    println!("{}", p.display());
+                }
+                if report_link(symlink_named_into_guffy(
+                    &p,
+                    &hf_hub_link_name(&repo, &p),
+                    cli.link_dir.as_ref(),
+                    cli.force,
+                    cli.verbose,
+                )) {
                     linked += 1;
                 }
             }
             println!();
-            for (name, tag) in enumerate_ollama_library_models() {
-                if let Some(blob) = resolve_ollama_library_gguf(&name, &tag) {
-                    let link_name = format!("{}-{}.gguf", name, tag);
-                    let _ = symlink_named_into_guffy(
+            for (name, tag) in enumerate_ollama_library_models(&stores) {
+                if let Some(blob) = cached_library_gguf(&stores, &name, &tag, &mut catalog) {
+                    let link_name = ollama_link_name(&name, &tag);
+                    if report_link(symlink_named_into_guffy(
                         &blob,
                         &link_name,
                         cli.link_dir.as_ref(),
                         cli.force,
                         cli.verbose,
-                    )
-                    .map(|_| {
+                    )) {
                         linked += 1;
-                    });
+                    }
                 }
             }
-            let link_dir = ggufy_models_dir_with(cli.link_dir.as_ref());
+            save_catalog(&mut catalog, cli.verbose);
             println!("linked {} models into {}", linked, link_dir.display());
         }
         Commands::OllamaRun { model, extra } => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+            ensure_models_dir(cli.link_dir.as_ref())?;
             if !cli.ollama || cli.llamacpp {
-                eprintln!("use -o for ollama mode");
-                std::process::exit(2);
+                return Err(GgufyError::Usage("use -o for ollama mode".into()));
             }
             let (name, tag) = split_model_tag(&model);
-            if let Some(blob) = resolve_ollama_library_gguf(&name, &tag) {
-                symlink_named_into_guffy(
-                    &blob,
-                    &format!("{}-{}.gguf", name, tag),
-                    cli.link_dir.as_ref(),
-                    cli.force,
-                    cli.verbose,
-                )
-                .ok();
-                let port = port_or_default(true, cli.port);
-                run_llama_server_model(&blob, Some(port), Some(&extra), cli.dry_run, cli.verbose);
+            if let Some(m) = resolve_ollama_model(&stores, &name, &tag)? {
+                serve_ollama_model(true, &cli, &opts, &m, &extra)?;
             } else {
-                eprintln!("no gguf blob found for {}:{} in ollama library", name, tag);
-                std::process::exit(1);
+                return Err(GgufyError::NotInOllamaLibrary { name, tag });
             }
         }
         Commands::Serve { model, extra } => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+            ensure_models_dir(cli.link_dir.as_ref())?;
             if !cli.llamacpp || cli.ollama {
-                eprintln!("use -c for llama.cpp mode");
-                std::process::exit(2);
+                return Err(GgufyError::Usage("use -c for llama.cpp mode".into()));
             }
             let target = resolve_model_ref(&model, cli.link_dir.as_ref())
                 .unwrap_or_else(|| PathBuf::from(&model));
-            let port = port_or_default(false, cli.port);
-            run_llama_server_model(&target, Some(port), Some(&extra), cli.dry_run, cli.verbose);
+            let port = pick_port(false, &cli, &opts, &model, Some(&target))?;
+            run_llama_server_model(&target, &model, Some(port), Some(&extra), &opts)?;
         }
         Commands::Cli { model, extra } => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+            ensure_models_dir(cli.link_dir.as_ref())?;
             if !cli.llamacpp || cli.ollama {
-                eprintln!("use -c for llama.cpp mode");
-                std::process::exit(2);
+                return Err(GgufyError::Usage("use -c for llama.cpp mode".into()));
             }
             let target = resolve_model_ref(&model, cli.link_dir.as_ref())
                 .unwrap_or_else(|| PathBuf::from(&model));
-            run_llama_cli_model(&target, Some(&extra), cli.dry_run, cli.verbose);
+            run_llama_cli_model(&target, &model, Some(&extra), &opts)?;
         }
         Commands::Locate { pattern } => {
-            let re = Regex::new(&pattern).unwrap_or_else(|_| Regex::new(".*").unwrap());
-            for p in find_llama_cache_models()
-                .into_iter()
-                .chain(find_ollama_blob_gguf())
-            {
-                let s = p.to_string_lossy().to_string();
-                if re.is_match(&s) {
-                    println!("{}", s);
+            let re = Regex::new(&pattern)
+                .map_err(|e| GgufyError::Usage(format!("invalid pattern: {}", e)))?;
+            let link_dir = ggufy_models_dir_with(cli.link_dir.as_ref());
+            let mut catalog = open_catalog(&cli);
+            let mut records = llama_cache_records(&link_dir, &mut catalog);
+            records.extend(hf_hub_records(&link_dir, &mut catalog));
+            records.extend(ollama_blob_records(&link_dir, &stores, &mut catalog));
+            records.retain(|r| re.is_match(&r.path.to_string_lossy()));
+            save_catalog(&mut catalog, cli.verbose);
+            if format == OutputFormat::Plain && cli.verbose {
+                for r in &records {
+                    println!("{} [{}]", r.path.display(), describe_gguf(r.gguf.clone()));
                 }
+            } else {
+                output::print_records(&records, format);
             }
         }
+        Commands::Inspect { model, full } => {
+            let path = resolve_model_path(&model, cli.link_dir.as_ref(), &stores)?
+                .ok_or(GgufyError::ModelNotFound(model))?;
+            let g = gguf::read_gguf(&path).map_err(|source| GgufyError::InvalidGguf {
+                path: path.clone(),
+                source,
+            })?;
+            if format == OutputFormat::Json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&inspect::render_json(&path, &g, full)).unwrap()
+                );
+            } else {
+                print!("{}", inspect::render_text(&path, &g, full));
+            }
+        }
+        Commands::Fit { model, ctx, extra } => {
+            let path = resolve_model_path(&model, cli.link_dir.as_ref(), &stores)?
+                .ok_or_else(|| GgufyError::ModelNotFound(model.clone()))?;
+            let g = gguf::read_gguf(&path).map_err(|source| GgufyError::InvalidGguf {
+                path: path.clone(),
+                source,
+            })?;
+            // A pulled Ollama model brings its Modelfile's num_ctx.
+            let (name, tag) = split_model_tag(&model);
+            let opts = match resolve_ollama_model(&stores, &name, &tag) {
+                Ok(Some(m)) if m.model == path => opts.with_model_preset(m.preset(cli.verbose)),
+                _ => opts,
+            };
+            let mut extra = extra;
+            if let Some(n) = ctx {
+                extra.extend(["--ctx-size".to_string(), n.to_string()]);
+            }
+            let args = launch::llama_server_args(&path, &model, &extra, &opts);
+            let est = fit::Estimate::new(&g, &fit::Settings::from_args(&args));
+            let mem = fit::memory();
+            if format == OutputFormat::Json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&fit::render_json(
+                        &model,
+                        &path,
+                        &est,
+                        mem.as_ref()
+                    ))
+                    .unwrap()
+                );
+            } else {
+                print!("{}", fit::render_text(&model, &est, mem.as_ref()));
+            }
+            if let Some(mem) = mem.filter(|m| fit::verdict(&est, m) == fit::Verdict::TooBig) {
+                return Err(fit::wont_fit(&model, &est, &mem));
+            }
+        }
+        Commands::Ps => {
+            output::print_instances(&instance::list_instances(), format);
+        }
+        Commands::Stop { selector, all } => {
+            let targets = match (selector, all) {
+                (_, true) => instance::list_instances(),
+                (Some(sel), false) => instance::find_instances(&sel)?,
+                (None, false) => {
+                    return Err(GgufyError::Usage(
+                        "stop needs an instance id, port or model (or --all)".into(),
+                    ))
+                }
+            };
+            for inst in targets {
+                if cli.dry_run {
+                    println!("stop {} (pid {})", inst.id, inst.pid);
+                    continue;
+                }
+                instance::stop_instance(&inst)?;
+                println!("stopped {} ({})", inst.id, inst.model);
+            }
+        }
+        Commands::Logs {
+            selector,
+            follow,
+            tail,
+        } => {
+            let (log, pid) = match instance::find_instances(&selector) {
+                Ok(mut found) if found.len() == 1 => {
+                    let inst = found.remove(0);
+                    let log = inst.log.ok_or_else(|| {
+                        GgufyError::Usage(format!(
+                            "{} is attached to a terminal; its output is not captured",
+                            inst.id
+                        ))
+                    })?;
+                    (log, Some(inst.pid))
+                }
+                Ok(found) => {
+                    return Err(GgufyError::Usage(format!(
+                        "{} matches {} instances; use an id from `ggufy ps`",
+                        selector,
+                        found.len()
+                    )))
+                }
+                Err(e) => (instance::stale_log(&selector).ok_or(e)?, None),
+            };
+            instance::copy_log(&log, pid, tail, follow, &mut std::io::stdout().lock())?;
+        }
         Commands::Link => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+            ensure_models_dir(cli.link_dir.as_ref())?;
+            let mut catalog = open_catalog(&cli);
             for p in find_llama_cache_models()
                 .into_iter()
-                .chain(find_ollama_blob_gguf())
+                .chain(find_ollama_blob_gguf(&stores, &mut catalog))
             {
-                let _ = symlink_into_guffy(&p, cli.link_dir.as_ref(), cli.force, cli.verbose);
+                report_link(symlink_into_guffy(
+                    &p,
+                    cli.link_dir.as_ref(),
+                    cli.force,
+                    cli.verbose,
+                ));
+            }
+            for (repo, p) in find_hf_hub_models() {
+                report_link(symlink_named_into_guffy(
+                    &p,
+                    &hf_hub_link_name(&repo, &p),
+                    cli.link_dir.as_ref(),
+                    cli.force,
+                    cli.verbose,
+                ));
+            }
+            if format == OutputFormat::Plain {
+                save_catalog(&mut catalog, cli.verbose);
+                println!("link refresh complete");
+            } else {
+                let link_dir = ggufy_models_dir_with(cli.link_dir.as_ref());
+                let mut records = llama_cache_records(&link_dir, &mut catalog);
+                records.extend(hf_hub_records(&link_dir, &mut catalog));
+                records.extend(ollama_blob_records(&link_dir, &stores, &mut catalog));
+                save_catalog(&mut catalog, cli.verbose);
+                output::print_records(&records, format);
             }
-            println!("link refresh complete");
         }
         Commands::OllamaServe { target, extra } => {
-            ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+            ensure_models_dir(cli.link_dir.as_ref())?;
             if !cli.ollama || cli.llamacpp {
-                eprintln!("use -o for ollama mode");
-                std::process::exit(2);
+                return Err(GgufyError::Usage("use -o for ollama mode".into()));
             }
-            let port = port_or_default(true, cli.port);
             if target.contains(':') {
                 let (name, tag) = split_model_tag(&target);
-                if let Some(blob) = resolve_ollama_library_gguf(&name, &tag) {
-                    symlink_named_into_guffy(
-                        &blob,
-                        &format!("{}-{}.gguf", name, tag),
-                        cli.link_dir.as_ref(),
-                        cli.force,
-                        cli.verbose,
-                    )
-                    .ok();
-                    run_llama_server_model(
-                        &blob,
-                        Some(port),
-                        Some(&extra),
-                        cli.dry_run,
-                        cli.verbose,
-                    );
+                if let Some(m) = resolve_ollama_model(&stores, &name, &tag)? {
+                    serve_ollama_model(true, &cli, &opts, &m, &extra)?;
                 } else {
-                    eprintln!("no gguf blob found for {}:{} in ollama library", name, tag);
-                    std::process::exit(1);
+                    return Err(GgufyError::NotInOllamaLibrary { name, tag });
                 }
             } else {
                 let target_path = resolve_model_ref(&target, cli.link_dir.as_ref())
                     .unwrap_or_else(|| PathBuf::from(&target));
-                run_llama_server_model(
-                    &target_path,
-                    Some(port),
-                    Some(&extra),
-                    cli.dry_run,
-                    cli.verbose,
+                let port = pick_port(true, &cli, &opts, &target, Some(&target_path))?;
+                run_llama_server_model(&target_path, &target, Some(port), Some(&extra), &opts)?;
+            }
+        }
+        Commands::Config {
+            sub: ConfigCmd::Show { model },
+        } => {
+            let name = model.as_deref().unwrap_or("");
+            let path = match &model {
+                Some(m) => resolve_model_path(m, cli.link_dir.as_ref(), &stores).unwrap_or(None),
+                None => None,
+            };
+            let resolved = opts.config.resolve(name, path.as_deref());
+            if format == OutputFormat::Json {
+                let mut v = serde_json::to_value(&resolved).unwrap();
+                v["args"] = serde_json::json!(resolved.preset.to_args());
+                println!("{}", serde_json::to_string_pretty(&v).unwrap());
+            } else {
+                print!(
+                    "{}",
+                    describe_config(&resolved, &opts.config.profile_names())
                 );
             }
         }
+        Commands::Gateway(args) => {
+            run_gateway(&cli, opts, args, gateway::DEFAULT_PORT)?;
+        }
         Commands::Ollama { sub } => match sub {
             OllamaCmd::Serve { target, extra } => {
-                ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
-                let port = port_or_default(true, cli.port);
+                ensure_models_dir(cli.link_dir.as_ref())?;
                 if target.contains(':') {
                     let (name, tag) = split_model_tag(&target);
-                    if let Some(blob) = resolve_ollama_library_gguf(&name, &tag) {
-                        symlink_named_into_guffy(
-                            &blob,
-                            &format!("{}-{}.gguf", name, tag),
-                            cli.link_dir.as_ref(),
-                            cli.force,
-                            cli.verbose,
-                        )
-                        .ok();
-                        run_llama_server_model(
-                            &blob,
-                            Some(port),
-                            Some(&extra),
-                            cli.dry_run,
-                            cli.verbose,
-                        );
+                    if let Some(m) = resolve_ollama_model(&stores, &name, &tag)? {
+                        serve_ollama_model(true, &cli, &opts, &m, &extra)?;
                     } else {
-                        eprintln!("no gguf blob found for {}:{} in ollama library", name, tag);
-                        std::process::exit(1);
+                        return Err(GgufyError::NotInOllamaLibrary { name, tag });
                     }
                 } else {
                     let target_path = resolve_model_ref(&target, cli.link_dir.as_ref())
                         .unwrap_or_else(|| PathBuf::from(&target));
-                    run_llama_server_model(
-                        &target_path,
-                        Some(port),
-                        Some(&extra),
-                        cli.dry_run,
-                        cli.verbose,
-                    );
+                    let port = pick_port(true, &cli, &opts, &target, Some(&target_path))?;
+                    run_llama_server_model(&target_path, &target, Some(port), Some(&extra), &opts)?;
                 }
             }
             OllamaCmd::Run { model, extra } => {
-                ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
-                let port = port_or_default(true, cli.port);
+                ensure_models_dir(cli.link_dir.as_ref())?;
                 let (name, tag) = split_model_tag(&model);
-                if let Some(blob) = resolve_ollama_library_gguf(&name, &tag) {
-                    symlink_named_into_guffy(
-                        &blob,
-                        &format!("{}-{}.gguf", name, tag),
-                        cli.link_dir.as_ref(),
-                        cli.force,
-                        cli.verbose,
-                    )
-                    .ok();
-                    run_llama_server_model(
-                        &blob,
-                        Some(port),
-                        Some(&extra),
-                        cli.dry_run,
-                        cli.verbose,
-                    );
+                if let Some(m) = resolve_ollama_model(&stores, &name, &tag)? {
+                    serve_ollama_model(true, &cli, &opts, &m, &extra)?;
                 } else {
-                    eprintln!("no gguf blob found for {}:{} in ollama library", name, tag);
-                    std::process::exit(1);
+                    return Err(GgufyError::NotInOllamaLibrary { name, tag });
                 }
             }
+            OllamaCmd::Api(args) => {
+                run_gateway(&cli, opts, args, port_or_default(true, None))?;
+            }
             OllamaCmd::LinkAll => {
-                ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+                ensure_models_dir(cli.link_dir.as_ref())?;
                 let mut linked = 0usize;
-                for (name, tag) in enumerate_ollama_library_models() {
-                    if let Some(blob) = resolve_ollama_library_gguf(&name, &tag) {
-                        let link_name = format!("{}-{}.gguf", name, tag);
-                        if symlink_named_into_guffy(
+                for (name, tag) in enumerate_ollama_library_models(&stores) {
+                    if let Some(blob) = resolve_ollama_library_gguf(&stores, &name, &tag)? {
+                        let link_name = ollama_link_name(&name, &tag);
+                        if report_link(symlink_named_into_guffy(
                             &blob,
                             &link_name,
                             cli.link_dir.as_ref(),
                             cli.force,
                             cli.verbose,
-                        )
-                        .is_ok()
-                        {
+                        )) {
                             linked += 1;
                         }
                     }
@@ -544,527 +764,205 @@ fn main() {
         },
         Commands::Llama { sub } => match sub {
             LlamaCmd::Hf { repo, extra } => {
-                ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
-                let port = port_or_default(false, cli.port);
-                run_llama_server_hf(&repo, Some(port), Some(&extra), cli.dry_run, cli.verbose);
+                ensure_models_dir(cli.link_dir.as_ref())?;
+                let port = pick_port(false, &cli, &opts, &repo, None)?;
+                run_llama_server_hf(&repo, Some(port), Some(&extra), &opts)?;
                 if let Some(p) = find_hf_cached_gguf(&repo) {
-                    symlink_into_guffy(&p, cli.link_dir.as_ref(), cli.force, cli.verbose).ok();
+                    report_link(symlink_into_guffy(
+                        &p,
+                        cli.link_dir.as_ref(),
+                        cli.force,
+                        cli.verbose,
+                    ));
                 }
             }
             LlamaCmd::Serve { model, extra } => {
-                ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+                ensure_models_dir(cli.link_dir.as_ref())?;
                 let target = resolve_model_ref(&model, cli.link_dir.as_ref())
                     .unwrap_or_else(|| PathBuf::from(&model));
-                let port = port_or_default(false, cli.port);
-                run_llama_server_model(&target, Some(port), Some(&extra), cli.dry_run, cli.verbose);
+                let port = pick_port(false, &cli, &opts, &model, Some(&target))?;
+                run_llama_server_model(&target, &model, Some(port), Some(&extra), &opts)?;
             }
             LlamaCmd::Cli { model, extra } => {
-                ensure_models_dir(cli.link_dir.as_ref()).expect("models dir");
+                ensure_models_dir(cli.link_dir.as_ref())?;
                 let target = resolve_model_ref(&model, cli.link_dir.as_ref())
                     .unwrap_or_else(|| PathBuf::from(&model));
-                run_llama_cli_model(&target, Some(&extra), cli.dry_run, cli.verbose);
+                run_llama_cli_model(&target, &model, Some(&extra), &opts)?;
             }
         },
     }
+    Ok(())
 }
 
-fn ensure_models_dir(link_override: Option<&PathBuf>) -> IoResult<()> {
-    let p = ggufy_models_dir_with(link_override);
-    fs::create_dir_all(&p)
-}
-
-// removed unused ggufy_models_dir to satisfy clippy dead_code
-
-fn ggufy_models_dir_with(link_override: Option<&PathBuf>) -> PathBuf {
-    if let Some(o) = link_override {
-        return o.clone();
-    }
-    let mut p = home_dir().expect("home");
-    p.push(".guffy/models");
-    p
-}
-
-fn llama_cache_dir() -> PathBuf {
-    if let Ok(override_dir) = std::env::var("LLAMA_CPP_CACHE_DIR") {
-        return PathBuf::from(override_dir);
-    }
-    #[cfg(target_os = "macos")]
-    {
-        let mut p = home_dir().expect("home");
-        p.push("Library/Caches/llama.cpp");
-        p
-    }
-    #[cfg(target_os = "linux")]
-    {
-        let mut p = home_dir().expect("home");
-        p.push(".cache/llama.cpp");
-        p
-    }
-    #[cfg(target_os = "windows")]
-    {
-        if let Ok(local_app) = std::env::var("LOCALAPPDATA") {
-            let mut p = PathBuf::from(local_app);
-            p.push("llama.cpp");
-            p
-        } else {
-            PathBuf::from("C:/Users/Public/AppData/Local/llama.cpp")
-        }
-    }
-}
-
-fn ollama_blobs_dir() -> PathBuf {
-    let mut p = home_dir().expect("home");
-    p.push(".ollama/models/blobs");
-    p
-}
-
-fn ollama_library_manifest_dir() -> PathBuf {
-    let mut p = home_dir().expect("home");
-    p.push(".ollama/models/manifests/registry.ollama.ai/library");
-    p
-}
-
-fn symlink_into_guffy(
-    src: &Path,
-    link_override: Option<&PathBuf>,
-    force: bool,
-    verbose: bool,
-) -> IoResult<()> {
-    let name = src.file_name().unwrap().to_string_lossy().to_string();
-    let mut dst = ggufy_models_dir_with(link_override);
-    dst.push(name);
-    if dst.exists() {
-        if force {
-            let _ = fs::remove_file(&dst);
-        } else {
-            return Ok(());
-        }
-    }
-    match symlink_file(src, &dst) {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            if verbose {
-                eprintln!(
-                    "symlink failed: {} -> {} ({}) — falling back to copy",
-                    src.display(),
-                    dst.display(),
-                    e
-                );
-            }
-            fs::copy(src, &dst).map(|_| ())
-        }
-    }
-}
-
-fn symlink_named_into_guffy(
-    src: &Path,
-    link_name: &str,
-    link_override: Option<&PathBuf>,
-    force: bool,
-    verbose: bool,
-) -> IoResult<()> {
-    let mut dst = ggufy_models_dir_with(link_override);
-    dst.push(link_name);
-    if dst.exists() {
-        if force {
-            let _ = fs::remove_file(&dst);
-        } else {
-            return Ok(());
-        }
-    }
-    match symlink_file(src, &dst) {
-        Ok(()) => Ok(()),
+fn run_gateway(cli: &Cli, opts: LaunchOptions, args: GatewayArgs, default_port: u16) -> Result<()> {
+    ensure_models_dir(cli.link_dir.as_ref())?;
+    let port = match cli.port {
+        Some(PortSpec::Fixed(p)) => p,
+        Some(PortSpec::Auto) => choose_port(false, cli.port, cli.port_range)?,
+        None => default_port,
+    };
+    let gopts = gateway::GatewayOptions {
+        host: args.host,
+        port,
+        range: cli.port_range,
+        link_dir: cli.link_dir.clone(),
+        max_models: args.max_models,
+        idle: args.idle_minutes.and_then(gateway::idle_minutes),
+    };
+    gateway::serve(gopts, opts)
+}
+
+// Links a pulled Ollama model into the link dir and serves it with
+// llama-server, along with its projector and adapters.
+fn serve_ollama_model(
+    is_ollama: bool,
+    cli: &Cli,
+    opts: &LaunchOptions,
+    m: &OllamaModel,
+    extra: &[String],
+) -> Result<()> {
+    report_link(symlink_named_into_guffy(
+        &m.model,
+        &ollama_link_name(&m.name, &m.tag),
+        cli.link_dir.as_ref(),
+        cli.force,
+        cli.verbose,
+    ));
+    let label = format!("{}:{}", m.name, m.tag);
+    let port = pick_port(is_ollama, cli, opts, &label, Some(&m.model))?;
+    run_llama_server_model(
+        &m.model,
+        &label,
+        Some(port),
+        Some(extra),
+        &opts.with_model_preset(m.preset(opts.verbose)),
+    )
+}
+
+fn warn(e: &GgufyError) {
+    eprintln!("warning: {}", e);
+}
+
+// Link failures never abort a command; they are reported and skipped.
+fn report_link(res: Result<()>) -> bool {
+    match res {
+        Ok(()) => true,
         Err(e) => {
-            if verbose {
-                eprintln!(
-                    "symlink failed: {} -> {} ({}) — falling back to copy",
-                    src.display(),
-                    dst.display(),
-                    e
-                );
-            }
-            fs::copy(src, &dst).map(|_| ())
+            warn(&e);
+            false
         }
     }
 }
 
-fn find_llama_cache_models() -> Vec<PathBuf> {
-    let root = llama_cache_dir();
-    let mut v = Vec::new();
-    if root.exists() {
-        for e in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
-            if e.file_type().is_file() {
-                let p = e.path().to_path_buf();
-                if p.extension().map(|x| x == "gguf").unwrap_or(false) {
-                    v.push(p);
-                }
-            }
-        }
-    }
-    v
-}
-
-fn find_ollama_blob_gguf() -> Vec<PathBuf> {
-    let root = ollama_blobs_dir();
-    let mut v = Vec::new();
-    if root.exists() {
-        for e in WalkDir::new(root)
-            .max_depth(1)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if e.file_type().is_file() {
-                let p = e.path().to_path_buf();
-                if is_gguf_file(&p).unwrap_or(false) {
-                    v.push(p);
-                }
-            }
-        }
-    }
-    v
-}
-
-fn is_gguf_file(p: &Path) -> IoResult<bool> {
-    let mut f = fs::File::open(p)?;
-    let mut head = [0u8; 4];
-    let n = f.read(&mut head)?;
-    Ok(n >= 4 && &head == b"GGUF")
-}
-
-fn run_llama_server_hf(
-    repo: &str,
-    port: Option<u16>,
-    extra: Option<&[String]>,
-    dry_run: bool,
-    verbose: bool,
-) {
-    let bin = resolve_bin("llama-server").unwrap_or_else(|| {
-        eprintln!("llama-server not found on PATH");
-        std::process::exit(127)
+// Without --port, a `port` from the model's config preset is used. The
+// chosen port is announced when it was picked automatically.
+fn pick_port(
+    is_ollama: bool,
+    cli: &Cli,
+    opts: &LaunchOptions,
+    name: &str,
+    path: Option<&Path>,
+) -> Result<u16> {
+    let spec = cli.port.or_else(|| {
+        opts.config
+            .resolve(name, path)
+            .preset
+            .port
+            .map(PortSpec::Fixed)
     });
-    let mut cmd = Command::new(bin);
-    cmd.arg("-hf").arg(repo);
-    let p = port.unwrap_or(12434);
-    cmd.arg("--port").arg(p.to_string());
-    if let Some(args) = extra {
-        cmd.args(args);
+    let port = choose_port(is_ollama, spec, cli.port_range)?;
+    if cli.port == Some(PortSpec::Auto) {
+        eprintln!("using port {}", port);
     }
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-    if verbose {
-        eprintln!("starting: llama-server -hf {}", repo);
-    }
-    spawn_or_print(cmd, dry_run);
+    Ok(port)
 }
 
-fn run_llama_server_model(
-    model_path: &Path,
-    port: Option<u16>,
-    extra: Option<&[String]>,
-    dry_run: bool,
-    verbose: bool,
-) {
-    let bin = resolve_bin("llama-server").unwrap_or_else(|| {
-        eprintln!("llama-server not found on PATH");
-        std::process::exit(127)
-    });
-    let mut cmd = Command::new(bin);
-    cmd.arg("-m").arg(model_path);
-    let p = port.unwrap_or(12434);
-    cmd.arg("--port").arg(p.to_string());
-    if let Some(args) = extra {
-        cmd.args(args);
+fn describe_config(resolved: &Resolved, profiles: &[String]) -> String {
+    let mut out = String::new();
+    if resolved.sources.is_empty() {
+        out.push_str("# no config files found\n");
     }
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-    if verbose {
-        eprintln!("starting: llama-server -m {}", model_path.display());
+    for p in &resolved.sources {
+        out.push_str(&format!("# source: {}\n", p.display()));
     }
-    spawn_or_print(cmd, dry_run);
-}
-
-fn run_llama_cli_model(model_path: &Path, extra: Option<&[String]>, dry_run: bool, verbose: bool) {
-    let bin = resolve_bin("llama-cli").unwrap_or_else(|| {
-        eprintln!("llama-cli not found on PATH");
-        std::process::exit(127)
-    });
-    let mut cmd = Command::new(bin);
-    cmd.arg("-m").arg(model_path);
-    if let Some(args) = extra {
-        cmd.args(args);
-    }
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-    if verbose {
-        eprintln!("starting: llama-cli -m {}", model_path.display());
+    if !profiles.is_empty() {
+        out.push_str(&format!("# profiles: {}\n", profiles.join(", ")));
     }
-    spawn_or_print(cmd, dry_run);
-}
-
-fn run_ollama_list(dry_run: bool, verbose: bool) {
-    let bin = resolve_bin("ollama").unwrap_or_else(|| {
-        eprintln!("ollama not found on PATH");
-        std::process::exit(127)
-    });
-    if dry_run {
-        println!("{} list", bin.display());
-        return;
-    }
-    let out = Command::new(bin).arg("list").output().expect("ollama list");
-    if verbose {
-        eprintln!("ollama list completed");
-    }
-    let mut buf = out.stdout;
-    if !buf.ends_with(b"\n") {
-        buf.push(b'\n');
-    }
-    print!("{}", String::from_utf8_lossy(&buf));
-}
-
-fn is_cloud_model_available(model: &str) -> bool {
-    let bin = match resolve_bin("ollama") {
-        Some(b) => b,
-        None => return false,
-    };
-    let mut cmd = Command::new(bin);
-    cmd.arg("show").arg(format!("{}:cloud", model));
-    if let Ok(status) = cmd.status() {
-        return status.success();
+    for m in &resolved.matched {
+        out.push_str(&format!("# applied: {}\n", m));
     }
-    false
-}
-
-fn normalize_model_tag(target: &str, tag: Option<&str>) -> (String, String) {
-    if target.contains(':') {
-        let (n, t) = split_model_tag(target);
-        return (n, t);
+    if let Some(route) = resolved.prefer {
+        out.push_str(&format!("# prefer: {}\n", route.as_str()));
     }
-    let name = target.to_string();
-    let t = tag
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| "latest".to_string());
-    (name, t)
-}
-
-fn run_ollama_run(model: &str, tag: &str, extra: Option<&[String]>, dry_run: bool, verbose: bool) {
-    let bin = resolve_bin("ollama").unwrap_or_else(|| {
-        eprintln!("ollama not found on PATH");
-        std::process::exit(127)
-    });
-    let mut cmd = Command::new(bin);
-    cmd.arg("run").arg(format!("{}:{}", model, tag));
-    if let Some(args) = extra {
-        cmd.args(args);
+    for (k, v) in &resolved.env {
+        out.push_str(&format!("# env: {}={}\n", k, v));
     }
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-    if verbose {
-        eprintln!("starting: ollama run {}:{}", model, tag);
-    }
-    spawn_or_print(cmd, dry_run);
-}
-
-fn run_ollama_simple(model: &str, tag: &str, prompt: &str, dry_run: bool, verbose: bool) {
-    let bin = resolve_bin("ollama").unwrap_or_else(|| {
-        eprintln!("ollama not found on PATH");
-        std::process::exit(127)
-    });
-    let mut cmd = Command::new(bin);
-    cmd.arg("run").arg(format!("{}:{}", model, tag)).arg(prompt);
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-    if verbose {
-        eprintln!("starting: ollama run {}:{} \"{}\"", model, tag, prompt);
-    }
-    spawn_or_print(cmd, dry_run);
-}
-
-fn find_hf_cached_gguf(repo: &str) -> Option<PathBuf> {
-    let parts: Vec<&str> = repo.split('/').collect();
-    if parts.len() != 2 {
-        return None;
-    }
-    let owner = parts[0];
-    let name = parts[1];
-    let root = llama_cache_dir();
-    if !root.exists() {
-        return None;
-    }
-    let mut candidates: Vec<PathBuf> = Vec::new();
-    for e in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
-        if e.file_type().is_file() {
-            let p = e.path();
-            if p.extension().map(|x| x == "gguf").unwrap_or(false) {
-                let s = p.to_string_lossy();
-                if s.contains(owner) && s.contains(name) {
-                    candidates.push(p.to_path_buf());
-                }
-            }
-        }
-    }
-    candidates.sort_by_key(|p| fs::metadata(p).map(|m| m.modified().ok()).ok().flatten());
-    candidates.pop()
-}
-
-fn split_model_tag(s: &str) -> (String, String) {
-    let mut parts = s.split(':');
-    let name = parts.next().unwrap_or("").to_string();
-    let tag = parts.next().unwrap_or("latest").to_string();
-    (name, tag)
-}
-
-fn resolve_ollama_library_gguf(model: &str, tag: &str) -> Option<PathBuf> {
-    let mut manifest_path = ollama_library_manifest_dir();
-    manifest_path.push(model);
-    manifest_path.push(tag);
-    if !manifest_path.exists() {
-        return None;
-    }
-    let content = fs::read_to_string(&manifest_path).ok()?;
-    let json: Value = serde_json::from_str(&content).ok()?;
-    let mut blobs: Vec<PathBuf> = Vec::new();
-    collect_digests(&json, &mut blobs);
-    if blobs.is_empty() {
-        return None;
-    }
-    let mut ggufs: Vec<(u64, PathBuf)> = Vec::new();
-    for b in blobs {
-        if is_gguf_file(&b).unwrap_or(false) {
-            if let Ok(md) = fs::metadata(&b) {
-                ggufs.push((md.len(), b));
-            }
-        }
-    }
-    ggufs.sort_by_key(|(len, _)| *len);
-    ggufs.pop().map(|(_, p)| p)
-}
-
-fn collect_digests(v: &Value, out: &mut Vec<PathBuf>) {
-    match v {
-        Value::String(s) => {
-            if s.starts_with("sha256:") {
-                let mut p = ollama_blobs_dir();
-                let fname = s.replace(":", "-");
-                p.push(fname);
-                if p.exists() {
-                    out.push(p);
-                }
-            }
-        }
-        Value::Array(arr) => {
-            for e in arr {
-                collect_digests(e, out);
-            }
-        }
-        Value::Object(map) => {
-            for (_k, val) in map.iter() {
-                collect_digests(val, out);
-            }
-        }
-        _ => {}
-    }
-}
-
-fn enumerate_ollama_library_models() -> Vec<(String, String)> {
-    let root = ollama_library_manifest_dir();
-    let mut out = Vec::new();
-    if !root.exists() {
-        return out;
-    }
-    if let Ok(models) = fs::read_dir(&root) {
-        for m in models.flatten() {
-            let model_name = match m.file_name().into_string() {
-                Ok(s) => s,
-                Err(_) => continue,
-            };
-            let model_dir = m.path();
-            if !model_dir.is_dir() {
-                continue;
-            }
-            if let Ok(tags) = fs::read_dir(&model_dir) {
-                for t in tags.flatten() {
-                    let tag_name = match t.file_name().into_string() {
-                        Ok(s) => s,
-                        Err(_) => continue,
-                    };
-                    let tag_path = t.path();
-                    if tag_path.is_file() {
-                        out.push((model_name.clone(), tag_name));
-                    }
-                }
-            }
-        }
+    out.push_str(&toml::to_string(&resolved.preset).unwrap_or_default());
+    let args = resolved.preset.to_args();
+    if !args.is_empty() {
+        out.push_str(&format!("# llama.cpp args: {}\n", args.join(" ")));
     }
     out
 }
 
-fn resolve_model_ref(model: &str, link_override: Option<&PathBuf>) -> Option<PathBuf> {
-    let mut p = ggufy_models_dir_with(link_override);
-    p.push(model);
-    if p.exists() {
-        return Some(p);
-    }
-    // allow shorthand without .gguf
-    let mut p2 = ggufy_models_dir_with(link_override);
-    p2.push(model);
-    if p2.extension().is_none() {
-        p2.set_extension("gguf");
-    }
-    if p2.exists() {
-        return Some(p2);
-    }
-    None
-}
-
-fn port_or_default(is_ollama: bool, requested: Option<u16>) -> u16 {
-    if let Some(p) = requested {
-        return p;
-    }
-    if is_ollama {
-        11434
+fn open_catalog(cli: &Cli) -> Catalog {
+    let dir = ggufy_models_dir_with(cli.link_dir.as_ref());
+    if cli.rescan {
+        Catalog::empty(&dir)
     } else {
-        12434
+        Catalog::load(&dir)
     }
 }
 
-fn symlink_file(src: &Path, dst: &Path) -> IoResult<()> {
-    #[cfg(unix)]
-    {
-        std::os::unix::fs::symlink(src, dst)
-    }
-    #[cfg(windows)]
-    {
-        std::os::windows::fs::symlink_file(src, dst)
+fn save_catalog(catalog: &mut Catalog, verbose: bool) {
+    if let Err(e) = catalog.save() {
+        if verbose {
+            eprintln!("catalog not saved: {}", e);
+        }
     }
 }
 
-fn resolve_bin(name: &str) -> Option<PathBuf> {
-    if let Ok(path) = std::env::var("PATH") {
-        for p in path.split(if cfg!(windows) { ';' } else { ':' }) {
-            let mut candidate = PathBuf::from(p);
-            candidate.push(name);
-            if cfg!(windows) {
-                if candidate.exists() {
-                    return Some(candidate);
-                }
-                let mut exe = candidate.clone();
-                exe.set_extension("exe");
-                if exe.exists() {
-                    return Some(exe);
-                }
-            } else if candidate.exists() {
-                return Some(candidate);
-            }
+fn describe_gguf(summary: Option<GgufSummary>) -> String {
+    match summary {
+        Some(s) => {
+            let mut parts = Vec::new();
+            if let Some(name) = s.name {
+                parts.push(name);
+            }
+            parts.push(s.architecture.unwrap_or_else(|| "unknown".to_string()));
+            parts.push(format!(
+                "{} params",
+                gguf::format_param_count(s.parameter_count)
+            ));
+            match (s.quantization, s.file_type) {
+                (Some(q), Some(ft)) => parts.push(format!("{} (ftype {})", q, ft)),
+                (Some(q), None) => parts.push(q),
+                _ => {}
+            }
+            if let Some(ctx) = s.context_length {
+                parts.push(format!("ctx {}", ctx));
+            }
+            parts.push(format!(
+                "gguf v{}, {} tensors, {} MiB",
+                s.version,
+                s.tensor_count,
+                s.data_size / (1024 * 1024)
+            ));
+            parts.join(", ")
         }
+        None => "unreadable gguf".to_string(),
     }
-    None
 }
 
-#[allow(clippy::zombie_processes)]
-fn spawn_or_print(mut cmd: Command, dry_run: bool) {
-    if dry_run {
-        let args: Vec<String> = cmd
-            .get_args()
-            .map(|a| a.to_string_lossy().to_string())
-            .collect();
-        let prog = cmd.get_program().to_string_lossy().to_string();
-        println!("{} {}", prog, args.join(" "));
-        return;
-    }
-    let _ = cmd.spawn().expect("spawn");
+// Manifest errors are reported but do not stop a listing.
+fn cached_library_gguf(
+    roots: &[PathBuf],
+    name: &str,
+    tag: &str,
+    catalog: &mut Catalog,
+) -> Option<PathBuf> {
+    resolve_ollama_library_gguf_with(roots, name, tag, |p| catalog.is_gguf(p)).unwrap_or_else(|e| {
+        warn(&e);
+        None
+    })
 }
@@ -0,0 +1,254 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Ollama image manifests (`manifests/<host>/<namespace>/<model>/<tag>`).
+// Each layer's media type says what its blob is: the GGUF weights, a vision
+// projector, a LoRA adapter, or the Modelfile's template, parameters, system
+// prompt and license.
+
+use crate::config::Preset;
+use crate::discovery::ollama_blobs_dir;
+use crate::error::{GgufyError, Result};
+use crate::{instance, template};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MEDIA_TYPE_PREFIX: &str = "application/vnd.ollama.image.";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(default)]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub config: Option<Layer>,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub media_type: String,
+    pub digest: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerKind {
+    Model,
+    Projector,
+    Adapter,
+    Template,
+    Params,
+    System,
+    License,
+    Messages,
+    Other,
+}
+
+impl Layer {
+    pub fn kind(&self) -> LayerKind {
+        match self.media_type.strip_prefix(MEDIA_TYPE_PREFIX) {
+            Some("model") => LayerKind::Model,
+            Some("projector") => LayerKind::Projector,
+            Some("adapter") => LayerKind::Adapter,
+            Some("template") => LayerKind::Template,
+            Some("params") => LayerKind::Params,
+            Some("system") => LayerKind::System,
+            Some("license") => LayerKind::License,
+            Some("messages") => LayerKind::Messages,
+            _ => LayerKind::Other,
+        }
+    }
+
+    // `sha256:abc…` is stored as `blobs/sha256-abc…`.
+    pub fn blob_path(&self, root: &Path) -> PathBuf {
+        ollama_blobs_dir(root).join(self.digest.replace(':', "-"))
+    }
+}
+
+impl Manifest {
+    pub fn read(path: &Path) -> Result<Manifest> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|source| GgufyError::ManifestParse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn layers_of(&self, kind: LayerKind) -> impl Iterator<Item = &Layer> {
+        self.layers.iter().filter(move |l| l.kind() == kind)
+    }
+}
+
+// A pulled model with its layers located on disk. Layers whose blob is
+// missing are left out.
+#[derive(Clone, Debug, Serialize)]
+pub struct OllamaModel {
+    pub name: String,
+    pub tag: String,
+    pub manifest: PathBuf,
+    pub model: PathBuf,
+    pub projector: Option<PathBuf>,
+    pub adapters: Vec<PathBuf>,
+    pub template: Option<PathBuf>,
+    pub params: Option<PathBuf>,
+    pub system: Option<PathBuf>,
+    pub license: Vec<PathBuf>,
+}
+
+impl OllamaModel {
+    // None when the manifest has no model layer or its blob is missing.
+    pub fn from_manifest(
+        name: &str,
+        tag: &str,
+        root: &Path,
+        manifest_path: &Path,
+        manifest: &Manifest,
+    ) -> Option<OllamaModel> {
+        let blobs = |kind| -> Vec<PathBuf> {
+            manifest
+                .layers_of(kind)
+                .map(|l| l.blob_path(root))
+                .filter(|p| p.is_file())
+                .collect()
+        };
+        // Ollama images carry a single model layer.
+        let model = blobs(LayerKind::Model).into_iter().next()?;
+        Some(OllamaModel {
+            name: name.to_string(),
+            tag: tag.to_string(),
+            manifest: manifest_path.to_path_buf(),
+            model,
+            projector: blobs(LayerKind::Projector).into_iter().next(),
+            adapters: blobs(LayerKind::Adapter),
+            template: blobs(LayerKind::Template).into_iter().next(),
+            params: blobs(LayerKind::Params).into_iter().next(),
+            system: blobs(LayerKind::System).into_iter().next(),
+            license: blobs(LayerKind::License),
+        })
+    }
+
+    // Launch defaults carried by the image itself, so that llama.cpp behaves
+    // like `ollama run`: projector, adapters, Modelfile parameters, and the
+    // chat template (with the default system prompt) converted to Jinja.
+    // Config presets and explicit arguments are applied on top.
+    pub fn preset(&self, verbose: bool) -> Preset {
+        let mut preset = Preset {
+            mmproj: self.projector.clone(),
+            lora: self.adapters.clone(),
+            ..Preset::default()
+        };
+        let params = self
+            .params
+            .as_deref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str::<Map<String, Value>>(&s).ok());
+        if let Some(params) = params {
+            preset.merge(&params_preset(&params));
+        }
+        match self.chat_template_file() {
+            Ok(Some(path)) => {
+                preset.chat_template_file = Some(path);
+                preset.args.push("--jinja".to_string());
+            }
+            Ok(None) => {}
+            Err(e) if verbose => eprintln!(
+                "{}:{}: keeping the GGUF chat template ({})",
+                self.name, self.tag, e
+            ),
+            Err(_) => {}
+        }
+        preset
+    }
+
+    // Writes the translated template to the state dir, named after the
+    // template and system blobs, and returns its path.
+    fn chat_template_file(&self) -> std::result::Result<Option<PathBuf>, String> {
+        let template = match &self.template {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let go = fs::read_to_string(template).map_err(|e| e.to_string())?;
+        let system = match &self.system {
+            Some(p) => Some(fs::read_to_string(p).map_err(|e| e.to_string())?),
+            None => None,
+        };
+        let jinja = template::to_jinja(&go, system.as_deref())?;
+        let short = |p: &Path| {
+            let name = p.file_name().unwrap_or_default().to_string_lossy();
+            name.trim_start_matches("sha256-")
+                .chars()
+                .take(12)
+                .collect::<String>()
+        };
+        let mut name = short(template);
+        if let Some(system) = &self.system {
+            name = format!("{}-{}", name, short(system));
+        }
+        let dir = instance::state_dir().join("templates");
+        let path = dir.join(format!("{}.jinja", name));
+        if fs::read_to_string(&path).ok().as_deref() != Some(jinja.as_str()) {
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            fs::write(&path, jinja).map_err(|e| e.to_string())?;
+        }
+        Ok(Some(path))
+    }
+}
+
+// Modelfile PARAMETERs in their llama.cpp spelling. Unknown keys are
+// ignored; Ollama accepts a few that llama.cpp has no flag for.
+pub fn params_preset(params: &Map<String, Value>) -> Preset {
+    let mut p = Preset::default();
+    let int = |k: &str| params.get(k).and_then(Value::as_i64);
+    // Negative or oversized counts are dropped rather than wrapped.
+    let count = |k: &str| int(k).and_then(|v| u32::try_from(v).ok());
+    let float = |k: &str| params.get(k).and_then(Value::as_f64);
+    p.ctx_size = count("num_ctx");
+    p.threads = count("num_thread");
+    p.batch_size = count("num_batch");
+    p.n_gpu_layers = int("num_gpu").and_then(|v| i32::try_from(v).ok());
+    p.temp = float("temperature");
+    p.top_p = float("top_p");
+    p.top_k = count("top_k");
+    p.min_p = float("min_p");
+    p.repeat_penalty = float("repeat_penalty");
+    p.seed = int("seed");
+    p.stop = match params.get("stop") {
+        Some(Value::Array(a)) => a
+            .iter()
+            .filter_map(|s| s.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(s)) => vec![s.clone()],
+        _ => Vec::new(),
+    };
+    for (key, flag) in [
+        ("num_predict", "--n-predict"),
+        ("num_keep", "--keep"),
+        ("repeat_last_n", "--repeat-last-n"),
+        ("presence_penalty", "--presence-penalty"),
+        ("frequency_penalty", "--frequency-penalty"),
+        ("typical_p", "--typical"),
+        ("mirostat", "--mirostat"),
+        ("mirostat_tau", "--mirostat-ent"),
+        ("mirostat_eta", "--mirostat-lr"),
+    ] {
+        if let Some(v) = params.get(key).filter(|v| v.is_number()) {
+            p.args.push(flag.to_string());
+            p.args.push(v.to_string());
+        }
+    }
+    if params.get("use_mmap") == Some(&Value::Bool(false)) {
+        p.args.push("--no-mmap".to_string());
+    }
+    if params.get("use_mlock") == Some(&Value::Bool(true)) {
+        p.args.push("--mlock".to_string());
+    }
+    p
+}
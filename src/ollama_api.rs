@@ -0,0 +1,493 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// The Ollama REST API (`/api/*`) on top of llama-server. Requests are turned
+// into OpenAI-style ones for llama-server's `/v1` endpoints, always streamed;
+// `Reply` folds the SSE events back into Ollama's NDJSON objects. Listings
+// and `show` come from the link directory, the Ollama stores and the GGUF
+// metadata itself.
+
+use crate::catalog::Catalog;
+use crate::discovery::{enumerate_ollama_library_models, ggufy_models_dir_with};
+use crate::gguf::{format_param_count, GgufFile, GgufSummary};
+use crate::inspect::scalar_json;
+use crate::manifest::OllamaModel;
+use crate::resolve::resolve_ollama_model;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    Generate,
+    Chat,
+}
+
+// Ollama `options` with an OpenAI/llama-server request field of their own.
+// `num_ctx` and friends are server settings and come from the model's preset.
+const OPTIONS: [(&str, &str); 10] = [
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("top_k", "top_k"),
+    ("min_p", "min_p"),
+    ("seed", "seed"),
+    ("repeat_penalty", "repeat_penalty"),
+    ("presence_penalty", "presence_penalty"),
+    ("frequency_penalty", "frequency_penalty"),
+    ("stop", "stop"),
+    ("num_predict", "max_tokens"),
+];
+
+// `/api/tags`: link-directory GGUFs as `<stem>:latest`, then every pulled
+// Ollama model. Links that point into an Ollama store are listed once, under
+// their Ollama name.
+pub fn tags(link_dir: Option<&PathBuf>, roots: &[PathBuf]) -> Value {
+    let dir = ggufy_models_dir_with(link_dir);
+    let mut catalog = Catalog::load(&dir);
+    let mut models = Vec::new();
+    let mut links: Vec<PathBuf> = fs::read_dir(&dir)
+        .map(|rd| rd.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    links.sort();
+    for path in links {
+        let stem = match path.file_stem() {
+            Some(s) if path.extension().map(|x| x == "gguf").unwrap_or(false) => {
+                s.to_string_lossy().to_string()
+            }
+            _ => continue,
+        };
+        if stem.to_lowercase().contains("mmproj") || is_ollama_blob(&path) {
+            continue;
+        }
+        let name = format!("{}:latest", stem);
+        models.push(tag_entry(&name, &path, None, &mut catalog));
+    }
+    for (name, tag) in enumerate_ollama_library_models(roots) {
+        if let Ok(Some(m)) = resolve_ollama_model(roots, &name, &tag) {
+            let name = format!("{}:{}", name, tag);
+            models.push(tag_entry(&name, &m.model, Some(&m.model), &mut catalog));
+        }
+    }
+    json!({ "models": models })
+}
+
+fn is_ollama_blob(path: &Path) -> bool {
+    fs::canonicalize(path)
+        .ok()
+        .and_then(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy().starts_with("sha256-"))
+        })
+        .unwrap_or(false)
+}
+
+fn tag_entry(name: &str, path: &Path, blob: Option<&Path>, catalog: &mut Catalog) -> Value {
+    let meta = fs::metadata(path).ok();
+    json!({
+        "name": name,
+        "model": name,
+        "modified_at": rfc3339(meta.as_ref().and_then(|m| m.modified().ok()).unwrap_or(UNIX_EPOCH)),
+        "size": meta.map(|m| m.len()).unwrap_or(0),
+        "digest": blob.map(digest).unwrap_or_default(),
+        "details": details(catalog.summary(path).as_ref()),
+    })
+}
+
+// The sha256 an Ollama blob is named after.
+pub fn digest(blob: &Path) -> String {
+    blob.file_name()
+        .map(|n| {
+            n.to_string_lossy()
+                .trim_start_matches("sha256-")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+pub fn details(summary: Option<&GgufSummary>) -> Value {
+    let family = summary
+        .and_then(|s| s.architecture.clone())
+        .unwrap_or_default();
+    json!({
+        "parent_model": "",
+        "format": "gguf",
+        "family": family,
+        "families": [family],
+        "parameter_size": summary.map(|s| format_param_count(s.parameter_count)).unwrap_or_default(),
+        "quantization_level": summary.and_then(|s| s.quantization.clone()).unwrap_or_default(),
+    })
+}
+
+// `/api/show`. Arrays in `model_info` are null, as Ollama reports them
+// unless asked for verbose output.
+pub fn show(path: &Path, g: &GgufFile, ollama: Option<&OllamaModel>, mmproj: bool) -> Value {
+    let mut info = Map::new();
+    for (k, v) in &g.metadata {
+        info.insert(k.clone(), scalar_json(v));
+    }
+    let read = |p: &Option<PathBuf>| {
+        p.as_deref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .unwrap_or_default()
+    };
+    let (template, system, params, license) = match ollama {
+        Some(m) => (
+            read(&m.template),
+            read(&m.system),
+            read(&m.params),
+            m.license
+                .iter()
+                .filter_map(|p| fs::read_to_string(p).ok())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        None => (
+            g.get_str("tokenizer.chat_template")
+                .unwrap_or_default()
+                .to_string(),
+            String::new(),
+            String::new(),
+            g.get_str("general.license").unwrap_or_default().to_string(),
+        ),
+    };
+    let parameters = parameters_text(&params);
+    let mut modelfile = format!("FROM {}\n", path.display());
+    if !template.is_empty() {
+        modelfile.push_str(&format!("TEMPLATE \"\"\"{}\"\"\"\n", template));
+    }
+    if !system.is_empty() {
+        modelfile.push_str(&format!("SYSTEM \"\"\"{}\"\"\"\n", system));
+    }
+    for line in parameters.lines() {
+        modelfile.push_str(&format!("PARAMETER {}\n", line));
+    }
+    let mut capabilities = vec!["completion"];
+    if mmproj {
+        capabilities.push("vision");
+    }
+    if template.contains("tools") || template.contains(".Tools") {
+        capabilities.push("tools");
+    }
+    json!({
+        "license": license,
+        "modelfile": modelfile,
+        "parameters": parameters,
+        "template": template,
+        "system": system,
+        "details": details(Some(&g.summary())),
+        "model_info": info,
+        "capabilities": capabilities,
+        "modified_at": rfc3339(fs::metadata(path).and_then(|m| m.modified()).unwrap_or(UNIX_EPOCH)),
+    })
+}
+
+// Modelfile PARAMETER lines from the params blob (`stop` may repeat).
+fn parameters_text(params: &str) -> String {
+    let map: Map<String, Value> = serde_json::from_str(params).unwrap_or_default();
+    let mut lines = Vec::new();
+    for (k, v) in map {
+        let values = match v {
+            Value::Array(a) => a,
+            v => vec![v],
+        };
+        for v in values {
+            lines.push(format!("{:<30} {}", k, v));
+        }
+    }
+    lines.join("\n")
+}
+
+// The llama-server path and body for an Ollama generate/chat request.
+// `stop` is the model's Modelfile default, used when the request has none.
+pub fn upstream_request(endpoint: Endpoint, req: &Value, stop: &[String]) -> (&'static str, Value) {
+    let raw = endpoint == Endpoint::Generate && req["raw"] == true;
+    let mut out = Map::new();
+    out.insert("model".into(), req["model"].clone());
+    out.insert("stream".into(), json!(true));
+    out.insert("stream_options".into(), json!({"include_usage": true}));
+    if raw {
+        out.insert("prompt".into(), req["prompt"].clone());
+    } else {
+        let mut messages = Vec::new();
+        if endpoint == Endpoint::Generate {
+            if let Some(system) = req["system"].as_str() {
+                messages.push(json!({"role": "system", "content": system}));
+            }
+            messages.push(message(&json!({
+                "role": "user",
+                "content": req["prompt"],
+                "images": req["images"],
+            })));
+        } else {
+            for m in req["messages"].as_array().into_iter().flatten() {
+                messages.push(message(m));
+            }
+        }
+        out.insert("messages".into(), Value::Array(messages));
+        if let Some(tools) = req.get("tools").filter(|t| t.is_array()) {
+            out.insert("tools".into(), tools.clone());
+        }
+        if let Some(think) = req["think"].as_bool() {
+            out.insert(
+                "chat_template_kwargs".into(),
+                json!({"enable_thinking": think}),
+            );
+        }
+    }
+    for (from, to) in OPTIONS {
+        match req["options"].get(from) {
+            Some(v) if from == "num_predict" && v.as_i64().map(|n| n < 0).unwrap_or(true) => {}
+            Some(v) => {
+                out.insert(to.into(), v.clone());
+            }
+            None => {}
+        }
+    }
+    if !out.contains_key("stop") && !stop.is_empty() {
+        out.insert("stop".into(), json!(stop));
+    }
+    match &req["format"] {
+        Value::String(f) if f == "json" => {
+            out.insert("response_format".into(), json!({"type": "json_object"}));
+        }
+        schema @ Value::Object(_) => {
+            out.insert(
+                "response_format".into(),
+                json!({"type": "json_schema", "json_schema": {"name": "format", "schema": schema}}),
+            );
+        }
+        _ => {}
+    }
+    let path = if raw {
+        "/v1/completions"
+    } else {
+        "/v1/chat/completions"
+    };
+    (path, Value::Object(out))
+}
+
+// An Ollama chat message in OpenAI form: base64 `images` become image parts,
+// tool call arguments become JSON strings, `thinking` is llama-server's
+// `reasoning_content`.
+fn message(m: &Value) -> Value {
+    let mut out = Map::new();
+    out.insert("role".into(), m["role"].clone());
+    let text = m["content"].as_str().unwrap_or_default();
+    let images: Vec<&str> = m["images"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|i| i.as_str())
+        .collect();
+    if images.is_empty() {
+        out.insert("content".into(), json!(text));
+    } else {
+        let mut parts = vec![json!({"type": "text", "text": text})];
+        for image in images {
+            let url = format!("data:image/png;base64,{}", image);
+            parts.push(json!({"type": "image_url", "image_url": {"url": url}}));
+        }
+        out.insert("content".into(), Value::Array(parts));
+    }
+    if let Some(thinking) = m["thinking"].as_str() {
+        out.insert("reasoning_content".into(), json!(thinking));
+    }
+    if let Some(calls) = m["tool_calls"].as_array() {
+        let calls: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let args = match &c["function"]["arguments"] {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                json!({
+                    "id": format!("call_{}", i),
+                    "type": "function",
+                    "function": {"name": c["function"]["name"], "arguments": args},
+                })
+            })
+            .collect();
+        out.insert("tool_calls".into(), Value::Array(calls));
+    }
+    Value::Object(out)
+}
+
+// Tool calls arrive in pieces keyed by index; arguments are a JSON string
+// spread over several events.
+#[derive(Default)]
+struct ToolCall {
+    name: String,
+    arguments: String,
+}
+
+pub struct Reply {
+    endpoint: Endpoint,
+    model: String,
+    started: Instant,
+    load: Duration,
+    text: String,
+    thinking: String,
+    tools: Vec<ToolCall>,
+    finish: Option<String>,
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+    prompt_ms: Option<f64>,
+    completion_ms: Option<f64>,
+}
+
+impl Reply {
+    // `started` is when the request came in; `load` how long starting the
+    // server took.
+    pub fn new(endpoint: Endpoint, model: &str, started: Instant, load: Duration) -> Reply {
+        Reply {
+            endpoint,
+            model: model.to_string(),
+            started,
+            load,
+            text: String::new(),
+            thinking: String::new(),
+            tools: Vec::new(),
+            finish: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            prompt_ms: None,
+            completion_ms: None,
+        }
+    }
+
+    // Folds in one SSE event and returns the object to stream, if the event
+    // carried text.
+    pub fn push(&mut self, event: &Value) -> Option<Value> {
+        if let Some(u) = event.get("usage").filter(|u| u.is_object()) {
+            self.prompt_tokens = u["prompt_tokens"].as_u64().or(self.prompt_tokens);
+            self.completion_tokens = u["completion_tokens"].as_u64().or(self.completion_tokens);
+        }
+        if let Some(t) = event.get("timings").filter(|t| t.is_object()) {
+            self.prompt_tokens = t["prompt_n"].as_u64().or(self.prompt_tokens);
+            self.completion_tokens = t["predicted_n"].as_u64().or(self.completion_tokens);
+            self.prompt_ms = t["prompt_ms"].as_f64();
+            self.completion_ms = t["predicted_ms"].as_f64();
+        }
+        let choice = event["choices"].get(0)?;
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            self.finish = Some(reason.to_string());
+        }
+        let delta = &choice["delta"];
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let i = call["index"].as_u64().unwrap_or(0) as usize;
+            if self.tools.len() <= i {
+                self.tools.resize_with(i + 1, ToolCall::default);
+            }
+            let f = &call["function"];
+            self.tools[i]
+                .name
+                .push_str(f["name"].as_str().unwrap_or_default());
+            self.tools[i]
+                .arguments
+                .push_str(f["arguments"].as_str().unwrap_or_default());
+        }
+        let text = delta["content"]
+            .as_str()
+            .or_else(|| choice["text"].as_str())
+            .unwrap_or_default();
+        let thinking = delta["reasoning_content"].as_str().unwrap_or_default();
+        if text.is_empty() && thinking.is_empty() {
+            return None;
+        }
+        self.text.push_str(text);
+        self.thinking.push_str(thinking);
+        Some(self.object(text, thinking, false))
+    }
+
+    // The closing object; it carries the whole text when nothing was
+    // streamed.
+    pub fn done(&self, streamed: bool) -> Value {
+        let (text, thinking) = if streamed {
+            ("", "")
+        } else {
+            (self.text.as_str(), self.thinking.as_str())
+        };
+        let mut v = self.object(text, thinking, true);
+        let ns = |ms: Option<f64>| ms.map(|ms| (ms * 1e6) as u64).unwrap_or(0);
+        v["done_reason"] = json!(match self.finish.as_deref() {
+            Some("length") => "length",
+            _ => "stop",
+        });
+        v["total_duration"] = json!(self.started.elapsed().as_nanos() as u64);
+        v["load_duration"] = json!(self.load.as_nanos() as u64);
+        v["prompt_eval_count"] = json!(self.prompt_tokens.unwrap_or(0));
+        v["prompt_eval_duration"] = json!(ns(self.prompt_ms));
+        v["eval_count"] = json!(self.completion_tokens.unwrap_or(0));
+        v["eval_duration"] = json!(ns(self.completion_ms));
+        if !self.tools.is_empty() && self.endpoint == Endpoint::Chat {
+            let calls: Vec<Value> = self
+                .tools
+                .iter()
+                .map(|t| {
+                    let args: Value =
+                        serde_json::from_str(&t.arguments).unwrap_or_else(|_| json!(t.arguments));
+                    json!({"function": {"name": t.name, "arguments": args}})
+                })
+                .collect();
+            v["message"]["tool_calls"] = Value::Array(calls);
+        }
+        v
+    }
+
+    fn object(&self, text: &str, thinking: &str, done: bool) -> Value {
+        let mut v = json!({
+            "model": self.model,
+            "created_at": rfc3339(SystemTime::now()),
+        });
+        match self.endpoint {
+            Endpoint::Generate => {
+                v["response"] = json!(text);
+                if !thinking.is_empty() {
+                    v["thinking"] = json!(thinking);
+                }
+            }
+            Endpoint::Chat => {
+                v["message"] = json!({"role": "assistant", "content": text});
+                if !thinking.is_empty() {
+                    v["message"]["thinking"] = json!(thinking);
+                }
+            }
+        }
+        v["done"] = json!(done);
+        v
+    }
+}
+
+// What Ollama answers to an empty prompt (load the model) or one with
+// `keep_alive: 0` (unload it).
+pub fn lifecycle(endpoint: Endpoint, model: &str, reason: &str) -> Value {
+    let mut v = Reply::new(endpoint, model, Instant::now(), Duration::ZERO).object("", "", true);
+    v["done_reason"] = json!(reason);
+    v
+}
+
+pub fn rfc3339(t: SystemTime) -> String {
+    let d = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = d.as_secs();
+    // Days to civil date, after Howard Hinnant's `civil_from_days`.
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        d.subsec_nanos()
+    )
+}
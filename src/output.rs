@@ -0,0 +1,219 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Structured model records shared by `list`, `locate` and `link`.
+
+use crate::gguf::GgufSummary;
+use crate::instance::{self, Instance};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Plain,
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    LlamaCache,
+    OllamaBlob,
+    HfCache,
+}
+
+impl Source {
+    fn label(self) -> &'static str {
+        match self {
+            Source::LlamaCache => "llama",
+            Source::OllamaBlob => "ollama",
+            Source::HfCache => "hf",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStatus {
+    // Symlink in the models dir points at the real path.
+    Linked,
+    // Regular file of the same size (symlink fell back to copy).
+    Copied,
+    // Something else already occupies the link path.
+    Conflict,
+    Missing,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ModelRecord {
+    pub source: Source,
+    pub name: String,
+    pub tag: Option<String>,
+    pub path: PathBuf,
+    pub link: PathBuf,
+    pub size: Option<u64>,
+    pub digest: Option<String>,
+    pub link_status: LinkStatus,
+    pub gguf: Option<GgufSummary>,
+    // Paired projector and what it adds (`vision`, `audio`).
+    pub mmproj: Option<PathBuf>,
+    pub modalities: Vec<String>,
+}
+
+impl ModelRecord {
+    pub fn new(
+        source: Source,
+        name: String,
+        tag: Option<String>,
+        path: &Path,
+        link: PathBuf,
+        gguf: Option<GgufSummary>,
+    ) -> Self {
+        let digest = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .filter(|n| n.starts_with("sha256-"))
+            .map(|n| n.replacen("sha256-", "sha256:", 1));
+        let link_status = link_status(path, &link);
+        ModelRecord {
+            source,
+            name,
+            tag,
+            path: path.to_path_buf(),
+            size: fs::metadata(path).map(|m| m.len()).ok(),
+            link,
+            digest,
+            link_status,
+            gguf,
+            mmproj: None,
+            modalities: Vec::new(),
+        }
+    }
+
+    pub fn with_mmproj(mut self, mmproj: Option<PathBuf>, summary: Option<GgufSummary>) -> Self {
+        if let Some(p) = mmproj {
+            self.modalities = summary.map(|s| s.projector).unwrap_or_default();
+            self.mmproj = Some(p);
+        }
+        self
+    }
+
+    fn multimodal_label(&self) -> String {
+        if self.gguf.as_ref().is_some_and(|g| !g.projector.is_empty()) {
+            "mmproj".to_string()
+        } else if self.mmproj.is_some() && !self.modalities.is_empty() {
+            self.modalities.join("+")
+        } else if self.mmproj.is_some() {
+            "yes".to_string()
+        } else {
+            "-".to_string()
+        }
+    }
+}
+
+pub fn link_status(src: &Path, link: &Path) -> LinkStatus {
+    let meta = match fs::symlink_metadata(link) {
+        Ok(m) => m,
+        Err(_) => return LinkStatus::Missing,
+    };
+    if meta.file_type().is_symlink() {
+        return match fs::read_link(link) {
+            Ok(target) if target == src => LinkStatus::Linked,
+            _ => LinkStatus::Conflict,
+        };
+    }
+    let src_len = fs::metadata(src).map(|m| m.len()).ok();
+    if meta.is_file() && src_len == Some(meta.len()) {
+        LinkStatus::Copied
+    } else {
+        LinkStatus::Conflict
+    }
+}
+
+pub fn print_records(records: &[ModelRecord], format: OutputFormat) {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(records).unwrap());
+        }
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = records
+                .iter()
+                .map(|r| {
+                    vec![
+                        r.source.label().to_string(),
+                        r.name.clone(),
+                        r.tag.clone().unwrap_or_else(|| "-".into()),
+                        r.size
+                            .map(crate::inspect::human_bytes)
+                            .unwrap_or_else(|| "-".into()),
+                        status_label(r.link_status).to_string(),
+                        r.multimodal_label(),
+                        r.path.display().to_string(),
+                    ]
+                })
+                .collect();
+            print_table(
+                &["SOURCE", "NAME", "TAG", "SIZE", "LINK", "MM", "PATH"],
+                &rows,
+            );
+        }
+        OutputFormat::Plain => {
+            for r in records {
+                println!("{}", r.path.display());
+            }
+        }
+    }
+}
+
+fn status_label(s: LinkStatus) -> &'static str {
+    match s {
+        LinkStatus::Linked => "linked",
+        LinkStatus::Copied => "copied",
+        LinkStatus::Conflict => "conflict",
+        LinkStatus::Missing => "missing",
+    }
+}
+
+// Plain and table output are the same for instances: one aligned row each.
+pub fn print_instances(instances: &[Instance], format: OutputFormat) {
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(instances).unwrap());
+        return;
+    }
+    let rows: Vec<Vec<String>> = instances
+        .iter()
+        .map(|i| {
+            vec![
+                i.id.clone(),
+                i.pid.to_string(),
+                i.port.map(|p| p.to_string()).unwrap_or_else(|| "-".into()),
+                instance::format_uptime(i.uptime()),
+                i.model.clone(),
+            ]
+        })
+        .collect();
+    print_table(&["ID", "PID", "PORT", "UPTIME", "MODEL"], &rows);
+}
+
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row.iter()) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(c, w)| format!("{:<width$}", c, width = w))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(header.to_vec());
+    for row in rows {
+        line(row.iter().map(|s| s.as_str()).collect());
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Turning user-supplied model references into paths on disk.
+
+use crate::discovery::{ggufy_models_dir_with, ollama_manifest_path};
+use crate::error::Result;
+use crate::gguf::is_gguf_file;
+use crate::launch::resolve_bin;
+use crate::manifest::{Manifest, OllamaModel};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The tag follows the last ':' in the final path segment, so a registry
+// port (`host:5000/team/model`) is not taken for one.
+pub fn split_model_tag(s: &str) -> (String, String) {
+    let last = s.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match s[last..].rfind(':') {
+        Some(i) => (s[..last + i].to_string(), s[last + i + 1..].to_string()),
+        None => (s.to_string(), "latest".to_string()),
+    }
+}
+
+pub fn normalize_model_tag(target: &str, tag: Option<&str>) -> (String, String) {
+    let (name, t) = split_model_tag(target);
+    if name.len() < target.len() {
+        return (name, t);
+    }
+    let t = tag
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| "latest".to_string());
+    (name, t)
+}
+
+pub fn resolve_model_ref(model: &str, link_override: Option<&PathBuf>) -> Option<PathBuf> {
+    let mut p = ggufy_models_dir_with(link_override);
+    p.push(model);
+    if p.exists() {
+        return Some(p);
+    }
+    // allow shorthand without .gguf
+    let mut p2 = ggufy_models_dir_with(link_override);
+    p2.push(model);
+    if p2.extension().is_none() {
+        p2.set_extension("gguf");
+    }
+    if p2.exists() {
+        return Some(p2);
+    }
+    None
+}
+
+// Accepts a filesystem path, a name in the link directory, or an Ollama
+// `model:tag` reference found in one of `roots`.
+pub fn resolve_model_path(
+    model: &str,
+    link_override: Option<&PathBuf>,
+    roots: &[PathBuf],
+) -> Result<Option<PathBuf>> {
+    let direct = PathBuf::from(model);
+    if direct.is_file() {
+        return Ok(Some(direct));
+    }
+    if let Some(p) = resolve_model_ref(model, link_override) {
+        return Ok(Some(p));
+    }
+    let (name, tag) = split_model_tag(model);
+    resolve_ollama_library_gguf(roots, &name, &tag)
+}
+
+pub fn resolve_ollama_library_gguf(
+    roots: &[PathBuf],
+    model: &str,
+    tag: &str,
+) -> Result<Option<PathBuf>> {
+    resolve_ollama_library_gguf_with(roots, model, tag, |p| is_gguf_file(p).unwrap_or(false))
+}
+
+pub fn resolve_ollama_library_gguf_with(
+    roots: &[PathBuf],
+    model: &str,
+    tag: &str,
+    is_gguf: impl FnMut(&Path) -> bool,
+) -> Result<Option<PathBuf>> {
+    Ok(resolve_ollama_model_with(roots, model, tag, is_gguf)?.map(|m| m.model))
+}
+
+pub fn resolve_ollama_model(
+    roots: &[PathBuf],
+    model: &str,
+    tag: &str,
+) -> Result<Option<OllamaModel>> {
+    resolve_ollama_model_with(roots, model, tag, |p| is_gguf_file(p).unwrap_or(false))
+}
+
+// Reads the manifest from the first of `roots` (see `ollama_models_roots_with`)
+// that has `model:tag`. The model layer must be a GGUF file; anything else
+// cannot be run by llama.cpp.
+pub fn resolve_ollama_model_with(
+    roots: &[PathBuf],
+    model: &str,
+    tag: &str,
+    mut is_gguf: impl FnMut(&Path) -> bool,
+) -> Result<Option<OllamaModel>> {
+    let found = roots.iter().find_map(|root| {
+        let manifest = ollama_manifest_path(root, model, tag)?;
+        manifest.exists().then_some((root, manifest))
+    });
+    let (root, manifest_path) = match found {
+        Some(f) => f,
+        None => return Ok(None),
+    };
+    let manifest = Manifest::read(&manifest_path)?;
+    Ok(
+        OllamaModel::from_manifest(model, tag, root, &manifest_path, &manifest)
+            .filter(|m| is_gguf(&m.model)),
+    )
+}
+
+pub fn is_cloud_model_available(model: &str) -> bool {
+    let bin = match resolve_bin("ollama") {
+        Some(b) => b,
+        None => return false,
+    };
+    let mut cmd = Command::new(bin);
+    cmd.arg("show").arg(format!("{}:cloud", model));
+    if let Ok(status) = cmd.status() {
+        return status.success();
+    }
+    false
+}
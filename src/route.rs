@@ -0,0 +1,216 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Where a one-shot prompt (`ggufy simple`, `ggufy-simple`) goes. `decide` is
+// pure: everything it needs to know about the machine comes through `Probe`,
+// asked lazily and in order, so the cloud check only runs when it matters.
+
+use crate::config::Route;
+use crate::error::{GgufyError, Result};
+use crate::launch::{run_local_prompt, run_ollama_simple, LaunchOptions, Prompt};
+use crate::manifest::OllamaModel;
+use crate::resolve::{
+    is_cloud_model_available, resolve_model_ref, resolve_ollama_model, split_model_tag,
+};
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+pub trait Probe {
+    // A GGUF by path or by name in the link directory.
+    fn local(&mut self, model: &str) -> Option<PathBuf>;
+    fn ollama(&mut self, name: &str, tag: &str) -> Result<Option<OllamaModel>>;
+    fn cloud(&mut self, name: &str) -> bool;
+}
+
+#[derive(Clone, Debug)]
+pub enum Target {
+    Gguf(PathBuf),
+    Ollama(OllamaModel),
+    Cloud { name: String, tag: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct Decision {
+    pub target: Target,
+    pub reason: String,
+}
+
+impl Decision {
+    fn new(target: Target, reason: String) -> Decision {
+        Decision { target, reason }
+    }
+
+    pub fn backend(&self) -> String {
+        match &self.target {
+            Target::Gguf(p) => format!("local gguf {}", p.display()),
+            Target::Ollama(m) => format!("ollama store {}", m.model.display()),
+            Target::Cloud { name, tag } => format!("ollama cloud {}:{}", name, tag),
+        }
+    }
+}
+
+// The link directory first, then the Ollama stores, then `name:cloud`. Local
+// wins over `name:cloud` unless the profile prefers the cloud; an explicit
+// `:cloud` tag always goes to the cloud.
+pub fn decide(model: &str, prefer: Option<Route>, probe: &mut impl Probe) -> Result<Decision> {
+    let cloud_first = prefer == Some(Route::Cloud);
+    let cloud = |name: &str, why: String| {
+        Decision::new(
+            Target::Cloud {
+                name: name.to_string(),
+                tag: "cloud".to_string(),
+            },
+            why,
+        )
+    };
+    if let Some(path) = probe.local(model) {
+        if !cloud_first || !probe.cloud(model) {
+            return Ok(Decision::new(
+                Target::Gguf(path),
+                format!("{} is a local GGUF", model),
+            ));
+        }
+        return Ok(cloud(
+            model,
+            format!("profile prefers the cloud and {}:cloud exists", model),
+        ));
+    }
+    // A bare name is `name:latest` in the Ollama stores, as `ollama run` has it.
+    let bare = !model.contains(':');
+    let (name, tag) = split_model_tag(model);
+    if tag.eq_ignore_ascii_case("cloud") {
+        return Ok(Decision::new(
+            Target::Cloud {
+                name,
+                tag: tag.clone(),
+            },
+            format!("tag :{} asks for the cloud", tag),
+        ));
+    }
+    if cloud_first && probe.cloud(&name) {
+        return Ok(cloud(
+            &name,
+            format!("profile prefers the cloud and {}:cloud exists", name),
+        ));
+    }
+    if let Some(m) = probe.ollama(&name, &tag)? {
+        let why = format!("{}:{} is pulled ({})", name, tag, m.manifest.display());
+        return Ok(Decision::new(Target::Ollama(m), why));
+    }
+    if !cloud_first && probe.cloud(&name) {
+        let why = if bare {
+            format!(
+                "no local GGUF named {} and {}:{} is not pulled, but {}:cloud exists",
+                name, name, tag, name
+            )
+        } else {
+            format!("{}:{} is not pulled but {}:cloud exists", name, tag, name)
+        };
+        return Ok(cloud(&name, why));
+    }
+    if bare {
+        return Err(GgufyError::ModelNotFound(model.to_string()));
+    }
+    Err(GgufyError::NoLocalOrCloud { name, tag })
+}
+
+// The real machine: link directory, Ollama stores, `ollama show`.
+pub struct SystemProbe {
+    pub link_dir: Option<PathBuf>,
+    // Ollama stores to search, from `LaunchOptions::ollama_stores`.
+    pub ollama_roots: Vec<PathBuf>,
+    // Never ask `ollama show`: the gateway sets this when OLLAMA_HOST is the
+    // gateway itself, which would answer the question by asking again.
+    pub no_cloud: bool,
+}
+
+impl Probe for SystemProbe {
+    fn local(&mut self, model: &str) -> Option<PathBuf> {
+        resolve_model_ref(model, self.link_dir.as_ref())
+    }
+
+    fn ollama(&mut self, name: &str, tag: &str) -> Result<Option<OllamaModel>> {
+        resolve_ollama_model(&self.ollama_roots, name, tag)
+    }
+
+    fn cloud(&mut self, name: &str) -> bool {
+        !self.no_cloud && is_cloud_model_available(name)
+    }
+}
+
+// The prompt is the argv words, then `--prompt-file`, then stdin, separated
+// by blank lines. A `-` word reads stdin in its place; otherwise stdin is
+// read only when it is not a terminal.
+pub fn read_prompt(
+    words: &[String],
+    file: Option<&Path>,
+    system: Option<String>,
+) -> Result<Prompt> {
+    let read_stdin = || -> Result<String> {
+        let mut buf = String::new();
+        io::stdin().read_to_string(&mut buf)?;
+        Ok(buf.trim_end().to_string())
+    };
+    let mut parts = Vec::new();
+    let mut line = Vec::new();
+    let mut used_stdin = false;
+    for word in words {
+        if word == "-" && !used_stdin {
+            if !line.is_empty() {
+                parts.push(line.join(" "));
+                line.clear();
+            }
+            parts.push(read_stdin()?);
+            used_stdin = true;
+        } else {
+            line.push(word.as_str());
+        }
+    }
+    if !line.is_empty() {
+        parts.push(line.join(" "));
+    }
+    if let Some(path) = file {
+        let text = fs::read_to_string(path).map_err(|e| {
+            GgufyError::Usage(format!("cannot read prompt file {}: {}", path.display(), e))
+        })?;
+        parts.push(text.trim_end().to_string());
+    }
+    if !used_stdin && !io::stdin().is_terminal() {
+        parts.push(read_stdin()?);
+    }
+    parts.retain(|p| !p.trim().is_empty());
+    if parts.is_empty() {
+        return Err(GgufyError::Usage("missing prompt".into()));
+    }
+    Ok(Prompt {
+        text: parts.join("\n\n"),
+        system: system.filter(|s| !s.is_empty()),
+    })
+}
+
+pub fn run_simple(
+    model: &str,
+    prompt: &Prompt,
+    link_dir: Option<&PathBuf>,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let mut probe = SystemProbe {
+        link_dir: link_dir.cloned(),
+        ollama_roots: opts.ollama_stores(),
+        no_cloud: false,
+    };
+    let d = decide(model, opts.config.prefer(), &mut probe)?;
+    if opts.explain {
+        eprintln!("route: {} ({})", d.backend(), d.reason);
+    }
+    match d.target {
+        Target::Gguf(p) => run_local_prompt(&p, model, prompt, opts),
+        Target::Ollama(m) => run_local_prompt(
+            &m.model,
+            model,
+            prompt,
+            &opts.with_model_preset(m.preset(opts.verbose)),
+        ),
+        Target::Cloud { name, tag } => run_ollama_simple(&name, &tag, prompt, opts),
+    }
+}
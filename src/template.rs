@@ -0,0 +1,665 @@
+// Copyright © 2025 ᓂᐲᔥ ᐙᐸᓂᒥᑮ-ᑭᓇᐙᐸᑭᓯ (Nbiish Waabanimikii-Kinawaabakizi), also known legally as JUSTIN PAUL KENWABIKISE, professionally documented as Nbiish-Justin Paul Kenwabikise, Anishinaabek Dodem (Anishinaabe Clan): Animikii (Thunder), a descendant of Chief ᑭᓇᐙᐸᑭᓯ (Kinwaabakizi) of the Beaver Island Band, and an enrolled member of the sovereign Grand Traverse Band of Ottawa and Chippewa Indians. This work embodies Traditional Knowledge and Traditional Cultural Expressions. All rights reserved.
+
+// Ollama chat templates are Go text/template; llama.cpp takes Jinja. This
+// translates the subset Ollama's library templates use: field access, `if` /
+// `else if` / `else`, `range` (with `$i, $v :=`), `with`, variables, and the
+// functions eq, ne, lt, le, gt, ge, and, or, not, len, slice, index and json.
+// Anything else is an error and the GGUF's own template is used instead.
+//
+// Two shapes are handled the way Ollama renders them:
+//  - templates that use `.Messages` get the whole conversation, with `.System`
+//    set to the joined system messages;
+//  - legacy `.System`/`.Prompt`/`.Response` templates are rendered once per
+//    turn, the last one cut off after `{{ .Response }}`.
+// A default system prompt (the Modelfile's SYSTEM) is used when the request
+// has none.
+//
+// Go trim markers are applied here, and text next to block tags that llama.cpp's
+// trim_blocks/lstrip_blocks would eat is emitted as a string literal, so the
+// output renders identically with or without those settings.
+
+type Res<T> = std::result::Result<T, String>;
+
+pub fn to_jinja(go: &str, default_system: Option<&str>) -> Res<String> {
+    let pieces = lex(go)?;
+    let legacy = !pieces.iter().any(|p| match p {
+        Piece::Action(a) => a.contains(".Messages"),
+        Piece::Text(_) => false,
+    });
+    let mut t = Translator {
+        out: String::new(),
+        blocks: Vec::new(),
+        vars: Vec::new(),
+        legacy,
+        cut: None,
+        seq: 0,
+    };
+    for (i, piece) in pieces.iter().enumerate() {
+        match piece {
+            Piece::Text(text) => {
+                let after_block = i > 0 && is_block(&pieces[i - 1]);
+                let before_block = pieces.get(i + 1).map(is_block).unwrap_or(false);
+                push_text(&mut t.out, text, after_block, before_block);
+            }
+            Piece::Action(body) => t.action(body)?,
+        }
+    }
+    if let Some(b) = t.blocks.last() {
+        return Err(format!("unclosed {{{{ {} }}}}", b.keyword));
+    }
+    let system = default_system.filter(|s| !s.is_empty()).map(literal);
+    Ok(if legacy {
+        t.legacy_wrapper(system)
+    } else {
+        messages_prelude(system) + &t.out
+    })
+}
+
+enum Piece<'a> {
+    Text(String),
+    Action(&'a str),
+}
+
+// Splits into text and `{{ }}` actions, applying `{{-`/`-}}` trimming and
+// dropping comments.
+fn lex(src: &str) -> Res<Vec<Piece<'_>>> {
+    let mut pieces = Vec::new();
+    let mut rest = src;
+    let mut trim_next = false;
+    while let Some(start) = rest.find("{{") {
+        let mut text = &rest[..start];
+        if trim_next {
+            text = text.trim_start();
+        }
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "unterminated {{".to_string())?;
+        let mut body = &after[..end];
+        // As in Go, the marker must be set off by a space, tab or newline.
+        if let Some(b) = body
+            .strip_prefix('-')
+            .filter(|b| b.starts_with(is_go_space))
+        {
+            text = text.trim_end();
+            body = b;
+        }
+        trim_next = false;
+        if let Some(b) = body.strip_suffix('-').filter(|b| b.ends_with(is_go_space)) {
+            trim_next = true;
+            body = b;
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text.to_string()));
+        }
+        let body = body.trim();
+        if !(body.starts_with("/*") && body.ends_with("*/")) {
+            pieces.push(Piece::Action(body));
+        }
+        rest = &after[end + 2..];
+    }
+    let text = if trim_next { rest.trim_start() } else { rest };
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text.to_string()));
+    }
+    Ok(pieces)
+}
+
+fn is_go_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+// Actions that become `{% %}` tags rather than output.
+fn is_block(p: &Piece) -> bool {
+    let body = match p {
+        Piece::Action(b) => *b,
+        Piece::Text(_) => return false,
+    };
+    let word = body.split_whitespace().next().unwrap_or("");
+    matches!(word, "if" | "else" | "end" | "range" | "with")
+        || (body.starts_with('$') && (body.contains(":=") || body.contains(" = ")))
+}
+
+fn push_text(out: &mut String, text: &str, after_block: bool, before_block: bool) {
+    let literal_needed = text.contains("{{")
+        || text.contains("{%")
+        || text.contains("{#")
+        || (after_block && (text.starts_with('\n') || text.starts_with("\r\n")))
+        || (before_block && text.ends_with([' ', '\t']));
+    if literal_needed {
+        out.push_str(&format!("{{{{ {} }}}}", literal(text)));
+    } else {
+        out.push_str(text);
+    }
+}
+
+fn literal(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+fn messages_prelude(system: Option<String>) -> String {
+    let mut p = String::from("{% set ns = namespace(system='') %}");
+    if let Some(system) = system {
+        p.push_str(&format!(
+            "{{% if not (messages and messages[0].role == 'system') %}}\
+             {{% set messages = [{{'role': 'system', 'content': {}}}] + messages %}}\
+             {{% endif %}}",
+            system
+        ));
+    }
+    p.push_str(
+        "{% for m in messages %}{% if m.role == 'system' %}\
+         {% set ns.system = ns.system + ('\\n\\n' if ns.system else '') + m.content %}\
+         {% endif %}{% endfor %}\
+         {% set system = ns.system %}{% set prompt = '' %}{% set response = '' %}",
+    );
+    p
+}
+
+struct Block {
+    keyword: &'static str,
+    // Range and with blocks change what `.` refers to.
+    dot: Option<String>,
+    vars: usize,
+}
+
+struct Translator {
+    out: String,
+    blocks: Vec<Block>,
+    // Go range variables and what they are called in Jinja.
+    vars: Vec<(String, String)>,
+    legacy: bool,
+    // Legacy templates: where `{{ .Response }}` was emitted.
+    cut: Option<(usize, usize)>,
+    seq: usize,
+}
+
+impl Translator {
+    fn dot(&self) -> Option<&str> {
+        self.blocks.iter().rev().find_map(|b| b.dot.as_deref())
+    }
+
+    fn action(&mut self, body: &str) -> Res<()> {
+        let toks = tokenize(body)?;
+        let first = match toks.first() {
+            Some(t) => t,
+            None => return Err("empty action".into()),
+        };
+        match first {
+            Tok::Ident(kw) if kw == "if" => {
+                let cond = self.expr(&toks[1..])?;
+                self.out.push_str(&format!("{{% if {} %}}", cond));
+                self.open("if", None);
+            }
+            Tok::Ident(kw) if kw == "else" => {
+                match toks.get(1) {
+                    Some(Tok::Ident(k)) if k == "if" => {
+                        let cond = self.expr(&toks[2..])?;
+                        self.out.push_str(&format!("{{% elif {} %}}", cond));
+                    }
+                    None => self.out.push_str("{% else %}"),
+                    _ => return Err(format!("unsupported {{{{ {} }}}}", body)),
+                }
+                if self.blocks.is_empty() {
+                    return Err("else outside a block".into());
+                }
+            }
+            Tok::Ident(kw) if kw == "end" => {
+                let b = self.blocks.pop().ok_or("unmatched {{ end }}")?;
+                self.vars.truncate(b.vars);
+                self.out.push_str(match b.keyword {
+                    "range" => "{% endfor %}",
+                    _ => "{% endif %}",
+                });
+            }
+            Tok::Ident(kw) if kw == "with" => {
+                let value = self.expr(&toks[1..])?;
+                self.out.push_str(&format!("{{% if {} %}}", value));
+                self.open("with", Some(value));
+            }
+            Tok::Ident(kw) if kw == "range" => self.range(&toks[1..])?,
+            Tok::Var(name, path) if path.is_empty() && toks.len() > 2 => match &toks[1] {
+                Tok::Declare | Tok::Assign => {
+                    let value = self.expr(&toks[2..])?;
+                    self.out
+                        .push_str(&format!("{{% set ns.{} = {} %}}", name, value));
+                }
+                _ => self.output(&toks)?,
+            },
+            Tok::Ident(kw)
+                if matches!(
+                    kw.as_str(),
+                    "define" | "template" | "block" | "break" | "continue"
+                ) =>
+            {
+                return Err(format!("unsupported {{{{ {} }}}}", kw));
+            }
+            _ => self.output(&toks)?,
+        }
+        Ok(())
+    }
+
+    fn output(&mut self, toks: &[Tok]) -> Res<()> {
+        let is_response = matches!(toks, [Tok::Field(f)] if f == &["Response"])
+            || matches!(toks, [Tok::Var(v, f)] if v.is_empty() && f == &["Response"]);
+        let value = self.expr(toks)?;
+        let start = self.out.len();
+        self.out.push_str(&format!("{{{{ {} }}}}", value));
+        if self.legacy && is_response && self.cut.is_none() {
+            if !self.blocks.is_empty() {
+                return Err("{{ .Response }} inside a block".into());
+            }
+            self.cut = Some((start, self.out.len()));
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, keyword: &'static str, dot: Option<String>) {
+        self.blocks.push(Block {
+            keyword,
+            dot,
+            vars: self.vars.len(),
+        });
+    }
+
+    fn range(&mut self, toks: &[Tok]) -> Res<()> {
+        let (index, value, over) = match toks {
+            [Tok::Var(i, p1), Tok::Comma, Tok::Var(v, p2), Tok::Declare, rest @ ..]
+                if p1.is_empty() && p2.is_empty() =>
+            {
+                (Some(i.clone()), Some(v.clone()), rest)
+            }
+            [Tok::Var(v, p), Tok::Declare, rest @ ..] if p.is_empty() => {
+                (None, Some(v.clone()), rest)
+            }
+            rest => (None, None, rest),
+        };
+        let over_jinja = self.expr(over)?;
+        self.seq += 1;
+        let item = match value.as_deref() {
+            Some(v) if v != "_" => v.to_string(),
+            _ if over_jinja == "messages" => "message".to_string(),
+            _ => format!("item{}", self.seq),
+        };
+        // Go ranges over a map yield key and value.
+        let is_map = matches!(over.last(), Some(Tok::Field(f)) | Some(Tok::Var(_, f))
+            if f.last().map(|s| s == "Properties").unwrap_or(false));
+        let vars = self.vars.len();
+        if is_map {
+            let key = index
+                .clone()
+                .filter(|k| k != "_")
+                .unwrap_or_else(|| format!("key{}", self.seq));
+            self.out.push_str(&format!(
+                "{{% for {}, {} in {}.items() %}}",
+                key, item, over_jinja
+            ));
+            self.vars.push((key.clone(), key));
+        } else {
+            self.out
+                .push_str(&format!("{{% for {} in {} %}}", item, over_jinja));
+            if let Some(i) = index.filter(|i| i != "_") {
+                self.out
+                    .push_str(&format!("{{% set {} = loop.index0 %}}", i));
+                self.vars.push((i.clone(), i));
+            }
+        }
+        if let Some(v) = value.filter(|v| v != "_") {
+            self.vars.push((v.clone(), v));
+        }
+        self.blocks.push(Block {
+            keyword: "range",
+            dot: Some(item),
+            vars,
+        });
+        Ok(())
+    }
+
+    // Pipeline: `a | f b` is `f b a`.
+    fn expr(&self, toks: &[Tok]) -> Res<String> {
+        let mut result: Option<String> = None;
+        for cmd in toks.split(|t| *t == Tok::Pipe) {
+            result = Some(self.command(cmd, result)?);
+        }
+        result.ok_or_else(|| "empty pipeline".to_string())
+    }
+
+    fn command(&self, toks: &[Tok], piped: Option<String>) -> Res<String> {
+        let mut operands = Vec::new();
+        let mut i = 0;
+        let func = match toks.first() {
+            Some(Tok::Ident(f)) if !matches!(f.as_str(), "true" | "false" | "nil") => {
+                i = 1;
+                Some(f.as_str())
+            }
+            _ => None,
+        };
+        while i < toks.len() {
+            let (value, next) = self.operand(toks, i)?;
+            operands.push(value);
+            i = next;
+        }
+        operands.extend(piped);
+        match func {
+            None if operands.len() == 1 => Ok(operands.remove(0)),
+            None => Err("expected a function".into()),
+            Some(f) => call(f, operands),
+        }
+    }
+
+    fn operand(&self, toks: &[Tok], i: usize) -> Res<(String, usize)> {
+        let (mut value, mut next) = match &toks[i] {
+            Tok::Str(s) => (literal(s), i + 1),
+            Tok::Num(n) => (n.clone(), i + 1),
+            Tok::Ident(b) if b == "true" || b == "false" => (b.clone(), i + 1),
+            Tok::Ident(b) if b == "nil" => ("none".to_string(), i + 1),
+            Tok::Field(path) => (self.field(self.dot(), path)?, i + 1),
+            Tok::Var(name, path) if name.is_empty() => (self.field(None, path)?, i + 1),
+            Tok::Var(name, path) => {
+                let base = self
+                    .vars
+                    .iter()
+                    .rev()
+                    .find(|(go, _)| go == name)
+                    .map(|(_, j)| j.clone())
+                    .unwrap_or_else(|| format!("ns.{}", name));
+                (self.field(Some(&base), path)?, i + 1)
+            }
+            Tok::LParen => {
+                let close = matching_paren(toks, i)?;
+                let inner = self.expr(&toks[i + 1..close])?;
+                (format!("({})", inner), close + 1)
+            }
+            t => return Err(format!("unexpected {:?}", t)),
+        };
+        // `(expr).Field`
+        while let Some(Tok::Chain(path)) = toks.get(next) {
+            value = self.field(Some(&value), path)?;
+            next += 1;
+        }
+        Ok((value, next))
+    }
+
+    // `None` is the template root.
+    fn field(&self, base: Option<&str>, path: &[String]) -> Res<String> {
+        let mut parts = path.iter();
+        let mut value = match base {
+            Some(b) => b.to_string(),
+            None => match parts.next() {
+                None => return Err("`.` at the top level".into()),
+                Some(f) => match f.as_str() {
+                    "System" => "system".to_string(),
+                    "Prompt" => "prompt".to_string(),
+                    "Response" => "response".to_string(),
+                    "Messages" => "messages".to_string(),
+                    "Tools" => "tools".to_string(),
+                    other => return Err(format!("unsupported field .{}", other)),
+                },
+            },
+        };
+        for f in parts {
+            value.push('.');
+            value.push_str(&snake_case(f));
+        }
+        Ok(value)
+    }
+
+    fn legacy_wrapper(&self, system: Option<String>) -> String {
+        let body = match self.cut {
+            Some((start, end)) => format!(
+                "{}{{{{ response }}}}{{% if not last %}}{}{{% endif %}}",
+                &self.out[..start],
+                &self.out[end..]
+            ),
+            None => self.out.clone(),
+        };
+        let flush = "{{ turn(ns.system, ns.prompt, ns.response, false) }}\
+                     {% set ns.system = '' %}{% set ns.prompt = '' %}{% set ns.response = '' %}";
+        format!(
+            "{{% macro turn(system, prompt, response, last) %}}\
+             {{% set ns = namespace() %}}{}{{% endmacro %}}\
+             {{% set ns = namespace(system={}, prompt='', response='') %}}\
+             {{% for message in messages %}}\
+             {{% if message.role == 'system' %}}\
+             {{% if ns.prompt or ns.response %}}{}{{% endif %}}\
+             {{% set ns.system = message.content %}}\
+             {{% elif message.role == 'user' %}}\
+             {{% if ns.response %}}{}{{% endif %}}\
+             {{% set ns.prompt = message.content %}}\
+             {{% elif message.role == 'assistant' %}}\
+             {{% set ns.response = message.content %}}\
+             {{% endif %}}{{% endfor %}}\
+             {{{{ turn(ns.system, ns.prompt, ns.response, true) }}}}",
+            body,
+            system.unwrap_or_else(|| "''".to_string()),
+            flush,
+            flush
+        )
+    }
+}
+
+fn call(f: &str, args: Vec<String>) -> Res<String> {
+    let arity = |n: usize| -> Res<()> {
+        if args.len() < n {
+            Err(format!("{} needs {} arguments", f, n))
+        } else {
+            Ok(())
+        }
+    };
+    let compare = |op: &str| -> Res<String> {
+        arity(2)?;
+        Ok(format!("({} {} {})", args[0], op, args[1]))
+    };
+    match f {
+        // Go's eq accepts several candidates: eq x a b is x == a || x == b.
+        "eq" => {
+            arity(2)?;
+            let alts: Vec<String> = args[1..]
+                .iter()
+                .map(|a| format!("{} == {}", args[0], a))
+                .collect();
+            Ok(format!("({})", alts.join(" or ")))
+        }
+        "ne" => compare("!="),
+        "lt" => compare("<"),
+        "le" => compare("<="),
+        "gt" => compare(">"),
+        "ge" => compare(">="),
+        "and" | "or" => {
+            arity(1)?;
+            Ok(format!("({})", args.join(&format!(" {} ", f))))
+        }
+        "not" => {
+            arity(1)?;
+            Ok(format!("(not {})", args[0]))
+        }
+        "len" => {
+            arity(1)?;
+            Ok(format!("({} | length)", args[0]))
+        }
+        "index" => {
+            arity(2)?;
+            Ok(format!("{}[{}]", args[0], args[1..].join("][")))
+        }
+        "slice" => {
+            arity(1)?;
+            let from = args.get(1).cloned().unwrap_or_default();
+            let to = args.get(2).cloned().unwrap_or_default();
+            Ok(format!("{}[{}:{}]", args[0], from, to))
+        }
+        "json" => {
+            arity(1)?;
+            Ok(format!("({} | tojson)", args[0]))
+        }
+        other => Err(format!("unsupported function {}", other)),
+    }
+}
+
+fn snake_case(field: &str) -> String {
+    let mut out = String::new();
+    let chars: Vec<char> = field.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev_lower = i > 0 && chars[i - 1].is_lowercase();
+            let next_lower = chars.get(i + 1).map(|n| n.is_lowercase()).unwrap_or(false);
+            if i > 0 && (prev_lower || (next_lower && chars[i - 1].is_uppercase())) {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(*c);
+        }
+    }
+    out
+}
+
+#[derive(Debug, PartialEq)]
+enum Tok {
+    LParen,
+    RParen,
+    Str(String),
+    Num(String),
+    Ident(String),
+    // `.A.B` (empty for `.`)
+    Field(Vec<String>),
+    // `.A` glued to a closing paren.
+    Chain(Vec<String>),
+    // `$x.A`; the name is empty for `$`.
+    Var(String, Vec<String>),
+    Declare,
+    Assign,
+    Comma,
+    Pipe,
+}
+
+fn tokenize(body: &str) -> Res<Vec<Tok>> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut toks = Vec::new();
+    let mut i = 0;
+    let word_end = |mut j: usize| {
+        while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_' || chars[j] == '.')
+        {
+            j += 1;
+        }
+        j
+    };
+    let path = |s: &str| -> Vec<String> {
+        s.split('.')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.to_string())
+            .collect()
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                toks.push(Tok::LParen);
+                i += 1;
+            }
+            ')' => {
+                toks.push(Tok::RParen);
+                i += 1;
+            }
+            ',' => {
+                toks.push(Tok::Comma);
+                i += 1;
+            }
+            '|' => {
+                toks.push(Tok::Pipe);
+                i += 1;
+            }
+            ':' if chars.get(i + 1) == Some(&'=') => {
+                toks.push(Tok::Declare);
+                i += 2;
+            }
+            '=' => {
+                toks.push(Tok::Assign);
+                i += 1;
+            }
+            '"' => {
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("unterminated string".into()),
+                        Some('"') => break,
+                        Some('\\') => {
+                            let e = chars.get(i + 1).ok_or("unterminated string")?;
+                            s.push(match e {
+                                'n' => '\n',
+                                't' => '\t',
+                                'r' => '\r',
+                                other => *other,
+                            });
+                            i += 2;
+                        }
+                        Some(ch) => {
+                            s.push(*ch);
+                            i += 1;
+                        }
+                    }
+                }
+                toks.push(Tok::Str(s));
+                i += 1;
+            }
+            '`' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|ch| *ch == '`')
+                    .ok_or("unterminated raw string")?;
+                toks.push(Tok::Str(chars[i + 1..i + 1 + end].iter().collect()));
+                i += end + 2;
+            }
+            '.' => {
+                let end = word_end(i + 1);
+                let p = path(&chars[i..end].iter().collect::<String>());
+                if matches!(toks.last(), Some(Tok::RParen)) && i > 0 && chars[i - 1] == ')' {
+                    toks.push(Tok::Chain(p));
+                } else {
+                    toks.push(Tok::Field(p));
+                }
+                i = end;
+            }
+            '$' => {
+                let end = word_end(i + 1);
+                let word: String = chars[i + 1..end].iter().collect();
+                let (name, rest) = word.split_once('.').unwrap_or((&word, ""));
+                toks.push(Tok::Var(name.to_string(), path(rest)));
+                i = end;
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                toks.push(Tok::Num(chars[start..i].iter().collect()));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let end = word_end(i);
+                toks.push(Tok::Ident(chars[i..end].iter().collect()));
+                i = end;
+            }
+            other => return Err(format!("unexpected {:?}", other)),
+        }
+    }
+    Ok(toks)
+}
+
+fn matching_paren(toks: &[Tok], open: usize) -> Res<usize> {
+    let mut depth = 0;
+    for (i, t) in toks.iter().enumerate().skip(open) {
+        match t {
+            Tok::LParen => depth += 1,
+            Tok::RParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("unbalanced parentheses".into())
+}
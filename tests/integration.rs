@@ -3,6 +3,8 @@
 use assert_cmd::prelude::*;
 use std::process::Command;
 
+// `cargo_bin` is deprecated in newer assert_cmd releases.
+#[allow(deprecated)]
 #[test]
 fn help_runs() {
     let mut cmd = Command::cargo_bin("ggufy").unwrap();
@@ -11,6 +13,7 @@ fn help_runs() {
     assert.success();
 }
 
+#[allow(deprecated)]
 #[test]
 fn dry_run_llama_hf() {
     if std::process::Command::new("llama-server")
@@ -34,3 +37,2237 @@ fn dry_run_llama_hf() {
     let assert = cmd.assert();
     assert.success();
 }
+
+fn gguf_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+// Writes a minimal GGUF v3 file: two metadata keys and one F32 tensor.
+fn write_tiny_gguf(path: &std::path::Path, arch: &str) {
+    let mut b = Vec::new();
+    b.extend_from_slice(b"GGUF");
+    b.extend_from_slice(&3u32.to_le_bytes());
+    b.extend_from_slice(&1u64.to_le_bytes());
+    b.extend_from_slice(&2u64.to_le_bytes());
+    gguf_string(&mut b, "general.architecture");
+    b.extend_from_slice(&8u32.to_le_bytes());
+    gguf_string(&mut b, arch);
+    gguf_string(&mut b, &format!("{}.context_length", arch));
+    b.extend_from_slice(&4u32.to_le_bytes());
+    b.extend_from_slice(&2048u32.to_le_bytes());
+    gguf_string(&mut b, "token_embd.weight");
+    b.extend_from_slice(&2u32.to_le_bytes());
+    b.extend_from_slice(&8u64.to_le_bytes());
+    b.extend_from_slice(&4u64.to_le_bytes());
+    b.extend_from_slice(&0u32.to_le_bytes());
+    b.extend_from_slice(&0u64.to_le_bytes());
+    while b.len() % 32 != 0 {
+        b.push(0);
+    }
+    b.extend_from_slice(&[0u8; 8 * 4 * 4]);
+    std::fs::write(path, b).unwrap();
+}
+
+#[test]
+fn inspect_reports_gguf_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = dir.path().join("tiny.gguf");
+    write_tiny_gguf(&model, "llama");
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .args(["inspect", "--json"])
+        .arg(&model)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(v["architecture"], "llama");
+    assert_eq!(v["context_length"], 2048);
+    assert_eq!(v["parameter_count"], 32);
+    assert_eq!(v["tensors"][0]["shape"], serde_json::json!([8, 4]));
+}
+
+#[test]
+fn inspect_json_keeps_truncated_arrays_as_arrays() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = dir.path().join("vocab.gguf");
+    let tokens: Vec<String> = (0..10).map(|i| format!("t{}", i)).collect();
+    write_gguf_with(
+        &model,
+        &[
+            ("general.architecture", serde_json::json!("llama")),
+            ("tokenizer.ggml.tokens", serde_json::json!(tokens)),
+        ],
+        "token_embd.weight",
+    );
+    let inspect = |extra: &[&str]| {
+        let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .args(["inspect", "--json"])
+            .args(extra)
+            .arg(&model)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap()
+    };
+    let v = inspect(&[]);
+    let head = v["metadata"]["tokenizer.ggml.tokens"].as_array().unwrap();
+    assert_eq!(head.len(), 8);
+    assert_eq!(head[0], "t0");
+    assert_eq!(v["truncated"]["tokenizer.ggml.tokens"], 10);
+    let v = inspect(&["--full"]);
+    assert_eq!(
+        v["metadata"]["tokenizer.ggml.tokens"]
+            .as_array()
+            .unwrap()
+            .len(),
+        10
+    );
+    assert_eq!(v["truncated"], serde_json::json!({}));
+}
+
+#[test]
+fn read_gguf_parses_v2_arrays_and_custom_alignment() {
+    use ggufy::gguf::{read_gguf, GgufValue};
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("v2.gguf");
+    let mut b = Vec::new();
+    b.extend_from_slice(b"GGUF");
+    b.extend_from_slice(&2u32.to_le_bytes());
+    b.extend_from_slice(&1u64.to_le_bytes());
+    b.extend_from_slice(&3u64.to_le_bytes());
+    gguf_string(&mut b, "general.alignment");
+    b.extend_from_slice(&4u32.to_le_bytes());
+    b.extend_from_slice(&64u32.to_le_bytes());
+    gguf_string(&mut b, "llama.attention.head_count_kv");
+    b.extend_from_slice(&9u32.to_le_bytes());
+    b.extend_from_slice(&4u32.to_le_bytes());
+    b.extend_from_slice(&3u64.to_le_bytes());
+    for n in [8u32, 0, 8] {
+        b.extend_from_slice(&n.to_le_bytes());
+    }
+    gguf_string(&mut b, "tokenizer.ggml.tokens");
+    b.extend_from_slice(&9u32.to_le_bytes());
+    b.extend_from_slice(&8u32.to_le_bytes());
+    b.extend_from_slice(&2u64.to_le_bytes());
+    gguf_string(&mut b, "<s>");
+    gguf_string(&mut b, "</s>");
+    gguf_string(&mut b, "token_embd.weight");
+    b.extend_from_slice(&2u32.to_le_bytes());
+    b.extend_from_slice(&4u64.to_le_bytes());
+    b.extend_from_slice(&2u64.to_le_bytes());
+    b.extend_from_slice(&1u32.to_le_bytes());
+    b.extend_from_slice(&0u64.to_le_bytes());
+    let header = b.len() as u64;
+    while b.len() % 64 != 0 {
+        b.push(0);
+    }
+    b.extend_from_slice(&[0u8; 16]);
+    std::fs::write(&path, &b).unwrap();
+
+    let g = read_gguf(&path).unwrap();
+    assert_eq!(g.version, 2);
+    assert_eq!(g.alignment, 64);
+    assert_eq!(g.data_offset, header.div_ceil(64) * 64);
+    assert_eq!(
+        g.get("llama.attention.head_count_kv"),
+        Some(&GgufValue::Array(vec![
+            GgufValue::U32(8),
+            GgufValue::U32(0),
+            GgufValue::U32(8)
+        ]))
+    );
+    match g.get("tokenizer.ggml.tokens") {
+        Some(GgufValue::Array(tokens)) => assert_eq!(tokens[1].as_str(), Some("</s>")),
+        other => panic!("tokens: {:?}", other),
+    }
+    assert_eq!(g.tensors[0].n_bytes(), Some(16));
+
+    // One byte short of the tensor data at its 64-byte aligned offset.
+    b.truncate(b.len() - 1);
+    std::fs::write(&path, &b).unwrap();
+    assert!(read_gguf(&path).is_err());
+}
+
+#[test]
+fn read_gguf_rejects_hostile_counts_and_sizes() {
+    use ggufy::gguf::read_gguf;
+    use std::io::ErrorKind;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bad.gguf");
+    let header = |tensors: u64, kvs: u64| {
+        let mut b = Vec::new();
+        b.extend_from_slice(b"GGUF");
+        b.extend_from_slice(&3u32.to_le_bytes());
+        b.extend_from_slice(&tensors.to_le_bytes());
+        b.extend_from_slice(&kvs.to_le_bytes());
+        b
+    };
+    let tensor = |b: &mut Vec<u8>, dims: &[u64], offset: u64| {
+        gguf_string(b, "t");
+        b.extend_from_slice(&(dims.len() as u32).to_le_bytes());
+        for d in dims {
+            b.extend_from_slice(&d.to_le_bytes());
+        }
+        b.extend_from_slice(&0u32.to_le_bytes());
+        b.extend_from_slice(&offset.to_le_bytes());
+    };
+
+    // A huge (sparse) blob lets a corrupt count pass the file size check;
+    // nothing may be allocated for it up front.
+    let mut b = header(0, 1 << 33);
+    gguf_string(&mut b, "k");
+    b.extend_from_slice(&99u32.to_le_bytes());
+    std::fs::write(&path, &b).unwrap();
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(1 << 36)
+        .unwrap();
+    let e = read_gguf(&path).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+
+    let mut b = header(1, 0);
+    tensor(&mut b, &[u64::MAX, 2], 0);
+    std::fs::write(&path, &b).unwrap();
+    assert_eq!(read_gguf(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+
+    let mut b = header(1, 0);
+    tensor(&mut b, &[4], u64::MAX - 31);
+    std::fs::write(&path, &b).unwrap();
+    assert_eq!(read_gguf(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+
+    // Array headers nested this deep would overflow the stack.
+    let mut b = header(0, 1);
+    gguf_string(&mut b, "k");
+    b.extend_from_slice(&9u32.to_le_bytes());
+    for _ in 0..100_000 {
+        b.extend_from_slice(&9u32.to_le_bytes());
+        b.extend_from_slice(&1u64.to_le_bytes());
+    }
+    std::fs::write(&path, &b).unwrap();
+    assert_eq!(read_gguf(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn list_json_emits_records() {
+    let home = tempfile::tempdir().unwrap();
+    let cache = home.path().join("cache");
+    let links = home.path().join("links");
+    std::fs::create_dir_all(&cache).unwrap();
+    write_tiny_gguf(&cache.join("tiny.gguf"), "llama");
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", home.path())
+        .env("LLAMA_CPP_CACHE_DIR", &cache)
+        .env_remove("HF_HOME")
+        .env_remove("HF_HUB_CACHE")
+        .arg("--link-dir")
+        .arg(&links)
+        .args(["list", "--json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let records = v.as_array().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["source"], "llama_cache");
+    assert_eq!(records[0]["name"], "tiny");
+    assert_eq!(records[0]["link_status"], "linked");
+    assert!(links.join("tiny.gguf").exists());
+    assert_eq!(records[0]["gguf"]["architecture"], "llama");
+    let catalog = std::fs::read_to_string(links.join(".ggufy-catalog.json")).unwrap();
+    assert!(catalog.contains("tiny.gguf"));
+}
+
+#[test]
+fn catalog_remembers_unreadable_files_until_they_change() {
+    use ggufy::catalog::Catalog;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.gguf");
+    std::fs::write(&path, b"GGUF\x03\x00\x00\x00truncated").unwrap();
+
+    let mut catalog = Catalog::empty(dir.path());
+    assert!(catalog.is_gguf(&path));
+    assert!(catalog.summary(&path).is_none());
+    catalog.save().unwrap();
+    let saved = std::fs::read_to_string(dir.path().join(".ggufy-catalog.json")).unwrap();
+    assert!(saved.contains("\"unreadable\":true"));
+    assert!(Catalog::load(dir.path()).summary(&path).is_none());
+
+    write_tiny_gguf(&path, "llama");
+    let summary = Catalog::load(dir.path()).summary(&path).unwrap();
+    assert_eq!(summary.architecture.as_deref(), Some("llama"));
+}
+
+#[test]
+fn exit_codes_distinguish_missing_model_and_binary() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", dir.path())
+        .arg("--link-dir")
+        .arg(dir.path())
+        .args(["inspect", "no-such-model"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("model not found: no-such-model"));
+
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", dir.path())
+        .env("PATH", "")
+        .arg("--link-dir")
+        .arg(dir.path())
+        .args(["-c", "serve", "model.gguf"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(127));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("llama-server not found on PATH"));
+}
+
+#[test]
+fn library_api_resolves_and_links() {
+    use ggufy::{gguf, link, resolve};
+    assert_eq!(
+        resolve::split_model_tag("gemma3"),
+        ("gemma3".to_string(), "latest".to_string())
+    );
+    assert_eq!(
+        resolve::split_model_tag("my.registry:5000/team/coder:7b"),
+        ("my.registry:5000/team/coder".to_string(), "7b".to_string())
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let links = dir.path().join("links");
+    std::fs::create_dir_all(&links).unwrap();
+    let model = dir.path().join("tiny.gguf");
+    write_tiny_gguf(&model, "qwen2");
+    let g = gguf::read_gguf(&model).unwrap();
+    assert_eq!(g.architecture(), Some("qwen2"));
+
+    link::symlink_into_guffy(&model, Some(&links), false, false).unwrap();
+    let resolved = resolve::resolve_model_ref("tiny", Some(&links)).unwrap();
+    assert_eq!(resolved, links.join("tiny.gguf"));
+    assert!(matches!(
+        resolve::resolve_model_path("missing", Some(&links), &[]),
+        Ok(None)
+    ));
+
+    let store = dir.path().join("store");
+    write_ollama_model(&store, "qwen", "0.5b", "qwen2");
+    let roots = ggufy::discovery::ollama_models_roots_with(std::slice::from_ref(&store));
+    assert!(roots.contains(&store));
+    let blob = resolve::resolve_model_path("qwen:0.5b", Some(&links), &roots)
+        .unwrap()
+        .unwrap();
+    assert!(blob.starts_with(store.join("blobs")));
+    assert!(matches!(
+        resolve::resolve_model_path("qwen:0.5b", Some(&links), &[]),
+        Ok(None)
+    ));
+}
+
+// Lays out an Ollama store under `root` the way `ollama pull` does: a
+// manifest whose model layer points at a GGUF blob. `name` is expanded like
+// Ollama does (`model`, `user/model`, `host/namespace/model`).
+fn write_ollama_model(
+    root: &std::path::Path,
+    name: &str,
+    tag: &str,
+    arch: &str,
+) -> std::path::PathBuf {
+    let digest = format!("{:0>64}", format!("{:x}", name.len() * 131 + tag.len()));
+    let blobs = root.join("blobs");
+    std::fs::create_dir_all(&blobs).unwrap();
+    write_tiny_gguf(&blobs.join(format!("sha256-{}", digest)), arch);
+    let manifests = match name.matches('/').count() {
+        0 => root.join("manifests/registry.ollama.ai/library").join(name),
+        1 => root.join("manifests/registry.ollama.ai").join(name),
+        _ => root.join("manifests").join(name),
+    };
+    std::fs::create_dir_all(&manifests).unwrap();
+    let manifest = serde_json::json!({
+        "schemaVersion": 2,
+        "layers": [{
+            "mediaType": "application/vnd.ollama.image.model",
+            "digest": format!("sha256:{}", digest),
+        }],
+    });
+    std::fs::write(manifests.join(tag), manifest.to_string()).unwrap();
+    manifests.join(tag)
+}
+
+// Adds a layer with the given media type suffix and blob contents.
+fn add_ollama_layer(root: &std::path::Path, manifest: &std::path::Path, kind: &str, body: &[u8]) {
+    let mut m: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(manifest).unwrap()).unwrap();
+    let digest = format!("sha256:{:0>64}", kind.len() * 7919 + body.len());
+    std::fs::write(root.join("blobs").join(digest.replace(':', "-")), body).unwrap();
+    m["layers"].as_array_mut().unwrap().push(serde_json::json!({
+        "mediaType": format!("application/vnd.ollama.image.{}", kind),
+        "digest": digest,
+        "size": body.len(),
+    }));
+    std::fs::write(manifest, m.to_string()).unwrap();
+}
+
+// Stands in for llama-server: logs its arguments and idles without binding.
+#[cfg(unix)]
+const FAKE_SERVER: &str = "echo \"fake $@\"\nexec /bin/sleep 30";
+
+#[cfg(unix)]
+fn write_script(dir: &std::path::Path, name: &str, body: &str) {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::create_dir_all(dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+// The first of `n` consecutive ports that are free right now, found by
+// letting the OS pick one and checking the ports after it.
+#[cfg(unix)]
+fn free_ports(n: u16) -> u16 {
+    use std::net::TcpListener;
+    loop {
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let start = first.local_addr().unwrap().port();
+        let Some(end) = start.checked_add(n - 1) else {
+            continue;
+        };
+        if (start + 1..=end).all(|p| TcpListener::bind(("127.0.0.1", p)).is_ok()) {
+            return start;
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn serve_is_tracked_by_ps_logs_and_stop() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    let model = dir.path().join("tiny.gguf");
+    write_tiny_gguf(&model, "llama");
+    let state = dir.path().join("state");
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", &state)
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let port = free_ports(1).to_string();
+    let out = ggufy(&[
+        "-c",
+        "--detach",
+        "--no-wait",
+        "--port",
+        &port,
+        "serve",
+        model.to_str().unwrap(),
+    ]);
+    assert!(out.status.success());
+    let ps: serde_json::Value = serde_json::from_slice(&ggufy(&["ps", "--json"]).stdout).unwrap();
+    let ps = ps.as_array().unwrap();
+    assert_eq!(ps.len(), 1);
+    assert_eq!(ps[0]["port"].to_string(), port);
+    assert_eq!(ps[0]["model"], "tiny");
+    let id = ps[0]["id"].as_str().unwrap().to_string();
+
+    // The log is written by the child; give it a moment.
+    let mut log = String::new();
+    for _ in 0..50 {
+        log = String::from_utf8_lossy(&ggufy(&["logs", &id]).stdout).to_string();
+        if !log.is_empty() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(log.contains(&format!("--port {}", port)));
+
+    assert!(ggufy(&["stop", &port]).status.success());
+    let ps: serde_json::Value = serde_json::from_slice(&ggufy(&["ps", "--json"]).stdout).unwrap();
+    assert!(ps.as_array().unwrap().is_empty());
+    assert_eq!(ggufy(&["stop", &port]).status.code(), Some(3));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn records_whose_pid_was_reused_are_pruned_not_signalled() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    std::fs::create_dir_all(&state).unwrap();
+    let mut bystander = Command::new("sleep").arg("30").spawn().unwrap();
+    // A server that is long gone, and whose pid now belongs to `bystander`.
+    let record = serde_json::json!({
+        "id": "deadbeef",
+        "pid": bystander.id(),
+        "port": 18999,
+        "model": "tiny",
+        "model_path": null,
+        "program": "llama-server",
+        "args": [],
+        "started_at": 0,
+        "log": null,
+        "start_time": 1,
+    });
+    std::fs::write(state.join("deadbeef.json"), record.to_string()).unwrap();
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("GGUFY_STATE_DIR", &state)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    assert!(ggufy(&["stop", "--all"]).status.success());
+    assert!(bystander.try_wait().unwrap().is_none());
+    assert!(!state.join("deadbeef.json").exists());
+    let ps: serde_json::Value = serde_json::from_slice(&ggufy(&["ps", "--json"]).stdout).unwrap();
+    assert!(ps.as_array().unwrap().is_empty());
+    bystander.kill().unwrap();
+    bystander.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn attached_server_that_is_not_ready_fails_with_its_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_tiny_gguf(&dir.path().join("tiny.gguf"), "llama");
+    let serve = |script: &str| {
+        write_script(&bin, "llama-server", script);
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args([
+                "-c",
+                "--ready-timeout",
+                "1",
+                "--port",
+                "auto",
+                "serve",
+                "tiny",
+            ])
+            .output()
+            .unwrap()
+    };
+
+    let out = serve("echo 'error: unknown model architecture' >&2\nexit 1");
+    assert_eq!(out.status.code(), Some(6));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        err.contains("exited before it was ready (exit status 1)"),
+        "{}",
+        err
+    );
+    assert_eq!(
+        err.matches("unknown model architecture").count(),
+        2,
+        "{}",
+        err
+    );
+
+    let out = serve("echo 'loading model' >&2\nexec /bin/sleep 30");
+    assert_eq!(out.status.code(), Some(6));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("timed out after 1s"), "{}", err);
+    assert!(err.ends_with("loading model\n"), "{}", err);
+    assert!(gateway_ps(dir.path()).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn attached_launch_propagates_child_exit_status() {
+    let dir = tempfile::tempdir().unwrap();
+    write_script(dir.path(), "llama-cli", "echo \"loading $2\"\nexit 7");
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", dir.path())
+        .env("PATH", dir.path())
+        .env("GGUFY_STATE_DIR", dir.path().join("state"))
+        .args(["-c", "cli", "model.gguf"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(7));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("loading model.gguf"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("llama-cli exited with status 7"));
+}
+
+#[test]
+fn health_wait_ready_polls_until_200() {
+    use ggufy::health::{self, Health, NotReady};
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    // Answers 503 twice, then 200, like llama-server while loading a model.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for (i, stream) in listener.incoming().enumerate() {
+            let mut stream = stream.unwrap();
+            let mut req = [0u8; 512];
+            let _ = stream.read(&mut req);
+            let status = if i < 2 {
+                "503 Service Unavailable"
+            } else {
+                "200 OK"
+            };
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                status
+            );
+        }
+    });
+    assert_eq!(
+        health::wait_ready("127.0.0.1", port, Duration::from_secs(10), || true),
+        Ok(())
+    );
+    assert_eq!(health::probe("127.0.0.1", port), Health::Ready);
+
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed_port = closed.local_addr().unwrap().port();
+    drop(closed);
+    assert_eq!(
+        health::wait_ready("127.0.0.1", closed_port, Duration::from_secs(10), || false),
+        Err(NotReady::Exited)
+    );
+    assert!(matches!(
+        health::wait_ready("127.0.0.1", closed_port, Duration::ZERO, || true),
+        Err(NotReady::TimedOut {
+            last: Health::Down,
+            ..
+        })
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn port_conflicts_and_auto_allocation() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    let state = dir.path().join("state");
+    let low = free_ports(4);
+    let range = format!("{}-{}", low, low + 3);
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", &state)
+            .args(["-c", "--no-wait", "--port-range", &range])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let first = ggufy(&["-d", "--port", "auto", "serve", "a.gguf"]);
+    assert!(first.status.success());
+    let stdout = String::from_utf8_lossy(&first.stdout).to_string();
+    let port: u16 = stdout
+        .split("on port ")
+        .nth(1)
+        .and_then(|s| s.split_whitespace().next())
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((low..=low + 3).contains(&port));
+
+    let clash = ggufy(&["--port", &port.to_string(), "serve", "b.gguf"]);
+    assert_eq!(clash.status.code(), Some(7));
+    assert!(String::from_utf8_lossy(&clash.stderr).contains("ggufy instance"));
+
+    let second = ggufy(&["--dry-run", "--port", "auto", "serve", "b.gguf"]);
+    assert!(second.status.success());
+    assert!(!String::from_utf8_lossy(&second.stdout).contains(&format!("--port {}", port)));
+
+    ggufy(&["stop", "--all"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn config_presets_merge_before_extra_args() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[defaults]\nctx_size = 8192\nn_gpu_layers = 0\n\n\
+         [models.\"qwen*\"]\nctx_size = 16384\ntemp = 0.7\n\n\
+         [models.\"qwen2.5-7b*\"]\nthreads = 8\n",
+    )
+    .unwrap();
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_CONFIG", &config)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let out = ggufy(&["config", "show", "--json", "qwen2.5-7b-instruct"]);
+    assert!(out.status.success());
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(v["preset"]["ctx_size"], 16384);
+    assert_eq!(v["preset"]["threads"], 8);
+    assert_eq!(v["preset"]["n_gpu_layers"], 0);
+    assert_eq!(v["matched"].as_array().unwrap().len(), 3);
+
+    let out = ggufy(&[
+        "-c",
+        "--dry-run",
+        "--port",
+        "auto",
+        "serve",
+        "qwen2.5-7b.gguf",
+        "--",
+        "--ctx-size",
+        "4096",
+    ]);
+    let line = String::from_utf8_lossy(&out.stdout).to_string();
+    let preset = line.find("--ctx-size 16384").unwrap();
+    let explicit = line.find("--ctx-size 4096").unwrap();
+    assert!(preset < explicit);
+    assert!(line.contains("--temp 0.7"));
+
+    std::fs::write(&config, "[defaults]\nctx = 1\n").unwrap();
+    assert_eq!(ggufy(&["config", "show"]).status.code(), Some(2));
+}
+
+#[test]
+fn project_config_needs_trust_for_env_and_args() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = dir.path().join("repo");
+    std::fs::create_dir_all(project.join("sub")).unwrap();
+    let global = dir.path().join("config.toml");
+    std::fs::write(&global, "[defaults]\nctx_size = 8192\n").unwrap();
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .current_dir(project.join("sub"))
+            .env("HOME", dir.path())
+            .env("GGUFY_CONFIG", &global)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let show = ["--profile", "dev", "config", "show", "--json", "tiny"];
+
+    std::fs::write(
+        project.join(".ggufy.toml"),
+        "[profiles.dev]\nctx_size = 4096\nenv = { LD_PRELOAD = \"/tmp/x.so\" }\n",
+    )
+    .unwrap();
+    let out = ggufy(&show);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("env in [profiles.dev]"));
+
+    std::fs::write(
+        project.join(".ggufy.toml"),
+        "[models.\"*\"]\nargs = [\"--host\", \"0.0.0.0\"]\n\n[profiles.dev]\n",
+    )
+    .unwrap();
+    assert_eq!(ggufy(&show).status.code(), Some(2));
+
+    // Plain presets need no trust; `env` and `args` need it from the global file.
+    std::fs::write(
+        project.join(".ggufy.toml"),
+        "[profiles.dev]\nctx_size = 4096\n",
+    )
+    .unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&ggufy(&show).stdout).unwrap();
+    assert_eq!(v["preset"]["ctx_size"], 4096);
+    std::fs::write(
+        project.join(".ggufy.toml"),
+        "[profiles.dev]\nenv = { GGML_SCHED_MAX_COPIES = \"1\" }\n",
+    )
+    .unwrap();
+    std::fs::write(&global, "trust_project_config = true\n").unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&ggufy(&show).stdout).unwrap();
+    assert_eq!(v["env"]["GGML_SCHED_MAX_COPIES"], "1");
+
+    // A project file cannot vouch for itself.
+    std::fs::write(&global, "").unwrap();
+    std::fs::write(
+        project.join(".ggufy.toml"),
+        "trust_project_config = true\n[profiles.dev]\n",
+    )
+    .unwrap();
+    assert_eq!(ggufy(&show).status.code(), Some(2));
+}
+
+#[cfg(unix)]
+#[test]
+fn profiles_apply_flags_env_and_routing() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    write_script(&bin, "llama-cli", "exit 0");
+    write_script(&bin, "ollama", "exit 0");
+    std::fs::write(dir.path().join("tiny.gguf"), b"GGUF").unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[defaults]\nctx_size = 8192\n\n\
+         [profiles.low-mem]\nctx_size = 4096\nn_gpu_layers = 0\nprefer = \"local\"\n\
+         env = { GGML_SCHED_MAX_COPIES = \"1\" }\n\n\
+         [profiles.cloud]\nprefer = \"cloud\"\n",
+    )
+    .unwrap();
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_CONFIG", &config)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let out = ggufy(&["--profile", "low-mem", "config", "show", "--json", "tiny"]);
+    assert!(out.status.success());
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(v["preset"]["ctx_size"], 4096);
+    assert_eq!(v["prefer"], "local");
+    assert_eq!(v["env"]["GGML_SCHED_MAX_COPIES"], "1");
+
+    let out = ggufy(&[
+        "-c",
+        "--dry-run",
+        "--profile",
+        "low-mem",
+        "--port",
+        "auto",
+        "serve",
+        "tiny",
+    ]);
+    let line = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(line.starts_with("GGML_SCHED_MAX_COPIES=1 "));
+    assert!(line.contains("--ctx-size 4096 --n-gpu-layers 0"));
+    assert!(!line.contains("8192"));
+
+    let out = ggufy(&["--dry-run", "simple", "tiny", "hi"]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("llama-cli"));
+    let out = ggufy(&["--dry-run", "--profile", "cloud", "simple", "tiny", "hi"]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("run tiny:cloud hi"));
+
+    let out = ggufy(&["--profile", "nope", "config", "show"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("cloud, low-mem"));
+
+    std::fs::write(&config, "[profiles.typo]\nctx = 1\n").unwrap();
+    assert_eq!(ggufy(&["config", "show"]).status.code(), Some(2));
+}
+
+#[test]
+fn ollama_roots_from_env_flag_and_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let env_root = dir.path().join("env-store");
+    let flag_root = dir.path().join("flag-store");
+    let config_root = dir.path().join("cfg/store");
+    write_ollama_model(&env_root, "alpha", "latest", "llama");
+    write_ollama_model(&flag_root, "beta", "7b", "qwen2");
+    write_ollama_model(&config_root, "gamma", "1b", "gemma3");
+    let config = dir.path().join("cfg/config.toml");
+    std::fs::write(&config, "ollama_roots = [\"store\"]\n").unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", dir.path())
+        .env("OLLAMA_MODELS", &env_root)
+        .env("GGUFY_CONFIG", &config)
+        .env("LLAMA_CPP_CACHE_DIR", dir.path().join("none"))
+        .env("HF_HUB_CACHE", dir.path().join("none"))
+        .arg("--link-dir")
+        .arg(dir.path().join("links"))
+        .arg("--ollama-root")
+        .arg(&flag_root)
+        .args(["list", "--json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let names: Vec<String> = v
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|r| r["source"] == "ollama_blob")
+        .map(|r| {
+            format!(
+                "{}:{}",
+                r["name"].as_str().unwrap(),
+                r["tag"].as_str().unwrap()
+            )
+        })
+        .collect();
+    for want in ["alpha:latest", "beta:7b", "gamma:1b"] {
+        assert!(names.iter().any(|n| n == want), "{} missing", want);
+    }
+    assert!(dir.path().join("links/beta-7b.gguf").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn namespaced_ollama_models_resolve_and_link() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    let store = dir.path().join("store");
+    write_ollama_model(&store, "hf.co/bartowski/Foo-GGUF", "Q4_K_M", "llama");
+    write_ollama_model(&store, "jdoe/coder", "7b", "qwen2");
+    write_ollama_model(&store, "gemma3", "1b", "gemma3");
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("OLLAMA_MODELS", &store)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path().join("links"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let out = ggufy(&["list", "--json"]);
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let names: Vec<String> = v
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|r| r["source"] == "ollama_blob")
+        .map(|r| {
+            format!(
+                "{}:{}",
+                r["name"].as_str().unwrap(),
+                r["tag"].as_str().unwrap()
+            )
+        })
+        .collect();
+    for want in [
+        "hf.co/bartowski/Foo-GGUF:Q4_K_M",
+        "jdoe/coder:7b",
+        "gemma3:1b",
+    ] {
+        assert!(names.iter().any(|n| n == want), "{} missing", want);
+    }
+    let links = dir.path().join("links");
+    assert!(links.join("hf.co_bartowski_Foo-GGUF-Q4_K_M.gguf").exists());
+    assert!(links.join("jdoe_coder-7b.gguf").exists());
+
+    let out = ggufy(&[
+        "-o",
+        "--dry-run",
+        "--port",
+        "auto",
+        "ollama-run",
+        "hf.co/bartowski/Foo-GGUF:Q4_K_M",
+    ]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("/store/blobs/sha256-"));
+}
+
+#[cfg(unix)]
+#[test]
+fn ollama_manifest_layers_drive_launch_args() {
+    use ggufy::manifest::{LayerKind, Manifest};
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    let store = dir.path().join("store");
+    let manifest = write_ollama_model(&store, "llava", "7b", "llama");
+    write_tiny_gguf(&dir.path().join("proj.gguf"), "clip");
+    let projector = std::fs::read(dir.path().join("proj.gguf")).unwrap();
+    add_ollama_layer(&store, &manifest, "projector", &projector);
+    add_ollama_layer(&store, &manifest, "adapter", b"GGUF lora");
+    add_ollama_layer(&store, &manifest, "template", b"{{ .Prompt }}");
+    // Bigger than the model: must not be picked by size.
+    add_ollama_layer(&store, &manifest, "license", &[b'x'; 4096]);
+
+    let m = Manifest::read(&manifest).unwrap();
+    let kinds: Vec<LayerKind> = m.layers.iter().map(|l| l.kind()).collect();
+    assert_eq!(
+        kinds,
+        [
+            LayerKind::Model,
+            LayerKind::Projector,
+            LayerKind::Adapter,
+            LayerKind::Template,
+            LayerKind::License
+        ]
+    );
+
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", dir.path())
+        .env("PATH", &bin)
+        .env("OLLAMA_MODELS", &store)
+        .env("GGUFY_STATE_DIR", dir.path().join("state"))
+        .arg("--link-dir")
+        .arg(dir.path().join("links"))
+        .args([
+            "-o",
+            "--dry-run",
+            "--port",
+            "auto",
+            "ollama-run",
+            "llava:7b",
+        ])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let line = String::from_utf8_lossy(&out.stdout).to_string();
+    let blob = |l: &serde_json::Value| {
+        store
+            .join("blobs")
+            .join(l["digest"].as_str().unwrap().replace(':', "-"))
+            .display()
+            .to_string()
+    };
+    let v: serde_json::Value = serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    let layers = v["layers"].as_array().unwrap();
+    assert!(line.contains(&format!("-m {}", blob(&layers[0]))));
+    assert!(line.contains(&format!("--mmproj {}", blob(&layers[1]))));
+    assert!(line.contains(&format!("--lora {}", blob(&layers[2]))));
+}
+
+#[cfg(unix)]
+#[test]
+fn ollama_params_and_template_become_flags() {
+    use ggufy::template::to_jinja;
+    let messages = "{{- range $i, $_ := .Messages }}\
+        {{- $last := eq (len (slice $.Messages $i)) 1 }}\
+        {{- if eq .Role \"user\" }}USER: {{ .Content }}\n{{ end }}\
+        {{- if $last }}ASSISTANT:{{ end }}{{ end }}";
+    let jinja = to_jinja(messages, None).unwrap();
+    assert!(jinja.contains("{% for message in messages %}"));
+    assert!(jinja.contains("{% if (message.role == \"user\") %}USER: {{ message.content }}"));
+    assert!(to_jinja("{{ currentDate }}", None).is_err());
+    let trimmed = to_jinja("x {{-\t/* note */\n-}} y{{- \n.Prompt}}", None).unwrap();
+    assert!(trimmed.contains("%}xy{{ prompt }}"), "{}", trimmed);
+
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    write_script(&bin, "llama-cli", "exit 0");
+    let store = dir.path().join("store");
+    let manifest = write_ollama_model(&store, "qwen", "0.5b", "qwen2");
+    let params = serde_json::json!({
+        "num_ctx": 8192,
+        "temperature": 0.2,
+        "num_predict": 64,
+        "num_thread": -1,
+        "num_batch": 4294967296u64,
+        "stop": ["<|im_end|>"],
+    });
+    add_ollama_layer(&store, &manifest, "params", params.to_string().as_bytes());
+    add_ollama_layer(
+        &store,
+        &manifest,
+        "template",
+        b"{{ if .System }}<|im_start|>system\n{{ .System }}<|im_end|>\n{{ end }}\
+          <|im_start|>user\n{{ .Prompt }}<|im_end|>\n<|im_start|>assistant\n{{ .Response }}<|im_end|>\n",
+    );
+    add_ollama_layer(&store, &manifest, "system", b"Be brief.");
+    let state = dir.path().join("state");
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[defaults]\nstop = [\"User:\"]\n").unwrap();
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("OLLAMA_MODELS", &store)
+            .env("GGUFY_STATE_DIR", &state)
+            .env("GGUFY_CONFIG", &config)
+            .arg("--link-dir")
+            .arg(dir.path().join("links"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let out = ggufy(&[
+        "-o",
+        "--dry-run",
+        "--port",
+        "auto",
+        "ollama-run",
+        "qwen:0.5b",
+    ]);
+    assert!(out.status.success());
+    let line = String::from_utf8_lossy(&out.stdout).to_string();
+    for flag in ["--ctx-size 8192", "--temp 0.2", "--n-predict 64", "--jinja"] {
+        assert!(line.contains(flag), "{} missing from {}", flag, line);
+    }
+    assert!(!line.contains("--reverse-prompt"));
+    assert!(!line.contains("--threads") && !line.contains("--batch-size"));
+    let template = line
+        .split("--chat-template-file ")
+        .nth(1)
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap();
+    assert!(template.starts_with(state.join("templates").to_str().unwrap()));
+    let jinja = std::fs::read_to_string(template).unwrap();
+    assert!(jinja.contains("{% macro turn(system, prompt, response, last) %}"));
+    assert!(jinja.contains("namespace(system=\"Be brief.\""));
+
+    std::fs::write(dir.path().join("links/chat.gguf"), b"GGUF").unwrap();
+    let out = ggufy(&["-c", "--dry-run", "cli", "chat"]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("--reverse-prompt User:"));
+}
+
+#[cfg(unix)]
+#[test]
+fn mmproj_siblings_pair_with_their_models() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", FAKE_SERVER);
+    write_script(&bin, "llama-cli", "exit 0");
+    let cache = dir.path().join("cache");
+    let links = dir.path().join("links");
+    std::fs::create_dir_all(&cache).unwrap();
+    write_tiny_gguf(&cache.join("owner_vl-GGUF_vl-Q4_K_M.gguf"), "llama");
+    write_tiny_gguf(&cache.join("owner_vl-GGUF_mmproj-f16.gguf"), "clip");
+    write_tiny_gguf(&cache.join("other_x-GGUF_mmproj-f16.gguf"), "clip");
+    write_tiny_gguf(&cache.join("owner_text-GGUF_text-Q4_K_M.gguf"), "llama");
+    let projector = std::fs::canonicalize(cache.join("owner_vl-GGUF_mmproj-f16.gguf")).unwrap();
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("LLAMA_CPP_CACHE_DIR", &cache)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .env_remove("HF_HOME")
+            .env_remove("HF_HUB_CACHE")
+            .arg("--link-dir")
+            .arg(&links)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let out = ggufy(&["list", "--json"]);
+    assert!(out.status.success());
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let record = |name: &str| {
+        v.as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["name"] == name)
+            .unwrap()
+            .clone()
+    };
+    let vl = record("owner_vl-GGUF_vl-Q4_K_M");
+    assert_eq!(vl["mmproj"], projector.to_string_lossy().as_ref());
+    assert_eq!(vl["modalities"], serde_json::json!(["vision"]));
+    assert!(record("owner_text-GGUF_text-Q4_K_M")["mmproj"].is_null());
+    assert_eq!(
+        record("owner_vl-GGUF_mmproj-f16")["gguf"]["projector"],
+        serde_json::json!(["vision"])
+    );
+    let table = String::from_utf8_lossy(&ggufy(&["list", "--format", "table"]).stdout).to_string();
+    assert!(table.lines().next().unwrap().contains("MM"));
+    assert!(table
+        .lines()
+        .any(|l| l.contains("owner_vl-GGUF_vl-Q4_K_M") && l.contains("vision")));
+
+    let model = links.join("owner_vl-GGUF_vl-Q4_K_M.gguf");
+    let model = model.to_str().unwrap();
+    let out = ggufy(&["-c", "--dry-run", "--port", "auto", "serve", model]);
+    let line = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(line.contains(&format!("--mmproj {}", projector.display())));
+    let out = ggufy(&["-c", "--dry-run", "cli", model]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("--mmproj"));
+    let out = ggufy(&["-c", "--dry-run", "serve", model, "--", "--no-mmproj"]);
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("--mmproj"));
+}
+
+// A GGUF with the given metadata (strings, u32s, bools, string arrays) and one
+// tensor; enough for `ModelKind` classification.
+fn write_gguf_with(path: &std::path::Path, kv: &[(&str, serde_json::Value)], tensor: &str) {
+    let mut b = Vec::new();
+    b.extend_from_slice(b"GGUF");
+    b.extend_from_slice(&3u32.to_le_bytes());
+    b.extend_from_slice(&1u64.to_le_bytes());
+    b.extend_from_slice(&(kv.len() as u64).to_le_bytes());
+    for (key, value) in kv {
+        gguf_string(&mut b, key);
+        match value {
+            serde_json::Value::Bool(v) => {
+                b.extend_from_slice(&7u32.to_le_bytes());
+                b.push(*v as u8);
+            }
+            serde_json::Value::Number(n) => {
+                b.extend_from_slice(&4u32.to_le_bytes());
+                b.extend_from_slice(&(n.as_u64().unwrap() as u32).to_le_bytes());
+            }
+            serde_json::Value::Array(items) => {
+                b.extend_from_slice(&9u32.to_le_bytes());
+                b.extend_from_slice(&8u32.to_le_bytes());
+                b.extend_from_slice(&(items.len() as u64).to_le_bytes());
+                for item in items {
+                    gguf_string(&mut b, item.as_str().unwrap());
+                }
+            }
+            other => {
+                b.extend_from_slice(&8u32.to_le_bytes());
+                gguf_string(&mut b, other.as_str().unwrap());
+            }
+        }
+    }
+    gguf_string(&mut b, tensor);
+    b.extend_from_slice(&1u32.to_le_bytes());
+    b.extend_from_slice(&4u64.to_le_bytes());
+    b.extend_from_slice(&0u32.to_le_bytes());
+    b.extend_from_slice(&0u64.to_le_bytes());
+    while b.len() % 32 != 0 {
+        b.push(0);
+    }
+    b.extend_from_slice(&[0u8; 16]);
+    std::fs::write(path, b).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn audio_models_are_routed_by_gguf_metadata() {
+    use serde_json::json;
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    for tool in ["llama-cli", "llama-tts", "llama-mtmd-cli", "ollama"] {
+        write_script(&bin, tool, &format!("echo \"{} $@\"", tool));
+    }
+    let models = dir.path().join("models");
+    std::fs::create_dir_all(&models).unwrap();
+    let arch = |a: &str| ("general.architecture", json!(a));
+    write_gguf_with(
+        &models.join("outetts-Q8_0.gguf"),
+        &[
+            arch("llama"),
+            (
+                "tokenizer.ggml.tokens",
+                json!(["<|im_start|>", "<|text_start|>", "<|audio_start|>"]),
+            ),
+        ],
+        "token_embd.weight",
+    );
+    write_gguf_with(
+        &models.join("WavTokenizer-F16.gguf"),
+        &[arch("wavtokenizer-dec")],
+        "posnet.0.norm.weight",
+    );
+    let snapshot = dir.path().join("snapshot");
+    std::fs::create_dir_all(&snapshot).unwrap();
+    let omni = snapshot.join("omni-Q4_K_M.gguf");
+    write_gguf_with(&omni, &[arch("qwen2")], "token_embd.weight");
+    write_gguf_with(
+        &snapshot.join("mmproj-omni-f16.gguf"),
+        &[arch("clip"), ("clip.has_audio_encoder", json!(true))],
+        "a.blk.0.attn_q.weight",
+    );
+    write_gguf_with(
+        &models.join("audio-notes.gguf"),
+        &[arch("llama")],
+        "token_embd.weight",
+    );
+    let clip = models.join("clip.wav");
+    std::fs::write(&clip, b"RIFF").unwrap();
+    let run = |exe: &str, args: &[&str]| {
+        let out = Command::new(exe)
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("LLAMA_CPP_CACHE_DIR", dir.path().join("cache"))
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(&models)
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&out.stdout).to_string()
+    };
+    let ggufy = |args: &[&str]| run(env!("CARGO_BIN_EXE_ggufy"), args);
+
+    let tts = ggufy(&["--dry-run", "simple", "outetts-Q8_0", "hello", "there"]);
+    assert!(tts.contains("llama-tts -m"));
+    assert!(tts.contains("-mv"));
+    assert!(tts.contains("WavTokenizer-F16.gguf"));
+
+    let clip_arg = clip.to_str().unwrap();
+    let omni = ggufy(&[
+        "--dry-run",
+        "simple",
+        omni.to_str().unwrap(),
+        "transcribe",
+        clip_arg,
+    ]);
+    assert!(omni.contains("llama-mtmd-cli -m"));
+    assert!(omni.contains("mmproj-omni-f16.gguf"));
+    assert!(omni.contains(&format!("--audio {} -p transcribe", clip_arg)));
+
+    let spaced = ggufy(&[
+        "--dry-run",
+        "simple",
+        snapshot.join("omni-Q4_K_M.gguf").to_str().unwrap(),
+        &format!("{}x", clip_arg),
+        "say  it\tback",
+        clip_arg,
+    ]);
+    assert!(spaced.contains(&format!(
+        "--audio {} -p {}x say  it\tback",
+        clip_arg, clip_arg
+    )));
+
+    let text = ggufy(&[
+        "--dry-run",
+        "simple",
+        "audio-notes",
+        "summarise",
+        "the",
+        "audio",
+    ]);
+    assert!(text.contains("llama-cli -m"));
+
+    let out = run(
+        env!("CARGO_BIN_EXE_ggufy-simple"),
+        &["audio-notes", "write", "a", "tts", "audio", "script"],
+    );
+    assert!(out.starts_with("llama-cli"));
+    assert!(!out.contains("ollama"));
+}
+
+#[cfg(unix)]
+#[test]
+fn simple_routing_is_shared_and_explained() {
+    use ggufy::config::Route;
+    use ggufy::manifest::OllamaModel;
+    use ggufy::route::{decide, Probe, Target};
+
+    struct Fake {
+        local: bool,
+        cloud: bool,
+        asked_cloud: usize,
+    }
+    impl Probe for Fake {
+        fn local(&mut self, model: &str) -> Option<std::path::PathBuf> {
+            self.local.then(|| format!("/models/{}.gguf", model).into())
+        }
+        fn ollama(&mut self, _: &str, _: &str) -> ggufy::Result<Option<OllamaModel>> {
+            Ok(None)
+        }
+        fn cloud(&mut self, _: &str) -> bool {
+            self.asked_cloud += 1;
+            self.cloud
+        }
+    }
+    let fake = |local, cloud| Fake {
+        local,
+        cloud,
+        asked_cloud: 0,
+    };
+
+    let mut probe = fake(true, true);
+    let d = decide("qwen", None, &mut probe).unwrap();
+    assert!(matches!(d.target, Target::Gguf(_)));
+    assert_eq!(probe.asked_cloud, 0);
+    let d = decide("qwen", Some(Route::Cloud), &mut probe).unwrap();
+    assert!(matches!(d.target, Target::Cloud { .. }));
+    assert!(d.reason.contains("prefers the cloud"));
+    let d = decide("qwen:cloud", None, &mut fake(false, false)).unwrap();
+    assert!(d.reason.contains(":cloud"));
+    let d = decide("qwen:7b", None, &mut fake(false, true)).unwrap();
+    assert!(d.reason.contains("not pulled"));
+    let err = decide("qwen:7b", None, &mut fake(false, false)).unwrap_err();
+    assert_eq!(err.exit_code(), 3);
+    assert_eq!(
+        decide("qwen", None, &mut fake(false, false))
+            .unwrap_err()
+            .exit_code(),
+        3
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-cli", "exit 0");
+    write_script(&bin, "ollama", "exit 1");
+    write_tiny_gguf(&dir.path().join("tiny.gguf"), "llama");
+    let explain = |exe: &str, args: &[&str]| {
+        let out = Command::new(exe)
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args(["--explain", "--dry-run"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        (
+            String::from_utf8_lossy(&out.stderr).to_string(),
+            String::from_utf8_lossy(&out.stdout).to_string(),
+        )
+    };
+    let (why, cmd) = explain(env!("CARGO_BIN_EXE_ggufy"), &["simple", "tiny", "hi"]);
+    assert!(why.contains("route: local gguf"));
+    assert!(why.contains("tool: llama-cli (text model)"));
+    assert!(cmd.contains("llama-cli -m"));
+    let (why2, cmd2) = explain(
+        env!("CARGO_BIN_EXE_ggufy-simple"),
+        &["-m", "tiny", "hi", "there"],
+    );
+    assert_eq!(why, why2);
+    assert!(cmd2.contains("-p hi there"));
+
+    // A bare name is `name:latest` in the Ollama stores for `run` as well.
+    write_script(&bin, "llama-server", "exit 0");
+    let store = dir.path().join("store");
+    write_ollama_model(&store, "qwen", "latest", "qwen2");
+    let routed = |args: &[&str]| {
+        let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args(["--ollama-root", store.to_str().unwrap()])
+            .args(["--explain", "--dry-run", "--port", "auto"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{:?}", out);
+        String::from_utf8_lossy(&out.stderr).to_string()
+    };
+    for args in [&["run", "qwen"][..], &["simple", "qwen", "hi"]] {
+        let why = routed(args);
+        assert!(why.contains("route: ollama store"), "{}", why);
+        assert!(why.contains("qwen:latest is pulled"), "{}", why);
+    }
+    let out = Command::new(env!("CARGO_BIN_EXE_ggufy-simple"))
+        .env("HOME", dir.path())
+        .env("PATH", &bin)
+        .args(["--ollama-root", store.to_str().unwrap()])
+        .args(["--explain", "--dry-run", "qwen", "hi"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{:?}", out);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("qwen:latest is pulled"));
+}
+
+#[cfg(unix)]
+#[test]
+fn simple_reads_prompts_from_stdin_files_and_system() {
+    use std::io::Write;
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-cli", "exit 0");
+    write_script(&bin, "ollama", "exit 0");
+    write_tiny_gguf(&dir.path().join("tiny.gguf"), "llama");
+    let doc = dir.path().join("doc.txt");
+    std::fs::write(&doc, "chapter one\n").unwrap();
+    let run = |exe: &str, args: &[&str], stdin: Option<&str>| {
+        let mut child = Command::new(exe)
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path())
+            .arg("--dry-run")
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut input = child.stdin.take().unwrap();
+        input.write_all(stdin.unwrap_or("").as_bytes()).unwrap();
+        drop(input);
+        child.wait_with_output().unwrap()
+    };
+    let file_arg = |stdout: &[u8], flag: &str| {
+        let line = String::from_utf8_lossy(stdout).to_string();
+        let rest = &line[line.find(flag).unwrap() + flag.len() + 1..];
+        let path = rest.split_whitespace().next().unwrap().to_string();
+        std::fs::read_to_string(path).unwrap()
+    };
+    let ggufy = env!("CARGO_BIN_EXE_ggufy");
+
+    let out = run(
+        ggufy,
+        &["simple", "tiny", "review", "this"],
+        Some("+a\n+b\n"),
+    );
+    assert!(out.status.success());
+    assert_eq!(file_arg(&out.stdout, "--file"), "review this\n\n+a\n+b");
+    assert!(String::from_utf8_lossy(&out.stdout).contains("-no-cnv"));
+
+    let out = run(
+        ggufy,
+        &[
+            "simple",
+            "--prompt-file",
+            doc.to_str().unwrap(),
+            "--system",
+            "be terse",
+            "tiny",
+            "summarise",
+        ],
+        None,
+    );
+    let line = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(line.contains("--system-prompt be terse --single-turn"));
+    assert!(!line.contains("-no-cnv"));
+    assert_eq!(file_arg(&out.stdout, "--file"), "summarise\n\nchapter one");
+
+    // Both go through files when both span lines; they must not share one.
+    let out = run(
+        ggufy,
+        &["simple", "--system", "be\nterse", "tiny", "-"],
+        Some("two\nlines"),
+    );
+    assert_eq!(file_arg(&out.stdout, "--system-prompt-file"), "be\nterse");
+    assert_eq!(file_arg(&out.stdout, "--file"), "two\nlines");
+
+    let out = run(
+        env!("CARGO_BIN_EXE_ggufy-simple"),
+        &["tiny", "before", "-", "after"],
+        Some("middle"),
+    );
+    assert_eq!(file_arg(&out.stdout, "--file"), "before\n\nmiddle\n\nafter");
+
+    let big = "x".repeat(4096);
+    let out = run(ggufy, &["simple", "qwen:cloud", "-"], Some(&big));
+    let line = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(line.contains("ollama run qwen:cloud < "));
+    assert_eq!(file_arg(&out.stdout, "<"), big);
+
+    let out = run(ggufy, &["simple", "tiny"], None);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("missing prompt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn simple_json_and_ndjson_capture_text_and_stats() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(
+        &bin,
+        "llama-cli",
+        "case \"$*\" in *--no-display-prompt*) ;; *) exit 9;; esac\n\
+         printf 'loading model\\n' >&2\n\
+         printf 'Hello, '\n\
+         printf 'wörld! [end of text]\\n'\n\
+         printf '%s\\n' \\\n\
+         'llama_perf_context_print: prompt eval time =      10.00 ms /     5 tokens (    2.00 ms per token,   500.00 tokens per second)' \\\n\
+         'llama_perf_context_print:        eval time =     200.00 ms /    20 runs   (   10.00 ms per token,   100.00 tokens per second)' \\\n\
+         'llama_perf_context_print:       total time =     215.50 ms /    25 tokens' >&2",
+    );
+    write_script(
+        &bin,
+        "ollama",
+        "[ \"$1\" = show ] && exit 0\n\
+         printf 'cloud says hi\\n'\n\
+         printf 'prompt eval count:    7 token(s)\\nprompt eval duration: 1.5ms\\neval count:           4 token(s)\\neval duration:        1m0.5s\\ntotal duration:       2.5s\\n' >&2",
+    );
+    write_tiny_gguf(&dir.path().join("tiny.gguf"), "llama");
+    let ggufy = |args: &[&str]| {
+        let out = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{:?}", out);
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let v: serde_json::Value =
+        serde_json::from_str(&ggufy(&["--json", "simple", "tiny", "hi"])).unwrap();
+    assert_eq!(v["model"], "tiny");
+    assert_eq!(v["backend"], "llama.cpp");
+    assert_eq!(v["tool"], "llama-cli");
+    assert_eq!(v["text"], "Hello, wörld!");
+    assert_eq!(v["prompt_tokens"], 5);
+    assert_eq!(v["completion_tokens"], 20);
+    assert_eq!(v["completion_ms"], 200.0);
+    assert_eq!(v["total_ms"], 215.5);
+    assert_eq!(v["tokens_per_second"], 100.0);
+    assert!(v["wall_ms"].as_f64().unwrap() > 0.0);
+
+    let lines: Vec<serde_json::Value> = ggufy(&["simple", "--stream", "ndjson", "tiny", "hi"])
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    let (done, chunks) = lines.split_last().unwrap();
+    assert_eq!(done["type"], "done");
+    assert_eq!(done["text"], "Hello, wörld!");
+    let streamed: String = chunks
+        .iter()
+        .inspect(|c| assert_eq!(c["type"], "chunk"))
+        .map(|c| c["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(streamed, "Hello, wörld!");
+
+    let v: serde_json::Value =
+        serde_json::from_str(&ggufy(&["--json", "simple", "qwen:cloud", "hi"])).unwrap();
+    assert_eq!(v["backend"], "ollama");
+    assert_eq!(v["text"], "cloud says hi");
+    assert_eq!(v["prompt_tokens"], 7);
+    assert_eq!(v["prompt_ms"], 1.5);
+    assert_eq!(v["completion_ms"], 60500.0);
+    assert_eq!(v["total_ms"], 2500.0);
+}
+
+// Reads one HTTP/1.1 message: the head (request or status line plus
+// headers) and its body.
+fn read_http(stream: &mut std::net::TcpStream) -> (String, String) {
+    use std::io::Read;
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let end = loop {
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i;
+        }
+        let n = stream.read(&mut chunk).unwrap();
+        if n == 0 {
+            break buf.len();
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..end]).to_string();
+    let len = head
+        .lines()
+        .find_map(|l| {
+            l.to_lowercase()
+                .strip_prefix("content-length:")
+                .map(|v| v.trim().to_string())
+        })
+        .and_then(|v| v.parse::<usize>().ok());
+    let mut body = buf[(end + 4).min(buf.len())..].to_vec();
+    match len {
+        Some(len) => {
+            while body.len() < len {
+                let n = stream.read(&mut chunk).unwrap();
+                if n == 0 {
+                    break;
+                }
+                body.extend_from_slice(&chunk[..n]);
+            }
+        }
+        // Responses without a length run to the end of the connection.
+        None if head.starts_with("HTTP/") => {
+            let _ = stream.read_to_end(&mut body);
+        }
+        None => {}
+    }
+    if head.to_lowercase().contains("transfer-encoding: chunked") {
+        body = dechunk(&body);
+    }
+    (head, String::from_utf8_lossy(&body).to_string())
+}
+
+fn dechunk(mut raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(i) = raw.windows(2).position(|w| w == b"\r\n") {
+        let size =
+            usize::from_str_radix(std::str::from_utf8(&raw[..i]).unwrap().trim(), 16).unwrap();
+        if size == 0 {
+            break;
+        }
+        out.extend_from_slice(&raw[i + 2..i + 2 + size]);
+        raw = &raw[i + 4 + size..];
+    }
+    out
+}
+
+// Answers /health, and completions with a JSON echo or, chunked like
+// llama-server, an SSE stream whose second piece describes the request.
+fn fake_openai_server(listener: std::net::TcpListener, name: &'static str) {
+    use std::io::Write;
+    for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let (head, body) = read_http(&mut stream);
+        let path = head.split_whitespace().nth(1).unwrap_or("").to_string();
+        let req: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+        if req["stream"] == true {
+            let mut about: Vec<String> = req["messages"]
+                .as_array()
+                .map(|m| {
+                    m.iter()
+                        .map(|m| m["role"].as_str().unwrap().to_string())
+                        .collect()
+                })
+                .unwrap_or_else(|| vec!["raw".to_string()]);
+            if let Some(n) = req["max_tokens"].as_u64() {
+                about.push(format!("max={}", n));
+            }
+            let events = [
+                serde_json::json!({"choices": [{"delta": {"content": "hi"}}]}),
+                serde_json::json!({
+                    "choices": [{"delta": {"content": format!(" {}", about.join(" "))}, "finish_reason": "stop"}],
+                    "timings": {"prompt_n": 3, "prompt_ms": 1.5, "predicted_n": 2, "predicted_ms": 4.0},
+                }),
+            ];
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n"
+            );
+            for data in events
+                .iter()
+                .map(|e| format!("data: {}\n\n", e))
+                .chain(["data: [DONE]\n\n".to_string()])
+            {
+                let _ = write!(stream, "{:x}\r\n{}\r\n", data.len(), data);
+            }
+            let _ = write!(stream, "0\r\n\r\n");
+            continue;
+        }
+        let reply = if path == "/health" {
+            "{}".to_string()
+        } else {
+            serde_json::json!({"server": name, "path": path, "model": req["model"]}).to_string()
+        };
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n{}",
+            reply
+        );
+    }
+}
+
+// Not a test on its own: `gateway_routes_openai_requests_by_model` runs this
+// test binary as its llama-server, which lands here with the port set.
+#[test]
+fn gateway_fake_llama_server() {
+    let Ok(port) = std::env::var("GGUFY_FAKE_LLAMA_PORT") else {
+        return;
+    };
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port.parse().unwrap())).unwrap();
+    fake_openai_server(listener, "llama-server");
+}
+
+// Starts `ggufy gateway` on a free port, with this test binary standing in
+// for llama-server and `dir` holding HOME, bin/, links/ and state/.
+#[cfg(unix)]
+fn spawn_gateway(
+    dir: &std::path::Path,
+    envs: &[(&str, String)],
+    args: &[&str],
+) -> (std::process::Child, u16) {
+    let exe = std::env::current_exe().unwrap();
+    write_script(
+        &dir.join("bin"),
+        "llama-server",
+        &format!(
+            "while [ $# -gt 0 ]; do [ \"$1\" = --port ] && port=$2; shift; done\n\
+             GGUFY_FAKE_LLAMA_PORT=$port exec '{}' gateway_fake_llama_server --exact --nocapture",
+            exe.display()
+        ),
+    );
+    let port = free_ports(1);
+    let gateway = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", dir)
+        .env("PATH", dir.join("bin"))
+        .env("GGUFY_STATE_DIR", dir.join("state"))
+        .envs(
+            envs.iter()
+                .map(|(k, v)| (k, v.replace("{port}", &port.to_string()))),
+        )
+        .arg("--link-dir")
+        .arg(dir.join("links"))
+        .args(["--port", &port.to_string()])
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    ggufy::health::wait_ready(
+        "127.0.0.1",
+        port,
+        std::time::Duration::from_secs(10),
+        || true,
+    )
+    .unwrap();
+    (gateway, port)
+}
+
+fn gateway_call(port: u16, method: &str, path: &str, body: &str) -> (u16, String) {
+    use std::io::Write;
+    let mut s = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        s,
+        "{} {} HTTP/1.1\r\nHost: x\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let (head, body) = read_http(&mut s);
+    let status: u16 = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, body)
+}
+
+// `ggufy ps --json` against the state dir of `spawn_gateway`.
+fn gateway_ps(dir: &std::path::Path) -> Vec<serde_json::Value> {
+    let ps = Command::new(env!("CARGO_BIN_EXE_ggufy"))
+        .env("HOME", dir)
+        .env("GGUFY_STATE_DIR", dir.join("state"))
+        .args(["ps", "--json"])
+        .output()
+        .unwrap();
+    let ps: serde_json::Value = serde_json::from_slice(&ps.stdout).unwrap();
+    ps.as_array().unwrap().clone()
+}
+
+#[cfg(unix)]
+#[test]
+fn gateway_routes_openai_requests_by_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let links = dir.path().join("links");
+    let store = dir.path().join("ollama");
+    std::fs::create_dir_all(&links).unwrap();
+    write_tiny_gguf(&links.join("tiny.gguf"), "llama");
+    write_ollama_model(&store, "gemma3", "4b", "gemma3");
+
+    let cloud = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let cloud_port = cloud.local_addr().unwrap().port();
+    std::thread::spawn(move || fake_openai_server(cloud, "ollama"));
+
+    let (mut gateway, port) = spawn_gateway(
+        dir.path(),
+        &[
+            ("OLLAMA_MODELS", store.display().to_string()),
+            ("OLLAMA_HOST", format!("http://127.0.0.1:{}", cloud_port)),
+        ],
+        &["--port-range", "18500-18520", "gateway"],
+    );
+    let call = |method: &str, path: &str, body: &str| gateway_call(port, method, path, body);
+
+    let (status, body) = call("GET", "/v1/models", "");
+    assert_eq!(status, 200);
+    let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let ids: Vec<&str> = v["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["tiny", "gemma3:4b"]);
+
+    // Local GGUF: a llama-server is started on an internal port.
+    let (status, body) = call(
+        "POST",
+        "/v1/chat/completions",
+        r#"{"model":"tiny","messages":[{"role":"user","content":"hi"}]}"#,
+    );
+    assert_eq!(status, 200, "{}", body);
+    let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["server"], "llama-server");
+    assert_eq!(v["path"], "/v1/chat/completions");
+    let ps = gateway_ps(dir.path());
+    assert_eq!(ps.len(), 1);
+    assert_eq!(ps[0]["model"], "tiny");
+    assert!((18500..=18520).contains(&ps[0]["port"].as_u64().unwrap()));
+
+    // The same model reuses its server; SSE passes through untouched.
+    let (status, body) = call(
+        "POST",
+        "/v1/completions",
+        r#"{"model":"tiny","prompt":"hi","stream":true}"#,
+    );
+    assert_eq!(status, 200);
+    assert!(body.starts_with("data: {"));
+    assert!(body.ends_with("data: [DONE]\n\n"));
+
+    // Cloud tags go to the Ollama daemon.
+    let (status, body) = call(
+        "POST",
+        "/v1/chat/completions",
+        r#"{"model":"qwen3:cloud","messages":[]}"#,
+    );
+    assert_eq!(status, 200);
+    let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["server"], "ollama");
+    assert_eq!(v["model"], "qwen3:cloud");
+
+    let (status, body) = call("POST", "/v1/chat/completions", r#"{"model":"nope"}"#);
+    assert_eq!(status, 404);
+    assert!(body.contains("model not found: nope"));
+    let (status, _) = call("POST", "/v1/completions", "not json");
+    assert_eq!(status, 400);
+
+    // Stopping the gateway stops the servers it started.
+    Command::new("kill")
+        .arg(gateway.id().to_string())
+        .status()
+        .unwrap();
+    gateway.wait().unwrap();
+    assert!(gateway_ps(dir.path()).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn gateway_evicts_idle_and_least_recently_used_servers() {
+    let dir = tempfile::tempdir().unwrap();
+    let links = dir.path().join("links");
+    std::fs::create_dir_all(&links).unwrap();
+    for name in ["keep", "b", "c"] {
+        write_tiny_gguf(&links.join(format!("{}.gguf", name)), "llama");
+    }
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[models.keep]\npin = true\n").unwrap();
+    let (mut gateway, port) = spawn_gateway(
+        dir.path(),
+        &[("GGUFY_CONFIG", config.display().to_string())],
+        &[
+            "--port-range",
+            "18540-18560",
+            "gateway",
+            "--max-models",
+            "2",
+            "--idle-minutes",
+            "0.05",
+        ],
+    );
+    let ask = |model: &str| {
+        let body = format!(r#"{{"model":"{}","prompt":"hi"}}"#, model);
+        assert_eq!(gateway_call(port, "POST", "/v1/completions", &body).0, 200);
+    };
+    let loaded = || {
+        let mut models: Vec<String> = gateway_ps(dir.path())
+            .iter()
+            .map(|i| i["model"].as_str().unwrap().to_string())
+            .collect();
+        models.sort();
+        models
+    };
+
+    ask("keep");
+    ask("b");
+    assert_eq!(loaded(), ["b", "keep"]);
+    // Over the cap: the least recently used unpinned server makes way.
+    ask("keep");
+    ask("c");
+    assert_eq!(loaded(), ["c", "keep"]);
+
+    // After three idle seconds only the pinned one is left.
+    let mut left = loaded();
+    for _ in 0..100 {
+        if left == ["keep"] {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        left = loaded();
+    }
+    assert_eq!(left, ["keep"]);
+
+    Command::new("kill")
+        .arg(gateway.id().to_string())
+        .status()
+        .unwrap();
+    gateway.wait().unwrap();
+    assert!(gateway_ps(dir.path()).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn gateway_shutdown_stops_servers_still_starting() {
+    use std::io::Write;
+    let dir = tempfile::tempdir().unwrap();
+    let links = dir.path().join("links");
+    std::fs::create_dir_all(&links).unwrap();
+    write_tiny_gguf(&links.join("slow.gguf"), "llama");
+    let (mut gateway, port) = spawn_gateway(
+        dir.path(),
+        &[],
+        &["--ready-timeout", "60", "gateway", "--max-models", "1"],
+    );
+    // A server that never becomes ready keeps its slot locked.
+    write_script(
+        &dir.path().join("bin"),
+        "llama-server",
+        "exec /bin/sleep 60",
+    );
+    let mut s = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let body = r#"{"model":"slow","prompt":"hi"}"#;
+    write!(
+        s,
+        "POST /v1/completions HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut ps = gateway_ps(dir.path());
+    for _ in 0..100 {
+        if !ps.is_empty() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        ps = gateway_ps(dir.path());
+    }
+    assert_eq!(ps.len(), 1);
+
+    Command::new("kill")
+        .arg(gateway.id().to_string())
+        .status()
+        .unwrap();
+    let mut exited = None;
+    for _ in 0..100 {
+        exited = gateway.try_wait().unwrap();
+        if exited.is_some() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    if exited.is_none() {
+        gateway.kill().unwrap();
+        panic!("gateway did not exit while a server was starting");
+    }
+    assert!(gateway_ps(dir.path()).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn ollama_api_is_served_from_llama_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let links = dir.path().join("links");
+    let store = dir.path().join("ollama");
+    std::fs::create_dir_all(&links).unwrap();
+    write_tiny_gguf(&links.join("tiny.gguf"), "llama");
+    write_ollama_model(&store, "gemma3", "4b", "gemma3");
+    let (mut gateway, port) = spawn_gateway(
+        dir.path(),
+        &[("OLLAMA_MODELS", store.display().to_string())],
+        &["--port-range", "18580-18600", "ollama", "api"],
+    );
+    let call = |method: &str, path: &str, body: &str| gateway_call(port, method, path, body);
+    let json = |body: &str| serde_json::from_str::<serde_json::Value>(body).unwrap();
+
+    assert_eq!(call("GET", "/", "").1, "Ollama is running");
+    let tags = json(&call("GET", "/api/tags", "").1);
+    let names: Vec<&str> = tags["models"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["tiny:latest", "gemma3:4b"]);
+    assert_eq!(tags["models"][0]["details"]["family"], "llama");
+    assert_eq!(tags["models"][1]["details"]["format"], "gguf");
+
+    let (status, body) = call("POST", "/api/show", r#"{"model":"gemma3:4b"}"#);
+    assert_eq!(status, 200);
+    let show = json(&body);
+    assert_eq!(show["model_info"]["general.architecture"], "gemma3");
+    assert_eq!(show["capabilities"][0], "completion");
+
+    // Streamed by default, one JSON object per line.
+    let (status, body) = call(
+        "POST",
+        "/api/generate",
+        r#"{"model":"tiny","prompt":"hello","system":"be brief","options":{"num_predict":5}}"#,
+    );
+    assert_eq!(status, 200);
+    let lines: Vec<serde_json::Value> = body.lines().map(json).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["response"], "hi");
+    assert_eq!(lines[1]["response"], " system user max=5");
+    assert_eq!(lines[2]["done"], true);
+    assert_eq!(lines[2]["done_reason"], "stop");
+    assert_eq!(lines[2]["prompt_eval_count"], 3);
+    assert_eq!(lines[2]["eval_count"], 2);
+    assert_eq!(lines[2]["eval_duration"], 4_000_000);
+
+    let (status, body) = call(
+        "POST",
+        "/api/chat",
+        r#"{"model":"tiny:latest","stream":false,"messages":[{"role":"user","content":"yo"}]}"#,
+    );
+    assert_eq!(status, 200);
+    let chat = json(&body);
+    assert_eq!(chat["message"]["content"], "hi user");
+    assert_eq!(chat["done"], true);
+
+    let ps = json(&call("GET", "/api/ps", "").1);
+    assert_eq!(ps["models"].as_array().unwrap().len(), 1);
+    assert_eq!(ps["models"][0]["details"]["family"], "llama");
+
+    // `keep_alive: 0` with no prompt unloads, like `ollama stop`.
+    let unload = json(
+        &call(
+            "POST",
+            "/api/generate",
+            r#"{"model":"tiny","keep_alive":0}"#,
+        )
+        .1,
+    );
+    assert_eq!(unload["done_reason"], "unload");
+    assert!(gateway_ps(dir.path()).is_empty());
+
+    let (status, body) = call("POST", "/api/chat", r#"{"model":"nope","messages":[]}"#);
+    assert_eq!(status, 404);
+    assert!(json(&body)["error"].as_str().unwrap().contains("nope"));
+
+    Command::new("kill")
+        .arg(gateway.id().to_string())
+        .status()
+        .unwrap();
+    gateway.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn fit_estimates_memory_and_guards_server_launches() {
+    use serde_json::json;
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    write_script(&bin, "llama-server", "echo \"llama-server $@\"");
+    let model = dir.path().join("big.gguf");
+    // 32 layers x 8 KV heads x (128 + 128) f16 values: 128 KiB per token.
+    write_gguf_with(
+        &model,
+        &[
+            ("general.architecture", json!("llama")),
+            ("llama.context_length", json!(32768)),
+            ("llama.block_count", json!(32)),
+            ("llama.embedding_length", json!(4096)),
+            ("llama.attention.head_count", json!(32)),
+            ("llama.attention.head_count_kv", json!(8)),
+        ],
+        "token_embd.weight",
+    );
+    let meminfo = dir.path().join("meminfo");
+    std::fs::write(
+        &meminfo,
+        "MemTotal:        4194304 kB\nMemFree:          524288 kB\nMemAvailable:    2097152 kB\n",
+    )
+    .unwrap();
+    let ggufy = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_ggufy"))
+            .env("HOME", dir.path())
+            .env("PATH", &bin)
+            .env("GGUFY_STATE_DIR", dir.path().join("state"))
+            .env("GGUFY_MEMINFO", &meminfo)
+            .env_remove("GGUFY_CONFIG")
+            .arg("--link-dir")
+            .arg(dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let model = model.to_str().unwrap();
+
+    let out = ggufy(&["fit", model, "--ctx", "8192", "--json"]);
+    assert!(out.status.success());
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(v["kv_per_token"], 128 * 1024);
+    assert_eq!(v["kv_cache"], 1u64 << 30);
+    assert_eq!(v["ram_available"], 2u64 << 30);
+    assert_eq!(v["fits"], true);
+    // (2 GiB - 512 MiB overhead - 16 B of weights) / 128 KiB, in steps of 256.
+    assert_eq!(v["largest_ctx"], 12032);
+
+    // A q8_0 cache is 34 bytes per 32 values instead of 64.
+    let out = ggufy(&[
+        "--json", "fit", model, "--ctx", "8192", "--", "-ctk", "q8_0", "-ctv", "q8_0",
+    ]);
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(v["kv_per_token"], 32 * 8 * 2 * 4 * 34);
+
+    let out = ggufy(&["fit", model, "--ctx", "16384"]);
+    assert_eq!(out.status.code(), Some(8));
+    let text = String::from_utf8_lossy(&out.stdout).to_string();
+    assert!(text.contains("does not fit; largest context that fits: 12032"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--ctx-size 12032 fits"));
+
+    // Launches are refused before llama-server starts, unless told to
+    // ignore the estimate; `--force` is only about links.
+    let out = ggufy(&["-c", "serve", model, "--", "-c", "16384"]);
+    assert_eq!(out.status.code(), Some(8));
+    assert!(out.stdout.is_empty());
+    let out = ggufy(&["-c", "--force", "serve", model, "--", "-c", "16384"]);
+    assert_eq!(out.status.code(), Some(8));
+    let out = ggufy(&["-c", "serve", model, "--", "-c", &u64::MAX.to_string()]);
+    assert_eq!(out.status.code(), Some(8));
+    let out = ggufy(&[
+        "-c",
+        "--no-wait",
+        "--ignore-fit",
+        "serve",
+        model,
+        "--",
+        "-c",
+        "16384",
+    ]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("llama-server -m"));
+
+    // Free VRAM from nvidia-smi counts toward the budget.
+    write_script(&bin, "nvidia-smi", "echo \"6144, 8192\"");
+    let out = ggufy(&["--json", "fit", model, "--ctx", "16384"]);
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(v["vram_free"], 6u64 << 30);
+    assert_eq!(v["fits"], true);
+    assert!(
+        ggufy(&["-c", "--no-wait", "serve", model, "--", "-c", "16384"])
+            .status
+            .success()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn gateway_does_not_forward_cloud_models_to_itself() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("links")).unwrap();
+    // The cloud probe (`ollama show`) would reach the gateway too.
+    let calls = dir.path().join("ollama-calls");
+    write_script(
+        &dir.path().join("bin"),
+        "ollama",
+        &format!("echo \"$@\" >> '{}'\nexit 1", calls.display()),
+    );
+    let (mut gateway, port) = spawn_gateway(
+        dir.path(),
+        &[("OLLAMA_HOST", "http://127.0.0.1:{port}".to_string())],
+        &["--port-range", "18620-18640", "ollama", "api"],
+    );
+
+    let (status, body) = gateway_call(
+        port,
+        "POST",
+        "/v1/chat/completions",
+        r#"{"model":"gpt-oss:cloud","messages":[]}"#,
+    );
+    assert_eq!(status, 502);
+    assert!(body.contains("no upstream Ollama"));
+    let (status, body) = gateway_call(port, "POST", "/api/chat", r#"{"model":"gpt-oss:cloud"}"#);
+    assert_eq!(status, 502);
+    assert!(body.contains("no upstream Ollama"));
+    let (status, _) = gateway_call(port, "POST", "/api/show", r#"{"model":"nope"}"#);
+    assert_eq!(status, 404);
+    assert!(!calls.exists());
+
+    Command::new("kill")
+        .arg(gateway.id().to_string())
+        .status()
+        .unwrap();
+    gateway.wait().unwrap();
+}